@@ -0,0 +1,14 @@
+#![no_main]
+// Extraction into memory, covering chunk assembly, decompression, name assignment and nesting
+use libfuzzer_sys::fuzz_target;
+use pfsextractor::{extract_in_memory, Options};
+
+fuzz_target!(|data: &[u8]| {
+    // Small limit keeps crafted zlib bombs from exhausting the fuzzer's memory
+    let options = Options::builder()
+        .jobs(1)
+        .max_decompressed_size(0x1000000)
+        .manifest(true)
+        .build();
+    let _ = extract_in_memory(&options, data);
+});
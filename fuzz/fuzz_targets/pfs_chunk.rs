@@ -0,0 +1,9 @@
+#![no_main]
+// Chunk header and metadata of chunked subsections
+use libfuzzer_sys::fuzz_target;
+use pfsextractor::parser;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = parser::pfs_chunk(data);
+    let _ = parser::pfs_chunk_metadata(data);
+});
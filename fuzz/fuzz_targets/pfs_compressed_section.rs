@@ -0,0 +1,10 @@
+#![no_main]
+// Compressed section header, the zlib stream itself is left to flate2
+use libfuzzer_sys::fuzz_target;
+use pfsextractor::parser;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = parser::pfs_compressed_section(data);
+    let _ = parser::find_compressed_offsets(data);
+    let _ = parser::classify(data);
+});
@@ -0,0 +1,23 @@
+#![no_main]
+// Whole file parsing and everything computed from parsed sections
+use libfuzzer_sys::fuzz_target;
+use pfsextractor::{extract, parser, verify};
+use pfsextractor::guids::GuidNames;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok((rest, mut file)) = parser::pfs_file(data) {
+        extract::pfs_set_names(&mut file, &GuidNames::default());
+        let _ = file.capsule_type();
+        let _ = verify::pfs_file_checksum(data, &file);
+        for section in &file.sections {
+            let _ = section.version.to_string();
+            let _ = section.decode_unknown();
+            let _ = parser::section_kind(section.data.unwrap_or(&[]));
+        }
+        let _ = parser::trailing_kind(rest);
+    }
+    let _ = parser::pfs_headerless(data);
+    let _ = parser::pfs_truncated(data);
+    let _ = parser::pfs_error_offset(data);
+    let _ = parser::find_pfs_offsets(data);
+});
@@ -0,0 +1,10 @@
+#![no_main]
+// Information section and model properties, both hold UTF-16 text of any length
+use libfuzzer_sys::fuzz_target;
+use pfsextractor::parser;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = parser::pfs_info(data);
+    let _ = parser::pfs_model_properties(data);
+    let _ = parser::pfs_section_metadata(data);
+});
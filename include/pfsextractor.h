@@ -0,0 +1,127 @@
+#ifndef PFSEXTRACTOR_H
+#define PFSEXTRACTOR_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+// Files extracted into memory
+typedef struct PfsFiles PfsFiles;
+
+// Parsed PFS file, doesn't borrow the buffer it was parsed from
+typedef struct PfsHandle PfsHandle;
+
+// GUID in the layout of EFI_GUID
+typedef struct PfsGuid {
+  uint32_t data1;
+  uint16_t data2;
+  uint16_t data3;
+  uint8_t data4[8];
+} PfsGuid;
+
+// Section of a parsed PFS file, strings are UTF-8 and owned by the handle
+typedef struct PfsSectionInfo {
+  struct PfsGuid guid;
+  // Name from the information section, empty if there is none
+  const char *name;
+  // Version as printed by the command line tool, like "1.2.3"
+  const char *version;
+  uint32_t header_version;
+  uint32_t data_size;
+  uint32_t data_sig_size;
+  uint32_t meta_size;
+  uint32_t meta_sig_size;
+  // Offset of section data in the parsed buffer
+  uint64_t data_offset;
+} PfsSectionInfo;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Parses a PFS file, returns NULL if the data is not a valid PFS file.
+// The handle has to be released with pfs_free.
+//
+// # Safety
+// `data` must point to `len` readable bytes.
+struct PfsHandle *pfs_parse(const uint8_t *data, size_t len);
+
+// Releases a handle returned by pfs_parse, NULL is ignored.
+//
+// # Safety
+// `handle` must be NULL or a handle not released yet.
+void pfs_free(struct PfsHandle *handle);
+
+// Number of sections of the parsed file, 0 for NULL.
+//
+// # Safety
+// `handle` must be NULL or a handle returned by pfs_parse.
+size_t pfs_section_count(const struct PfsHandle *handle);
+
+// Fills `out` with the section at `index`, returns false if there is no such section.
+// Strings stay valid until the handle is released.
+//
+// # Safety
+// `handle` must be NULL or a handle returned by pfs_parse, `out` must be NULL or writable.
+bool pfs_section_info(const struct PfsHandle *handle, size_t index, struct PfsSectionInfo *out);
+
+// Extracts everything the command line tool would into `dir`, which is created if needed.
+// Returns the exit code the command line tool would, 0 on success, see README.md for the others.
+//
+// # Safety
+// `data` must point to `len` readable bytes, `dir` must be a NUL-terminated UTF-8 path.
+int32_t pfs_extract_to_dir(const uint8_t *data, size_t len, const char *dir);
+
+// Extracts everything the command line tool would into memory, for callers without a filesystem.
+// Returns NULL if `data` is NULL, files have to be released with pfs_files_free.
+//
+// # Safety
+// `data` must point to `len` readable bytes.
+struct PfsFiles *pfs_extract_to_memory(const uint8_t *data, size_t len);
+
+// Exit code the command line tool would return for the extraction, 0 for NULL.
+//
+// # Safety
+// `files` must be NULL or returned by pfs_extract_to_memory.
+int32_t pfs_files_status(const struct PfsFiles *files);
+
+// Number of extracted files, 0 for NULL.
+//
+// # Safety
+// `files` must be NULL or returned by pfs_extract_to_memory.
+size_t pfs_files_count(const struct PfsFiles *files);
+
+// Name of the file at `index`, NULL if there is none.
+// Nested sections are written with their parent names as prefix, like in the output directory.
+//
+// # Safety
+// `files` must be NULL or returned by pfs_extract_to_memory.
+const char *pfs_files_name(const struct PfsFiles *files, size_t index);
+
+// Data of the file at `index` with its size stored into `size`, NULL if there is none.
+//
+// # Safety
+// `files` must be NULL or returned by pfs_extract_to_memory, `size` must be writable.
+const uint8_t *pfs_files_data(const struct PfsFiles *files, size_t index, size_t *size);
+
+// Releases files returned by pfs_extract_to_memory, NULL is ignored.
+//
+// # Safety
+// `files` must be NULL or not released yet.
+void pfs_files_free(struct PfsFiles *files);
+
+// Allocates `len` bytes to pass input data in, for WebAssembly callers that can't allocate
+// in its memory otherwise. Released with pfs_buffer_free.
+uint8_t *pfs_buffer_alloc(size_t len);
+
+// Releases a buffer returned by pfs_buffer_alloc with the same `len`, NULL is ignored.
+//
+// # Safety
+// `buffer` must be NULL or returned by pfs_buffer_alloc with this `len` and not released yet.
+void pfs_buffer_free(uint8_t *buffer, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* PFSEXTRACTOR_H */
@@ -0,0 +1,228 @@
+use extract::{self, pfs_set_names};
+use guids::GuidNames;
+use parser::{self, PFS_SECTION_HEADER_SIZE};
+use std::str::FromStr;
+
+//
+// Structure descriptions with the offsets of one particular file, for hex editors
+//
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnotateFormat {
+    Ksy, // Kaitai Struct
+    Bt,  // 010 Editor binary template
+}
+
+impl FromStr for AnnotateFormat {
+    type Err = String;
+
+    fn from_str(s : &str) -> Result<AnnotateFormat, String> {
+        match s {
+            "ksy" => Ok(AnnotateFormat::Ksy),
+            "bt" => Ok(AnnotateFormat::Bt),
+            _ => Err(String::from("expected ksy or bt")),
+        }
+    }
+}
+
+impl AnnotateFormat {
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            AnnotateFormat::Ksy => "ksy",
+            AnnotateFormat::Bt => "bt",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegionKind {
+    Header,
+    SectionHeader,
+    Footer,
+    Bytes, // Section data, signatures and metadata
+}
+
+// Part of the input at a known offset, ids are unique and usable as identifiers in both formats
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    pub id : String,
+    pub offset : usize,
+    pub size : usize,
+    pub kind : RegionKind,
+    pub comment : String,
+}
+
+fn pfs_regions(data : &[u8], base : usize, prefix : &str, comment : &str, depth : usize, regions : &mut Vec<Region>) {
+    let mut file = match parser::pfs_file(data) {
+        Ok((_, file)) => file,
+        Err(_) => return,
+    };
+    pfs_set_names(&mut file, &GuidNames::default());
+    if let Some(header) = file.header_range() {
+        regions.push(Region { id : format!("{}header", prefix), offset : base + header.start, size : header.len(), kind : RegionKind::Header,
+            comment : format!("{}PFS header, version {:X}", comment, file.header.header_version) });
+    }
+
+    let mut offset = base + file.sections_range().start;
+    for (i, section) in file.iter().enumerate() {
+        let id = format!("{}section_{}", prefix, i + 1);
+        let name = if section.name.is_empty() { String::from("Unnamed section") } else { section.name.clone() };
+        let label = format!("{}{} {} {}", comment, i + 1, name, section.guid);
+        regions.push(Region { id : format!("{}_header", id), offset, size : PFS_SECTION_HEADER_SIZE, kind : RegionKind::SectionHeader,
+            comment : format!("{}, version {}", label, section.version) });
+        offset += PFS_SECTION_HEADER_SIZE;
+
+        let parts = [("data", section.data), ("data_sig", section.data_sig), ("meta", section.meta), ("meta_sig", section.meta_sig)];
+        for &(part, blob) in parts.iter() {
+            let blob = match blob {
+                Some(blob) => blob,
+                None => continue,
+            };
+            regions.push(Region { id : format!("{}_{}", id, part), offset, size : blob.len(), kind : RegionKind::Bytes,
+                comment : format!("{}, {}", label, part.replace('_', " ")) });
+            // Subsections made of chunks are PFS files stored as they are, so their offsets are known too
+            if part == "data" && depth < extract::MAX_DEPTH && parser::pfs_file(blob).is_ok() {
+                pfs_regions(blob, offset, &format!("{}_pfs_", id), &format!("{}{} / ", comment, i + 1), depth + 1, regions);
+            }
+            offset += blob.len();
+        }
+    }
+    if let Some(footer) = file.footer_range() {
+        regions.push(Region { id : format!("{}footer", prefix), offset : base + footer.start, size : footer.len(), kind : RegionKind::Footer,
+            comment : format!("{}PFS footer, checksum {:08X}", comment, file.footer.checksum) });
+    }
+}
+
+// Every PFS file found in the input with its sections, nested ones only when they are not compressed
+pub fn regions(data : &[u8]) -> Vec<Region> {
+    let offsets = parser::find_pfs_offsets(data);
+    let mut regions = Vec::new();
+    for (n, &offset) in offsets.iter().enumerate() {
+        let (prefix, comment) = if offsets.len() > 1 { (format!("pfs_{}_", n + 1), format!("PFS file {}: ", n + 1)) } else { (String::new(), String::new()) };
+        pfs_regions(&data[offset..], offset, &prefix, &comment, 0, &mut regions);
+    }
+    regions
+}
+
+fn quoted(text : &str) -> String {
+    let text : String = text.chars().map(|c| if c.is_control() { ' ' } else { c }).collect();
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+const BT_TYPES : &str = "typedef struct {
+    char signature[8];
+    uint32 header_version;
+    uint32 data_size;
+} PFS_HEADER;
+
+typedef struct {
+    uchar guid[16];
+    uint32 header_version;
+    char version_types[4];
+    uint16 version[4];
+    uint64 reserved;
+    uint32 data_size;
+    uint32 data_sig_size;
+    uint32 meta_size;
+    uint32 meta_sig_size;
+    uchar unknown[16];
+} PFS_SECTION_HEADER;
+
+typedef struct {
+    uint32 data_size;
+    uint32 checksum;
+    char signature[8];
+} PFS_FOOTER;
+";
+
+fn bt(regions : &[Region], input : &str) -> String {
+    let mut out = format!("// 010 Editor template generated by PFSExtractor-RS {} from {}\n", env!("CARGO_PKG_VERSION"), input);
+    out.push_str("// Offsets are the ones of this file, other files need a template of their own\n");
+    out.push_str("LittleEndian();\n\n");
+    out.push_str(BT_TYPES);
+    out.push('\n');
+    for region in regions {
+        let declaration = match region.kind {
+            RegionKind::Header => format!("PFS_HEADER {}", region.id),
+            RegionKind::SectionHeader => format!("PFS_SECTION_HEADER {}", region.id),
+            RegionKind::Footer => format!("PFS_FOOTER {}", region.id),
+            RegionKind::Bytes => format!("uchar {}[0x{:X}]", region.id, region.size),
+        };
+        out.push_str(&format!("FSeek(0x{:X}); {} <comment={}>;\n", region.offset, declaration, quoted(&region.comment)));
+    }
+    out
+}
+
+const KSY_TYPES : &str = "types:
+  pfs_header:
+    seq:
+      - id: signature
+        contents: PFS.HDR.
+      - id: header_version
+        type: u4
+      - id: data_size
+        type: u4
+  pfs_section_header:
+    seq:
+      - id: guid
+        size: 16
+      - id: header_version
+        type: u4
+      - id: version_types
+        size: 4
+      - id: version
+        type: u2
+        repeat: expr
+        repeat-expr: 4
+      - id: reserved
+        type: u8
+      - id: data_size
+        type: u4
+      - id: data_sig_size
+        type: u4
+      - id: meta_size
+        type: u4
+      - id: meta_sig_size
+        type: u4
+      - id: unknown
+        size: 16
+  pfs_footer:
+    seq:
+      - id: data_size
+        type: u4
+      - id: checksum
+        type: u4
+      - id: signature
+        contents: PFS.FTR.
+";
+
+fn ksy(regions : &[Region], input : &str) -> String {
+    let mut out = format!("# Kaitai Struct description generated by PFSExtractor-RS {} from {}\n", env!("CARGO_PKG_VERSION"), input);
+    out.push_str("# Offsets are the ones of this file, other files need a description of their own\n");
+    out.push_str(&format!("meta:\n  id: pfs_layout\n  title: {}\n  endian: le\n", quoted(&format!("PFS layout of {}", input))));
+    out.push_str("instances:\n");
+    for region in regions {
+        out.push_str(&format!("  {}:\n    pos: 0x{:X}\n", region.id, region.offset));
+        match region.kind {
+            RegionKind::Header => out.push_str("    type: pfs_header\n"),
+            RegionKind::SectionHeader => out.push_str("    type: pfs_section_header\n"),
+            RegionKind::Footer => out.push_str("    type: pfs_footer\n"),
+            RegionKind::Bytes => out.push_str(&format!("    size: 0x{:X}\n", region.size)),
+        }
+        out.push_str(&format!("    doc: {}\n", quoted(&region.comment)));
+    }
+    out.push_str(KSY_TYPES);
+    out
+}
+
+// Description of the input named input in the given format, None if it has no PFS file
+pub fn annotate(data : &[u8], input : &str, format : AnnotateFormat) -> Option<String> {
+    let regions = regions(data);
+    if regions.is_empty() {
+        return None;
+    }
+    Some(match format {
+        AnnotateFormat::Ksy => ksy(&regions, input),
+        AnnotateFormat::Bt => bt(&regions, input),
+    })
+}
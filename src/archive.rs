@@ -0,0 +1,287 @@
+//
+// Archive output, extracted files go into a single zip or tar file
+//
+use flate2::{Compression, Crc};
+use flate2::write::{DeflateEncoder, GzEncoder};
+use std::convert::TryFrom;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl ArchiveFormat {
+    // Format is told by file name
+    pub fn from_path(path : &Path) -> Option<ArchiveFormat> {
+        let name = path.file_name()?.to_string_lossy().to_lowercase();
+        if name.ends_with(".zip") {
+            Some(ArchiveFormat::Zip)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveFormat::Tar)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveFormat::TarGz)
+        } else {
+            None
+        }
+    }
+}
+
+// Gzip trailer is only written by finish, so the encoder is kept by its type
+enum Writer {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+// Central directory record of a zip entry written already
+struct ZipEntry {
+    name : String,
+    crc : u32,
+    compressed_size : u32,
+    size : u32,
+    offset : u32,
+}
+
+// Files are written as they come, nothing is kept in memory but the zip directory
+pub struct Archive {
+    path : PathBuf,
+    format : ArchiveFormat,
+    writer : Writer,
+    offset : u64,
+    entries : Vec<ZipEntry>,
+}
+
+// Zip without zip64 extensions is limited to 4 GB and 65535 entries
+fn zip_limit(value : u64) -> io::Result<u32> {
+    if value > u64::from(u32::MAX) {
+        return Err(io::Error::other("zip archive is larger than 4 GB"));
+    }
+    Ok(value as u32)
+}
+
+// Entries have fixed time, so the same input gives the same archive
+const ZIP_DOS_DATE : u16 = 0x21; // 1980-01-01
+
+const TAR_BLOCK_SIZE : usize = 0x200;
+
+fn tar_octal(field : &mut [u8], value : u64) {
+    let digits = field.len() - 1;
+    let text = format!("{:0width$o}", value, width = digits);
+    field[..digits].copy_from_slice(text.as_bytes());
+}
+
+fn tar_header(name : &[u8], size : u64, kind : u8) -> [u8; TAR_BLOCK_SIZE] {
+    let mut header = [0u8; TAR_BLOCK_SIZE];
+    header[..name.len().min(100)].copy_from_slice(&name[..name.len().min(100)]);
+    tar_octal(&mut header[100..108], 0o644);
+    tar_octal(&mut header[108..116], 0);
+    tar_octal(&mut header[116..124], 0);
+    tar_octal(&mut header[124..136], size);
+    tar_octal(&mut header[136..148], 0);
+    header[156] = kind;
+    header[257..265].copy_from_slice(b"ustar  \0");
+
+    // Checksum is computed with its own field filled with spaces
+    header[148..156].copy_from_slice(b"        ");
+    let sum : u32 = header.iter().map(|&b| u32::from(b)).sum();
+    let text = format!("{:06o}\0 ", sum);
+    header[148..156].copy_from_slice(text.as_bytes());
+    header
+}
+
+impl Archive {
+    pub fn create(path : &Path, format : ArchiveFormat) -> io::Result<Archive> {
+        let file = BufWriter::new(OpenOptions::new().write(true).create_new(true).open(path)?);
+        let writer = match format {
+            ArchiveFormat::TarGz => Writer::Gzip(GzEncoder::new(file, Compression::default())),
+            _ => Writer::Plain(file),
+        };
+        Ok(Archive {
+            path : path.to_path_buf(),
+            format,
+            writer,
+            offset : 0,
+            entries : Vec::new(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn write(&mut self, data : &[u8]) -> io::Result<()> {
+        match self.writer {
+            Writer::Plain(ref mut w) => w.write_all(data)?,
+            Writer::Gzip(ref mut w) => w.write_all(data)?,
+        }
+        self.offset += data.len() as u64;
+        Ok(())
+    }
+
+    // Name uses / to separate directories
+    pub fn add(&mut self, name : &str, data : &[u8]) -> io::Result<()> {
+        match self.format {
+            ArchiveFormat::Zip => self.add_zip(name, data),
+            ArchiveFormat::Tar | ArchiveFormat::TarGz => self.add_tar(name, data),
+        }
+    }
+
+    fn add_zip(&mut self, name : &str, data : &[u8]) -> io::Result<()> {
+        if self.entries.len() == usize::from(u16::MAX) {
+            return Err(io::Error::other("zip archive can't have more than 65535 files"));
+        }
+        let mut crc = Crc::new();
+        crc.update(data);
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        let compressed = encoder.finish()?;
+        let entry = ZipEntry {
+            name : String::from(name),
+            crc : crc.sum(),
+            compressed_size : zip_limit(compressed.len() as u64)?,
+            size : zip_limit(data.len() as u64)?,
+            offset : zip_limit(self.offset)?,
+        };
+
+        let mut header = Vec::with_capacity(30 + name.len());
+        header.extend_from_slice(&0x04034b50u32.to_le_bytes());
+        header.extend_from_slice(&20u16.to_le_bytes()); // Version needed to extract
+        header.extend_from_slice(&0x0800u16.to_le_bytes()); // Name is UTF-8
+        header.extend_from_slice(&8u16.to_le_bytes()); // Deflate
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(&ZIP_DOS_DATE.to_le_bytes());
+        header.extend_from_slice(&entry.crc.to_le_bytes());
+        header.extend_from_slice(&entry.compressed_size.to_le_bytes());
+        header.extend_from_slice(&entry.size.to_le_bytes());
+        header.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        header.extend_from_slice(&0u16.to_le_bytes());
+        header.extend_from_slice(name.as_bytes());
+        self.write(&header)?;
+        self.write(&compressed)?;
+        self.entries.push(entry);
+        Ok(())
+    }
+
+    fn add_tar(&mut self, name : &str, data : &[u8]) -> io::Result<()> {
+        // Long names go into a GNU long name entry in front of the file
+        if name.len() >= 100 {
+            let mut long = name.as_bytes().to_vec();
+            long.push(0);
+            self.write(&tar_header(b"././@LongLink", long.len() as u64, b'L'))?;
+            self.write_tar_data(&long)?;
+        }
+        self.write(&tar_header(name.as_bytes(), data.len() as u64, b'0'))?;
+        self.write_tar_data(data)
+    }
+
+    fn write_tar_data(&mut self, data : &[u8]) -> io::Result<()> {
+        self.write(data)?;
+        let padding = (TAR_BLOCK_SIZE - data.len() % TAR_BLOCK_SIZE) % TAR_BLOCK_SIZE;
+        self.write(&[0u8; TAR_BLOCK_SIZE][..padding])
+    }
+
+    // Archive is incomplete until this is called
+    pub fn finish(mut self) -> io::Result<()> {
+        match self.format {
+            ArchiveFormat::Zip => {
+                let start = zip_limit(self.offset)?;
+                let mut directory = Vec::new();
+                for entry in &self.entries {
+                    directory.extend_from_slice(&0x02014b50u32.to_le_bytes());
+                    directory.extend_from_slice(&20u16.to_le_bytes()); // Version made by
+                    directory.extend_from_slice(&20u16.to_le_bytes()); // Version needed to extract
+                    directory.extend_from_slice(&0x0800u16.to_le_bytes());
+                    directory.extend_from_slice(&8u16.to_le_bytes());
+                    directory.extend_from_slice(&0u16.to_le_bytes());
+                    directory.extend_from_slice(&ZIP_DOS_DATE.to_le_bytes());
+                    directory.extend_from_slice(&entry.crc.to_le_bytes());
+                    directory.extend_from_slice(&entry.compressed_size.to_le_bytes());
+                    directory.extend_from_slice(&entry.size.to_le_bytes());
+                    directory.extend_from_slice(&(entry.name.len() as u16).to_le_bytes());
+                    directory.extend_from_slice(&[0u8; 12]); // Extra, comment, disk, attributes
+                    directory.extend_from_slice(&entry.offset.to_le_bytes());
+                    directory.extend_from_slice(entry.name.as_bytes());
+                }
+                let size = zip_limit(directory.len() as u64)?;
+                let count = self.entries.len() as u16;
+                directory.extend_from_slice(&0x06054b50u32.to_le_bytes());
+                directory.extend_from_slice(&[0u8; 4]); // Disk numbers
+                directory.extend_from_slice(&count.to_le_bytes());
+                directory.extend_from_slice(&count.to_le_bytes());
+                directory.extend_from_slice(&size.to_le_bytes());
+                directory.extend_from_slice(&start.to_le_bytes());
+                directory.extend_from_slice(&0u16.to_le_bytes());
+                self.write(&directory)?;
+            }
+            ArchiveFormat::Tar | ArchiveFormat::TarGz => {
+                self.write(&[0u8; TAR_BLOCK_SIZE * 2])?;
+            }
+        }
+        match self.writer {
+            Writer::Plain(w) => w.into_inner().map_err(|e| e.into_error())?.sync_all(),
+            Writer::Gzip(w) => w.finish()?.into_inner().map_err(|e| e.into_error())?.sync_all(),
+        }
+    }
+}
+
+//
+// Cabinet with stored files in one folder, the format fwupd takes firmware in
+//
+const CAB_BLOCK_SIZE : usize = 0x8000;
+const CAB_HEADER_SIZE : usize = 0x24;
+const CAB_FOLDER_SIZE : usize = 0x8;
+const CAB_FILE_SIZE : usize = 0x10;
+const CAB_DATA_SIZE : usize = 0x8;
+
+pub fn cab(files : &[(&str, &[u8])]) -> io::Result<Vec<u8>> {
+    let limit = || io::Error::other("cabinet is larger than 4 GB or has more than 65535 files or blocks");
+    let content : Vec<u8> = files.iter().flat_map(|&(_, data)| data.iter().copied()).collect();
+    let blocks = u16::try_from(content.len().div_ceil(CAB_BLOCK_SIZE)).map_err(|_| limit())?;
+    let count = u16::try_from(files.len()).map_err(|_| limit())?;
+    let files_offset = CAB_HEADER_SIZE + CAB_FOLDER_SIZE;
+    let data_offset = files_offset + files.iter().map(|&(name, _)| CAB_FILE_SIZE + name.len() + 1).sum::<usize>();
+    let size = u32::try_from(data_offset + blocks as usize * CAB_DATA_SIZE + content.len()).map_err(|_| limit())?;
+
+    let mut out = Vec::with_capacity(size as usize);
+    out.extend_from_slice(b"MSCF");
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&size.to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&(files_offset as u32).to_le_bytes());
+    out.extend_from_slice(&0u32.to_le_bytes());
+    out.extend_from_slice(&[3, 1]); // Format version 1.3
+    out.extend_from_slice(&1u16.to_le_bytes()); // Folders
+    out.extend_from_slice(&count.to_le_bytes());
+    out.extend_from_slice(&[0u8; 6]); // Flags, set ID and cabinet number
+
+    // Folder is stored without compression
+    out.extend_from_slice(&(data_offset as u32).to_le_bytes());
+    out.extend_from_slice(&blocks.to_le_bytes());
+    out.extend_from_slice(&0u16.to_le_bytes());
+
+    let mut offset = 0u32;
+    for &(name, data) in files {
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&offset.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes()); // Folder
+        out.extend_from_slice(&ZIP_DOS_DATE.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes()); // Time
+        out.extend_from_slice(&0u16.to_le_bytes()); // Attributes
+        out.extend_from_slice(name.as_bytes());
+        out.push(0);
+        offset += data.len() as u32;
+    }
+
+    // Zero checksum means there is none
+    for block in content.chunks(CAB_BLOCK_SIZE) {
+        out.extend_from_slice(&0u32.to_le_bytes());
+        out.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        out.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        out.extend_from_slice(block);
+    }
+    Ok(out)
+}
@@ -0,0 +1,54 @@
+//
+// Extraction for callers running on a tokio runtime, enabled with the tokio feature
+//
+use error::PfsError;
+use extract::{self, Extraction, Options};
+use std::fs::{self, File};
+use std::future::Future;
+use std::io::Read;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::task::{self, JoinHandle};
+
+// Extraction running on the blocking thread pool of the runtime, resolves to its result
+pub struct ExtractionTask {
+    handle : JoinHandle<Extraction>,
+}
+
+impl Future for ExtractionTask {
+    type Output = Extraction;
+
+    fn poll(mut self : Pin<&mut Self>, cx : &mut Context<'_>) -> Poll<Extraction> {
+        match Pin::new(&mut self.handle).poll(cx) {
+            Poll::Ready(Ok(extraction)) => Poll::Ready(extraction),
+            // Blocking tasks can't be cancelled, so the extraction panicked
+            Poll::Ready(Err(e)) => panic::resume_unwind(e.into_panic()),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+fn extract_file(options : &Options, path : &Path, dir : &Path) -> Extraction {
+    let mut data = Vec::new();
+    let read = File::open(path).map_err(|e| PfsError::Open(path.to_path_buf(), e))
+        .and_then(|mut file| file.read_to_end(&mut data).map_err(|e| PfsError::Read(path.to_path_buf(), e)));
+    if let Err(e) = read {
+        return Extraction::failed(e);
+    }
+    if let Err(e) = fs::create_dir_all(dir) {
+        return Extraction::failed(PfsError::Write(dir.to_path_buf(), e));
+    }
+    extract::extract(options, &data, dir)
+}
+
+// Extract PFS file at path into dir, which is created if needed. Reading, decompression and
+// writing all happen on the blocking thread pool, so the runtime is never blocked.
+// Has to be called from a tokio runtime, like tokio::task::spawn_blocking
+pub fn extract_async<P : Into<PathBuf>, Q : Into<PathBuf>>(options : Options, path : P, dir : Q) -> ExtractionTask {
+    let (path, dir) = (path.into(), dir.into());
+    ExtractionTask {
+        handle : task::spawn_blocking(move || extract_file(&options, &path, &dir)),
+    }
+}
@@ -0,0 +1,148 @@
+use error::PfsError;
+use compression;
+use parser::{self, Guid, PfsFile, PfsSection, PfsVersion};
+use verify;
+
+// Size field, signature and one unknown byte in front of compressed data
+const PFS_COMPRESSED_HEADER_SIZE : usize = 0x10;
+
+// Compresses data into the layout of the original compressed section the way it was compressed,
+// bytes around the compressed stream are kept as they are, except the signature of the old data
+pub fn pfs_recompress(original : &[u8], data : &[u8]) -> Result<Vec<u8>, PfsError> {
+    let (rest, section) = parser::pfs_compressed_section(original)
+        .map_err(|_| PfsError::Layout("section is not compressed"))?;
+    // Signature doesn't match the new data, dropping it gives the layout of an unsigned section
+    let signature_len = section.signature.map_or(0, <[u8]>::len);
+    if signature_len > 0 {
+        warn!("Signature of the compressed data is dropped, it won't match the new data");
+    }
+    let footer = &original[PFS_COMPRESSED_HEADER_SIZE + section.data.len() + signature_len..original.len() - rest.len()];
+    let compressed = compression::compress(compression::compression_type(section.data), data);
+
+    let mut out = Vec::with_capacity(PFS_COMPRESSED_HEADER_SIZE + compressed.len() + footer.len() + rest.len());
+    out.extend_from_slice(&size_u32(compressed.len(), "compressed data is larger than 4 GB")?.to_le_bytes());
+    out.extend_from_slice(&original[4..PFS_COMPRESSED_HEADER_SIZE]);
+    out.extend_from_slice(&compressed);
+    out.extend_from_slice(footer);
+    out.extend_from_slice(rest);
+    Ok(out)
+}
+
+//
+// Section of a PFS file being built, owns its data
+//
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PfsBuilderSection {
+    pub guid : Guid,
+    pub header_version : u32,
+    pub version : PfsVersion,
+    pub reserved : u64,
+    pub unknown : [u8; 16],
+    pub data : Vec<u8>,
+    pub data_sig : Vec<u8>,
+    pub meta : Vec<u8>,
+    pub meta_sig : Vec<u8>,
+}
+
+impl<'a> From<&'a PfsSection<'a>> for PfsBuilderSection {
+    fn from(section : &'a PfsSection<'a>) -> PfsBuilderSection {
+        PfsBuilderSection {
+            guid : section.guid,
+            header_version : section.header_version,
+            version : section.version,
+            reserved : section.reserved,
+            unknown : section.unknown,
+            data : section.data.unwrap_or_default().to_vec(),
+            data_sig : section.data_sig.unwrap_or_default().to_vec(),
+            meta : section.meta.unwrap_or_default().to_vec(),
+            meta_sig : section.meta_sig.unwrap_or_default().to_vec(),
+        }
+    }
+}
+
+//
+// PFS file writer, sizes and footer checksum are computed from section contents
+//
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PfsBuilder {
+    pub header_version : u32,
+    // Information section is not generated, it has to be the last one as in parsed files
+    pub sections : Vec<PfsBuilderSection>,
+}
+
+impl Default for PfsBuilder {
+    fn default() -> PfsBuilder {
+        PfsBuilder {
+            header_version : 1,
+            sections : Vec::new(),
+        }
+    }
+}
+
+fn size_u32(size : usize, what : &'static str) -> Result<u32, PfsError> {
+    if size > u32::MAX as usize {
+        return Err(PfsError::Layout(what));
+    }
+    Ok(size as u32)
+}
+
+impl PfsBuilder {
+    // Rebuilding a parsed file without changes gives the same bytes
+    pub fn from_file(file : &PfsFile) -> PfsBuilder {
+        PfsBuilder {
+            header_version : file.header.header_version,
+            sections : file.sections.iter().map(PfsBuilderSection::from).collect(),
+        }
+    }
+
+    // Replaces data of the only section with this GUID, returns whether the data was compressed
+    // because the section was compressed before
+    pub fn replace(&mut self, guid : &Guid, data : &[u8]) -> Result<bool, PfsError> {
+        let mut matched = self.sections.iter_mut().filter(|s| s.guid == *guid).collect::<Vec<_>>();
+        if matched.len() != 1 {
+            return Err(PfsError::Selection(matched.len()));
+        }
+        let section = &mut matched[0];
+        if parser::section_kind(&section.data) == parser::SectionKind::Compressed {
+            section.data = pfs_recompress(&section.data, data)?;
+            return Ok(true);
+        }
+        section.data = data.to_vec();
+        Ok(false)
+    }
+
+    pub fn build(&self) -> Result<Vec<u8>, PfsError> {
+        let mut sections = Vec::new();
+        for s in &self.sections {
+            sections.extend_from_slice(&s.guid.data1.to_le_bytes());
+            sections.extend_from_slice(&s.guid.data2.to_le_bytes());
+            sections.extend_from_slice(&s.guid.data3.to_le_bytes());
+            sections.extend_from_slice(&s.guid.data4);
+            sections.extend_from_slice(&s.header_version.to_le_bytes());
+            sections.extend_from_slice(&s.version.types);
+            for v in &s.version.numbers {
+                sections.extend_from_slice(&v.to_le_bytes());
+            }
+            sections.extend_from_slice(&s.reserved.to_le_bytes());
+            for part in &[&s.data, &s.data_sig, &s.meta, &s.meta_sig] {
+                sections.extend_from_slice(&size_u32(part.len(), "section part is larger than 4 GB")?.to_le_bytes());
+            }
+            sections.extend_from_slice(&s.unknown);
+            sections.extend_from_slice(&s.data);
+            sections.extend_from_slice(&s.data_sig);
+            sections.extend_from_slice(&s.meta);
+            sections.extend_from_slice(&s.meta_sig);
+        }
+        let data_size = size_u32(sections.len(), "sections are larger than 4 GB")?;
+
+        let mut out = Vec::with_capacity(parser::PFS_HEADER_SIZE + sections.len() + parser::PFS_FOOTER_SIZE);
+        out.extend_from_slice(b"PFS.HDR.");
+        out.extend_from_slice(&self.header_version.to_le_bytes());
+        out.extend_from_slice(&data_size.to_le_bytes());
+        out.extend_from_slice(&sections);
+        out.extend_from_slice(&data_size.to_le_bytes());
+        out.extend_from_slice(&verify::pfs_checksum(&sections).to_le_bytes());
+        out.extend_from_slice(b"PFS.FTR.");
+        Ok(out)
+    }
+}
@@ -0,0 +1,124 @@
+use error::PfsError;
+use parser::{Guid, PfsSection};
+use std::io;
+use std::str::FromStr;
+use walk::{self, PfsVisitor};
+
+//
+// One part of one section, for writing to stdout
+//
+
+// Section picked by GUID at any nesting level, or by index as extraction numbers them, like 2 or 1.3
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SectionSelector {
+    Guid(Guid),
+    Index(String),
+}
+
+impl FromStr for SectionSelector {
+    type Err = String;
+
+    fn from_str(s : &str) -> Result<SectionSelector, String> {
+        if let Ok(guid) = s.parse() {
+            return Ok(SectionSelector::Guid(guid));
+        }
+        let number = |part : &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+        let numbers = s.strip_prefix("pfs").and_then(|rest| rest.split_once('.')).filter(|&(n, _)| number(n)).map_or(s, |(_, rest)| rest);
+        if numbers.split('.').all(number) {
+            Ok(SectionSelector::Index(String::from(s)))
+        } else {
+            Err(String::from("expected a GUID or a section index like 2 or 1.3"))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionPart {
+    Data,
+    Meta,
+    Payload, // Data after decompression and chunk assembly, data itself for other sections
+}
+
+impl FromStr for SectionPart {
+    type Err = String;
+
+    fn from_str(s : &str) -> Result<SectionPart, String> {
+        match s {
+            "data" => Ok(SectionPart::Data),
+            "meta" => Ok(SectionPart::Meta),
+            "payload" => Ok(SectionPart::Payload),
+            _ => Err(String::from("expected data, meta or payload")),
+        }
+    }
+}
+
+struct CatVisitor<'s> {
+    selector : &'s SectionSelector,
+    part : SectionPart,
+    // Index and requested part of every matching section
+    found : Vec<(String, Option<Vec<u8>>)>,
+    error : Option<PfsError>,
+}
+
+impl<'s> CatVisitor<'s> {
+    fn current(&mut self, index : &str) -> Option<&mut (String, Option<Vec<u8>>)> {
+        self.found.last_mut().filter(|f| f.0 == index)
+    }
+}
+
+impl<'s> PfsVisitor for CatVisitor<'s> {
+    fn on_section(&mut self, index : &str, section : &PfsSection) -> bool {
+        let matched = match *self.selector {
+            SectionSelector::Guid(ref guid) => section.guid == *guid,
+            SectionSelector::Index(ref wanted) => index == wanted,
+        };
+        if matched {
+            let part = match self.part {
+                SectionPart::Data | SectionPart::Payload => section.data,
+                SectionPart::Meta => section.meta,
+            };
+            self.found.push((String::from(index), part.map(<[u8]>::to_vec)));
+        }
+        true
+    }
+
+    fn on_compressed_payload(&mut self, index : &str, data : &[u8]) {
+        self.on_chunked_payload(index, data);
+    }
+
+    // Called once for every layer, the last one is the final payload
+    fn on_chunked_payload(&mut self, index : &str, data : &[u8]) {
+        if self.part == SectionPart::Payload {
+            if let Some(found) = self.current(index) {
+                found.1 = Some(data.to_vec());
+            }
+        }
+    }
+
+    fn on_error(&mut self, index : &str, error : &PfsError) {
+        if self.part == SectionPart::Payload && self.current(index).is_some() && self.error.is_none() {
+            // Walking only fails to decompress, errors can't be cloned
+            self.error = Some(match *error {
+                PfsError::DecompressedSize(limit) => PfsError::DecompressedSize(limit),
+                PfsError::Decompress(ref e) => PfsError::Decompress(io::Error::new(e.kind(), e.to_string())),
+                _ => PfsError::Decompress(io::Error::other(error.to_string())),
+            });
+        }
+    }
+}
+
+// Requested part of the one section the selector matches, empty if the section has no such part
+pub fn section_part(data : &[u8], selector : &SectionSelector, part : SectionPart) -> Result<Vec<u8>, PfsError> {
+    let mut visitor = CatVisitor { selector, part, found : Vec::new(), error : None };
+    walk::pfs_walk(data, &mut visitor)?;
+    if visitor.found.len() != 1 {
+        for found in &visitor.found {
+            warn!("Section {} matches", found.0);
+        }
+        return Err(PfsError::Selection(visitor.found.len()));
+    }
+    if let Some(e) = visitor.error {
+        return Err(e);
+    }
+    Ok(visitor.found.remove(0).1.unwrap_or_default())
+}
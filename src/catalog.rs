@@ -0,0 +1,219 @@
+//
+// Dell update catalog, the CatalogPC.xml and Catalog.xml files listing update packages with the systems they are for
+//
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::fmt;
+
+// Element with everything inside it, text of CDATA sections included
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+struct XmlElement {
+    name : String,
+    attributes : Vec<(String, String)>,
+    children : Vec<XmlElement>,
+    text : String,
+}
+
+impl XmlElement {
+    fn attribute(&self, name : &str) -> Option<&str> {
+        self.attributes.iter().find(|a| a.0 == name).map(|a| a.1.as_str())
+    }
+
+    fn child(&self, name : &str) -> Option<&XmlElement> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    fn children<'a>(&'a self, name : &'a str) -> impl Iterator<Item = &'a XmlElement> + 'a {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    // Text of the Display child, Dell puts every name there, possibly in several languages with English first
+    fn display(&self) -> String {
+        self.child("Display").map_or(String::new(), |d| d.text.trim().to_string())
+    }
+}
+
+fn xml_error(e : impl fmt::Display) -> String {
+    e.to_string()
+}
+
+fn xml_element(start : &BytesStart) -> Result<XmlElement, String> {
+    let mut attributes = Vec::new();
+    for attribute in start.attributes() {
+        let attribute = attribute.map_err(xml_error)?;
+        let value = attribute.unescape_value().map_err(xml_error)?;
+        attributes.push((String::from_utf8_lossy(attribute.key.as_ref()).into_owned(), value.into_owned()));
+    }
+    Ok(XmlElement { name : String::from_utf8_lossy(start.name().as_ref()).into_owned(), attributes, ..XmlElement::default() })
+}
+
+// Whole document as a tree, comments, DTDs and processing instructions are skipped
+fn xml_parse(text : &str) -> Result<XmlElement, String> {
+    let mut reader = Reader::from_str(text);
+    // Open elements, the document node at the bottom
+    let mut stack = vec![XmlElement::default()];
+    loop {
+        match reader.read_event().map_err(xml_error)? {
+            Event::Start(start) => stack.push(xml_element(&start)?),
+            Event::Empty(start) => {
+                let element = xml_element(&start)?;
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(element);
+                }
+            }
+            Event::End(end) => {
+                if stack.len() < 2 {
+                    return Err(format!("unexpected </{}>", String::from_utf8_lossy(end.name().as_ref())));
+                }
+                let element = stack.pop().unwrap_or_default();
+                if let Some(parent) = stack.last_mut() {
+                    parent.children.push(element);
+                }
+            }
+            Event::Text(text) if stack.len() > 1 => {
+                let text = text.unescape().map_err(xml_error)?;
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&text);
+                }
+            }
+            Event::CData(cdata) if stack.len() > 1 => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&String::from_utf8_lossy(&cdata));
+                }
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+    if stack.len() > 1 {
+        return Err(format!("<{}> is not closed", stack.last().map_or("", |e| e.name.as_str())));
+    }
+    stack.pop().and_then(|document| document.children.into_iter().next()).ok_or_else(|| String::from("no root element"))
+}
+
+// Catalogs Dell publishes are UTF-16 with a byte order mark, saved copies may be UTF-8
+fn decode(data : &[u8]) -> String {
+    let utf16 = |le : bool, data : &[u8]| -> String {
+        let units : Vec<u16> = data.chunks_exact(2).map(|u| if le { u16::from_le_bytes([u[0], u[1]]) } else { u16::from_be_bytes([u[0], u[1]]) }).collect();
+        String::from_utf16_lossy(&units)
+    };
+    if let Some(data) = data.strip_prefix(b"\xFF\xFE") {
+        utf16(true, data)
+    } else if let Some(data) = data.strip_prefix(b"\xFE\xFF") {
+        utf16(false, data)
+    } else {
+        String::from_utf8_lossy(data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data)).into_owned()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatalogSystem {
+    pub brand : String,     // Like Latitude
+    pub model : String,     // Like 7420
+    pub system_id : String, // Four hex digits, as the BIOS reports it
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CatalogPackage {
+    pub name : String,
+    pub component_type : String, // Like BIOS or FRMW
+    pub version : String,
+    pub path : String,           // Relative to the base location of the catalog
+    pub size : Option<u64>,
+    pub sha256 : Option<String>, // Lowercase hex
+    pub systems : Vec<CatalogSystem>,
+}
+
+impl CatalogPackage {
+    // File name the package is saved under
+    pub fn file_name(&self) -> &str {
+        self.path.rsplit('/').next().unwrap_or(&self.path)
+    }
+
+    // Systems as "Brand Model", without repeating
+    pub fn models(&self) -> Vec<String> {
+        let mut models : Vec<String> = Vec::new();
+        for system in &self.systems {
+            let model = format!("{} {}", system.brand, system.model).trim().to_string();
+            if !models.contains(&model) {
+                models.push(model);
+            }
+        }
+        models
+    }
+}
+
+pub struct Catalog {
+    pub base_location : String,
+    pub packages : Vec<CatalogPackage>,
+}
+
+// Packages are picked by any of their systems, filters not given match everything
+#[derive(Debug, Clone, Default)]
+pub struct CatalogFilter {
+    pub model : Option<String>,          // Part of "Brand Model", case doesn't matter
+    pub system_id : Option<String>,
+    pub component_type : Option<String>,
+}
+
+impl CatalogFilter {
+    pub fn matches(&self, package : &CatalogPackage) -> bool {
+        if let Some(ref component_type) = self.component_type {
+            if !package.component_type.eq_ignore_ascii_case(component_type) {
+                return false;
+            }
+        }
+        let model = self.model.as_ref().map(|m| m.to_lowercase());
+        let system_id = self.system_id.as_ref().map(|id| id.trim_start_matches("0x").trim_start_matches('0').to_uppercase());
+        package.systems.iter().any(|system| {
+            model.as_ref().is_none_or(|m| format!("{} {}", system.brand, system.model).to_lowercase().contains(m.as_str()))
+                && system_id.as_ref().is_none_or(|id| system.system_id.trim_start_matches('0').eq_ignore_ascii_case(id))
+        }) || (package.systems.is_empty() && model.is_none() && system_id.is_none())
+    }
+}
+
+impl Catalog {
+    pub fn parse(data : &[u8]) -> Result<Catalog, String> {
+        let root = xml_parse(&decode(data))?;
+        if root.name != "Manifest" {
+            return Err(format!("root element is <{}>, not <Manifest>", root.name));
+        }
+        let mut packages = Vec::new();
+        for component in root.children("SoftwareComponent") {
+            let path = match component.attribute("path") {
+                Some(path) => path.replace('\\', "/"),
+                None => continue,
+            };
+            let mut systems = Vec::new();
+            for brand in component.child("SupportedSystems").into_iter().flat_map(|s| s.children("Brand")) {
+                for model in brand.children("Model") {
+                    systems.push(CatalogSystem {
+                        brand : brand.display(),
+                        model : model.display(),
+                        system_id : model.attribute("systemID").unwrap_or_default().to_uppercase(),
+                    });
+                }
+            }
+            let sha256 = component.child("Cryptography").into_iter().flat_map(|c| c.children("Hash"))
+                .find(|h| h.attribute("algorithm").is_some_and(|a| a.eq_ignore_ascii_case("SHA256")))
+                .map(|h| h.text.trim().to_lowercase());
+            packages.push(CatalogPackage {
+                name : component.child("Name").map_or(String::new(), XmlElement::display),
+                component_type : component.child("ComponentType").and_then(|t| t.attribute("value")).unwrap_or_default().to_string(),
+                version : component.attribute("vendorVersion").or_else(|| component.attribute("dellVersion")).unwrap_or_default().to_string(),
+                path,
+                size : component.attribute("size").and_then(|s| s.parse().ok()),
+                sha256,
+                systems,
+            });
+        }
+        Ok(Catalog { base_location : root.attribute("baseLocation").unwrap_or_default().to_string(), packages })
+    }
+
+    // Download URL of a package, over HTTPS when the catalog doesn't tell the scheme
+    pub fn url(&self, package : &CatalogPackage) -> String {
+        let base = self.base_location.trim_end_matches('/');
+        let base = if base.contains("://") { String::from(base) } else { format!("https://{}", base) };
+        format!("{}/{}", base, package.path.trim_start_matches('/'))
+    }
+}
@@ -0,0 +1,237 @@
+//
+// X.509 chains of PKCS#7 section signatures, checked up to roots given by the caller
+//
+use cms::cert::CertificateChoices;
+use cms::signed_data::{SignedData, SignerIdentifier, SignerInfo};
+use rsa::{Pkcs1v15Sign, RsaPublicKey};
+use rsa::pkcs8::DecodePublicKey;
+use sha2::{Sha224, Sha256, Sha384, Sha512};
+use signature;
+use std::fmt;
+use verify;
+pub use x509_cert::Certificate;
+use x509_cert::ext::pkix::{BasicConstraints, KeyUsage};
+use x509_cert::der::{Decode, Encode};
+use x509_cert::der::oid::ObjectIdentifier;
+use x509_cert::der::oid::db::rfc4519::CN;
+use x509_cert::der::oid::db::rfc5911::{ID_MESSAGE_DIGEST, ID_SIGNING_TIME};
+use x509_cert::der::oid::db::rfc5912::{ID_SHA_224, ID_SHA_256, ID_SHA_384, ID_SHA_512, RSA_ENCRYPTION,
+    SHA_224_WITH_RSA_ENCRYPTION, SHA_256_WITH_RSA_ENCRYPTION, SHA_384_WITH_RSA_ENCRYPTION, SHA_512_WITH_RSA_ENCRYPTION};
+use x509_cert::der::asn1::OctetString;
+use x509_cert::time::Time;
+
+// Intermediate certificates between the signer and a root
+const MAX_CHAIN_LENGTH : usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainStatus {
+    Trusted,
+    Untrusted,     // Chain doesn't end at one of the roots
+    Expired,       // Certificate in the chain not valid at signing time
+    BadSignature,  // Signer or certificate signature doesn't verify, or signed digest doesn't match
+    NoCertificate, // Signer certificate is not in the signature
+    Unsupported,   // Algorithms other than RSA with SHA-2
+    Missing,       // No PKCS#7 signature
+}
+
+impl fmt::Display for ChainStatus {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ChainStatus::Trusted => "trusted",
+            ChainStatus::Untrusted => "untrusted root",
+            ChainStatus::Expired => "expired",
+            ChainStatus::BadSignature => "bad signature",
+            ChainStatus::NoCertificate => "no signer certificate",
+            ChainStatus::Unsupported => "unsupported algorithm",
+            ChainStatus::Missing => "no PKCS#7 signature",
+        })
+    }
+}
+
+pub struct ChainReport {
+    pub status : ChainStatus,
+    pub signer : Option<String>,              // Common name of the signer certificate
+    pub validity : Option<(String, String)>,  // Validity window of the signer certificate
+}
+
+// Every certificate of a PEM bundle, a bundle without certificates is an error
+pub fn load_roots(pem : &str) -> Option<Vec<Certificate>> {
+    Certificate::load_pem_chain(pem.as_bytes()).ok().filter(|roots| !roots.is_empty())
+}
+
+fn common_name(cert : &Certificate) -> String {
+    let subject = &cert.tbs_certificate.subject;
+    subject.0.iter()
+        .flat_map(|rdn| rdn.0.iter())
+        .find(|atv| atv.oid == CN)
+        .map(|atv| atv.to_string().trim_start_matches("CN=").to_string())
+        .unwrap_or_else(|| subject.to_string())
+}
+
+fn public_key(cert : &Certificate) -> Option<RsaPublicKey> {
+    RsaPublicKey::from_public_key_der(&cert.tbs_certificate.subject_public_key_info.to_der().ok()?).ok()
+}
+
+// PKCS#1 v1.5 signature made with key, None for algorithms that are not supported
+fn rsa_verify(key : &RsaPublicKey, digest_algorithm : &ObjectIdentifier, message : &[u8], sig : &[u8]) -> Option<bool> {
+    let hash = verify::digest(digest_algorithm, message)?;
+    let scheme = match *digest_algorithm {
+        ID_SHA_224 => Pkcs1v15Sign::new::<Sha224>(),
+        ID_SHA_256 => Pkcs1v15Sign::new::<Sha256>(),
+        ID_SHA_384 => Pkcs1v15Sign::new::<Sha384>(),
+        _ => Pkcs1v15Sign::new::<Sha512>(),
+    };
+    Some(key.verify(scheme, &hash, sig).is_ok())
+}
+
+fn rsa_digest_algorithm(signature_algorithm : &ObjectIdentifier) -> Option<ObjectIdentifier> {
+    match *signature_algorithm {
+        SHA_224_WITH_RSA_ENCRYPTION => Some(ID_SHA_224),
+        SHA_256_WITH_RSA_ENCRYPTION => Some(ID_SHA_256),
+        SHA_384_WITH_RSA_ENCRYPTION => Some(ID_SHA_384),
+        SHA_512_WITH_RSA_ENCRYPTION => Some(ID_SHA_512),
+        _ => None,
+    }
+}
+
+// Signature of cert made by issuer, None for algorithms that are not supported
+fn issued_by(cert : &Certificate, issuer : &Certificate) -> Option<bool> {
+    if cert.tbs_certificate.issuer != issuer.tbs_certificate.subject {
+        return Some(false);
+    }
+    let digest_algorithm = rsa_digest_algorithm(&cert.signature_algorithm.oid)?;
+    let tbs = cert.tbs_certificate.to_der().ok()?;
+    rsa_verify(&public_key(issuer)?, &digest_algorithm, &tbs, cert.signature.raw_bytes())
+}
+
+// Certificates from the signature only issue others when they are CA certificates allowed to sign certificates,
+// key usage is optional. Roots are trusted as they are given
+fn is_ca(cert : &Certificate) -> bool {
+    let ca = matches!(cert.tbs_certificate.get::<BasicConstraints>(), Ok(Some((_, ref constraints))) if constraints.ca);
+    let cert_sign = match cert.tbs_certificate.get::<KeyUsage>() {
+        Ok(Some((_, usage))) => usage.key_cert_sign(),
+        Ok(None) => true,
+        Err(_) => false,
+    };
+    ca && cert_sign
+}
+
+fn valid_at(cert : &Certificate, time : u64) -> bool {
+    let validity = &cert.tbs_certificate.validity;
+    validity.not_before.to_unix_duration().as_secs() <= time && time <= validity.not_after.to_unix_duration().as_secs()
+}
+
+fn signing_time(signer : &SignerInfo) -> Option<u64> {
+    let attrs = signer.signed_attrs.as_ref()?;
+    let attribute = attrs.iter().find(|a| a.oid == ID_SIGNING_TIME)?;
+    let time = Time::from_der(&attribute.values.iter().next()?.to_der().ok()?).ok()?;
+    Some(time.to_unix_duration().as_secs())
+}
+
+// Signature of the signer over its signed attributes, which have to carry the digest of data,
+// or over data itself when there are no attributes
+fn signer_signature(signer : &SignerInfo, key : &RsaPublicKey, data : &[u8]) -> Option<bool> {
+    let digest_algorithm = signer.digest_alg.oid;
+    let algorithm = signer.signature_algorithm.oid;
+    if algorithm != RSA_ENCRYPTION && rsa_digest_algorithm(&algorithm) != Some(digest_algorithm) {
+        return None;
+    }
+    let message = match signer.signed_attrs {
+        Some(ref attrs) => {
+            let signed = attrs.iter()
+                .filter(|a| a.oid == ID_MESSAGE_DIGEST)
+                .flat_map(|a| a.values.iter())
+                .filter_map(|v| v.decode_as::<OctetString>().ok())
+                .any(|digest| Some(digest.as_bytes().to_vec()) == verify::digest(&digest_algorithm, data));
+            if !signed {
+                return Some(false);
+            }
+            attrs.to_der().ok()?
+        }
+        None => data.to_vec(),
+    };
+    rsa_verify(key, &digest_algorithm, &message, signer.signature.as_bytes())
+}
+
+fn is_signer(id : &SignerIdentifier, cert : &Certificate) -> bool {
+    match *id {
+        SignerIdentifier::IssuerAndSerialNumber(ref isn) =>
+            isn.issuer == cert.tbs_certificate.issuer && isn.serial_number == cert.tbs_certificate.serial_number,
+        // Key identifiers are not looked up, every certificate is tried
+        SignerIdentifier::SubjectKeyIdentifier(_) => true,
+    }
+}
+
+// Chain from the signer certificate up to one of roots, each certificate valid at signing time
+// or at now when the signature has no time
+fn check_chain(signed : &SignedData, data : &[u8], roots : &[Certificate], now : u64) -> ChainReport {
+    let mut report = ChainReport { status : ChainStatus::NoCertificate, signer : None, validity : None };
+    let certs : Vec<&Certificate> = signed.certificates.iter()
+        .flat_map(|set| set.0.iter())
+        .filter_map(|choice| match *choice {
+            CertificateChoices::Certificate(ref cert) => Some(cert),
+            _ => None,
+        })
+        .collect();
+    let signer = match signed.signer_infos.0.iter().next() {
+        Some(signer) => signer,
+        None => return report,
+    };
+
+    // Signer certificate is the one whose key verifies the signature
+    let mut found = None;
+    for &cert in certs.iter().filter(|&&cert| is_signer(&signer.sid, cert)) {
+        match public_key(cert).and_then(|key| signer_signature(signer, &key, data)) {
+            Some(true) => {
+                found = Some(cert);
+                break;
+            }
+            Some(false) => report.status = ChainStatus::BadSignature,
+            None => report.status = ChainStatus::Unsupported,
+        }
+    }
+    let mut cert = match found {
+        Some(cert) => cert,
+        None => return report,
+    };
+    let validity = &cert.tbs_certificate.validity;
+    report.signer = Some(common_name(cert));
+    report.validity = Some((validity.not_before.to_string(), validity.not_after.to_string()));
+
+    let time = signing_time(signer).unwrap_or(now);
+    for _ in 0..MAX_CHAIN_LENGTH {
+        if !valid_at(cert, time) {
+            report.status = ChainStatus::Expired;
+            return report;
+        }
+        if roots.contains(cert) {
+            report.status = ChainStatus::Trusted;
+            return report;
+        }
+        if let Some(root) = roots.iter().find(|root| issued_by(cert, root) == Some(true)) {
+            report.status = if valid_at(root, time) { ChainStatus::Trusted } else { ChainStatus::Expired };
+            return report;
+        }
+        match certs.iter().find(|&&issuer| issuer != cert && is_ca(issuer) && issued_by(cert, issuer) == Some(true)) {
+            Some(&issuer) => cert = issuer,
+            None => break,
+        }
+    }
+    report.status = ChainStatus::Untrusted;
+    report
+}
+
+// Chain of the PKCS#7 signature sig over data, now is the time in seconds since the Unix epoch
+pub fn validate_signature(data : &[u8], sig : Option<&[u8]>, roots : &[Certificate], now : u64) -> ChainReport {
+    match sig.and_then(signature::find_signed_data) {
+        Some((_, signed)) => check_chain(&signed, data, roots, now),
+        None => ChainReport { status : ChainStatus::Missing, signer : None, validity : None },
+    }
+}
+
+// Whether a signer of the PKCS#7 signature sig signed data with key, certificates in the signature are not looked at.
+// None when sig has no PKCS#7 signature
+pub fn verify_with_key(key : &RsaPublicKey, data : &[u8], sig : &[u8]) -> Option<bool> {
+    let (_, signed) = signature::find_signed_data(sig)?;
+    Some(signed.signer_infos.0.iter().any(|signer| signer_signature(signer, key, data) == Some(true)))
+}
@@ -0,0 +1,176 @@
+//
+// Compressed data of compressed sections, zlib in most files, LZMA or XZ in newer ones
+//
+use error::PfsError;
+use flate2::Compression as ZlibLevel;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use lzma_rs;
+use lzma_rs::decompress::Options;
+use std::cell::Cell;
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Compression {
+    Zlib,
+    Lzma, // LZMA_Alone stream with a 13-byte header
+    Xz,
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Compression::Zlib => "zlib",
+            Compression::Lzma => "LZMA",
+            Compression::Xz => "XZ",
+        })
+    }
+}
+
+const XZ_MAGIC : &[u8] = b"\xFD7zXZ\x00";
+
+// Properties byte encodes lc, lp and pb, at most 8, 4 and 4
+const LZMA_MAX_PROPERTIES : u8 = 9 * 5 * 5;
+const LZMA_HEADER_SIZE : usize = 13;
+
+fn is_zlib_header(data : &[u8]) -> bool {
+    data.len() >= 2 && data[0] & 0x0F == 8 && data[0] >> 4 <= 7 && (u16::from(data[0]) << 8 | u16::from(data[1])).is_multiple_of(31)
+}
+
+// Encoders write dictionary sizes of 2^n or 2^n + 2^(n-1) bytes
+fn is_lzma_header(data : &[u8]) -> bool {
+    if data.len() < LZMA_HEADER_SIZE || data[0] >= LZMA_MAX_PROPERTIES {
+        return false;
+    }
+    let dict_size = u32::from_le_bytes([data[1], data[2], data[3], data[4]]);
+    let low = dict_size & dict_size.wrapping_neg();
+    dict_size >= 0x1000 && (dict_size == low || dict_size == low * 3)
+}
+
+// Compression is told by the magic of the stream, zlib is assumed when nothing matches
+// so damaged streams fail with the usual error
+pub fn compression_type(data : &[u8]) -> Compression {
+    if data.starts_with(XZ_MAGIC) {
+        Compression::Xz
+    } else if !is_zlib_header(data) && is_lzma_header(data) {
+        Compression::Lzma
+    } else {
+        Compression::Zlib
+    }
+}
+
+// Data compressed the given way, writing into memory can't fail
+pub fn compress(kind : Compression, data : &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    match kind {
+        Compression::Zlib => {
+            let mut encoder = ZlibEncoder::new(out, ZlibLevel::default());
+            encoder.write_all(data).unwrap();
+            out = encoder.finish().unwrap();
+        }
+        Compression::Lzma => lzma_rs::lzma_compress(&mut &data[..], &mut out).unwrap(),
+        Compression::Xz => lzma_rs::xz_compress(&mut &data[..], &mut out).unwrap(),
+    }
+    out
+}
+
+// Input of LZMA and XZ decoders, counting what they have consumed for progress
+struct CountingReader<'a> {
+    data : &'a [u8],
+    consumed : &'a Cell<u64>,
+}
+
+impl<'a> Read for CountingReader<'a> {
+    fn read(&mut self, buf : &mut [u8]) -> io::Result<usize> {
+        let n = self.data.read(buf)?;
+        self.consumed.set(self.consumed.get() + n as u64);
+        Ok(n)
+    }
+}
+
+impl<'a> BufRead for CountingReader<'a> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(self.data)
+    }
+
+    fn consume(&mut self, amount : usize) {
+        self.data = &self.data[amount..];
+        self.consumed.set(self.consumed.get() + amount as u64);
+    }
+}
+
+// Output of LZMA and XZ decoders, errors of the callback are kept to be returned as they are
+struct Sink<'a, F> {
+    output : F,
+    consumed : &'a Cell<u64>,
+    error : Option<PfsError>,
+}
+
+impl<'a, F> Write for Sink<'a, F> where F : FnMut(&[u8], u64) -> Result<(), PfsError> {
+    fn write(&mut self, buf : &[u8]) -> io::Result<usize> {
+        if let Err(e) = (self.output)(buf, self.consumed.get()) {
+            self.error = Some(e);
+            return Err(io::Error::other("decompressed data is not accepted"));
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn lzma_error(e : lzma_rs::error::Error) -> PfsError {
+    match e {
+        lzma_rs::error::Error::IoError(e) | lzma_rs::error::Error::HeaderTooShort(e) => PfsError::Decompress(e),
+        e => PfsError::Decompress(io::Error::new(io::ErrorKind::InvalidData, e.to_string())),
+    }
+}
+
+// Decompressed data is passed to output block by block together with the number of compressed
+// bytes consumed so far. LZMA dictionaries are not allowed to take more than memory_limit bytes
+pub fn decompress_with<F>(data : &[u8], memory_limit : usize, mut output : F) -> Result<(), PfsError>
+    where F : FnMut(&[u8], u64) -> Result<(), PfsError>
+{
+    let kind = compression_type(data);
+    if kind == Compression::Zlib {
+        let mut zlib_decoder = ZlibDecoder::new(data);
+        let mut buffer = [0u8; 0x10000];
+        loop {
+            let n = zlib_decoder.read(&mut buffer).map_err(PfsError::Decompress)?;
+            if n == 0 {
+                return Ok(());
+            }
+            output(&buffer[..n], zlib_decoder.total_in())?;
+        }
+    }
+
+    let consumed = Cell::new(0);
+    let mut input = CountingReader { data, consumed : &consumed };
+    let mut sink = Sink { output, consumed : &consumed, error : None };
+    let result = match kind {
+        Compression::Lzma => {
+            let options = Options { memlimit : Some(memory_limit), ..Options::default() };
+            lzma_rs::lzma_decompress_with_options(&mut input, &mut sink, &options)
+        }
+        _ => lzma_rs::xz_decompress(&mut input, &mut sink),
+    };
+    match sink.error.take() {
+        Some(e) => Err(e),
+        None => result.map_err(lzma_error),
+    }
+}
+
+// Whole decompressed data, decompression producing more than limit is aborted
+pub fn decompress(data : &[u8], limit : usize) -> Result<Vec<u8>, PfsError> {
+    let mut decompressed = Vec::new();
+    decompress_with(data, limit, |block, _| {
+        if decompressed.len() + block.len() > limit {
+            return Err(PfsError::DecompressedSize(limit));
+        }
+        decompressed.extend_from_slice(block);
+        Ok(())
+    })?;
+    Ok(decompressed)
+}
@@ -0,0 +1,228 @@
+//
+// Content type detection of extracted data
+//
+use ec;
+use flate2::read::ZlibDecoder;
+use parser;
+use signature;
+use std::borrow::Cow;
+use std::fmt;
+use std::io::Read;
+use x509_cert::Certificate;
+use x509_cert::der::Decode;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ContentType {
+    Pfs,            // PFS file
+    UefiCapsule,    // UEFI capsule with a known capsule GUID
+    PeImage,        // PE/COFF or TE executable, usually an EFI module
+    FirmwareVolume, // UEFI firmware volume
+    FlashImage,     // Intel flash image starting with a flash descriptor
+    Text,           // ASCII text
+    Utf16Text,      // UTF-16LE text
+    Pem,            // PEM-encoded certificate or key
+    Certificate,    // DER-encoded X.509 certificate
+    Pkcs7,          // DER-encoded PKCS#7 SignedData
+    Zlib,           // Bare zlib stream
+    Raw,            // Anything else
+}
+
+impl fmt::Display for ContentType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ContentType::Pfs => "PFS file",
+            ContentType::UefiCapsule => "UEFI capsule",
+            ContentType::PeImage => "PE image",
+            ContentType::FirmwareVolume => "UEFI firmware volume",
+            ContentType::FlashImage => "Intel flash image",
+            ContentType::Text => "ASCII text",
+            ContentType::Utf16Text => "UTF-16 text",
+            ContentType::Pem => "PEM text",
+            ContentType::Certificate => "X.509 certificate",
+            ContentType::Pkcs7 => "PKCS#7 signed data",
+            ContentType::Zlib => "zlib stream",
+            ContentType::Raw => "raw data",
+        })
+    }
+}
+
+impl ContentType {
+    // Appended to file names, raw data gets none
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            ContentType::Pfs => ".pfs",
+            ContentType::UefiCapsule => ".cap",
+            ContentType::PeImage => ".efi",
+            ContentType::FirmwareVolume => ".fv",
+            ContentType::FlashImage => ".bin",
+            ContentType::Text | ContentType::Utf16Text => ".txt",
+            ContentType::Pem => ".pem",
+            ContentType::Certificate => ".cer",
+            ContentType::Pkcs7 => ".p7b",
+            ContentType::Zlib => ".zlib",
+            ContentType::Raw => "",
+        }
+    }
+}
+
+// Flash descriptor signature, at 0x10 in current images and at 0 in ICH8 ones
+const FLASH_DESCRIPTOR_SIGNATURE : &[u8] = b"\x5A\xA5\xF0\x0F";
+
+// Firmware volume header has its signature after zero vector, file system GUID and length
+const FV_SIGNATURE_OFFSET : usize = 0x28;
+
+fn is_pe_image(data : &[u8]) -> bool {
+    if data.starts_with(b"VZ") {
+        return true;
+    }
+    if !data.starts_with(b"MZ") || data.len() < 0x40 {
+        return false;
+    }
+    let pe = u32::from_le_bytes([data[0x3C], data[0x3D], data[0x3E], data[0x3F]]) as usize;
+    data.get(pe..pe + 4) == Some(b"PE\0\0")
+}
+
+fn is_text(data : &[u8]) -> bool {
+    let text = match data.iter().rposition(|&b| b != 0) {
+        Some(end) => &data[..=end],
+        None => return false,
+    };
+    text.iter().all(|&b| (0x20..0x7F).contains(&b) || b == b'\t' || b == b'\r' || b == b'\n')
+}
+
+fn is_utf16_text(data : &[u8]) -> bool {
+    let data = data.strip_prefix(b"\xFF\xFE").unwrap_or(data);
+    if data.len() < 2 || !data.len().is_multiple_of(2) {
+        return false;
+    }
+    let units : Vec<&[u8]> = data.chunks(2).collect();
+    let end = match units.iter().rposition(|u| u != &[0, 0]) {
+        Some(end) => end,
+        None => return false,
+    };
+    units[..=end].iter().all(|u| u[1] == 0 && is_text(&u[..1]))
+}
+
+fn is_zlib(data : &[u8]) -> bool {
+    // Deflate method with a valid header check, then some data has to come out of it
+    if data.len() < 2 || data[0] & 0x0F != 8 || data[0] >> 4 > 7 || !(u16::from(data[0]) << 8 | u16::from(data[1])).is_multiple_of(31) {
+        return false;
+    }
+    let mut buf = [0u8; 0x40];
+    matches!(ZlibDecoder::new(data).read(&mut buf), Ok(n) if n > 0)
+}
+
+pub fn content_type(data : &[u8]) -> ContentType {
+    if parser::classify(data) == parser::InputKind::Pfs {
+        ContentType::Pfs
+    } else if parser::uefi_capsule_header(data).is_ok() {
+        ContentType::UefiCapsule
+    } else if is_pe_image(data) {
+        ContentType::PeImage
+    } else if data.get(FV_SIGNATURE_OFFSET..FV_SIGNATURE_OFFSET + 4) == Some(b"_FVH") {
+        ContentType::FirmwareVolume
+    } else if data.get(0x10..0x14) == Some(FLASH_DESCRIPTOR_SIGNATURE) || data.starts_with(FLASH_DESCRIPTOR_SIGNATURE) {
+        ContentType::FlashImage
+    } else if data.starts_with(b"-----BEGIN ") {
+        ContentType::Pem
+    } else if is_text(data) {
+        ContentType::Text
+    } else if is_utf16_text(data) {
+        ContentType::Utf16Text
+    } else if data.first() == Some(&0x30) && Certificate::from_der(data).is_ok() {
+        ContentType::Certificate
+    } else if signature::parse_pkcs7(data).is_some_and(|sig| sig.offset == 0) {
+        ContentType::Pkcs7
+    } else if is_zlib(data) {
+        ContentType::Zlib
+    } else {
+        ContentType::Raw
+    }
+}
+
+// Offsets of zlib headers of the default and best compression levels, where bare streams may start
+pub fn find_zlib_headers(data : &[u8]) -> impl Iterator<Item = usize> + '_ {
+    data.windows(2).enumerate().filter(|&(_, w)| w == [0x78, 0x9C] || w == [0x78, 0xDA]).map(|(i, _)| i)
+}
+
+// Firmware volume header is checked in full, signature alone is found in random data too
+fn is_firmware_volume(data : &[u8]) -> Option<usize> {
+    let header = data.get(..0x38)?;
+    let length = u64::from_le_bytes([header[0x20], header[0x21], header[0x22], header[0x23],
+                                     header[0x24], header[0x25], header[0x26], header[0x27]]);
+    let header_length = usize::from(u16::from_le_bytes([header[0x30], header[0x31]]));
+    let revision = header[0x37];
+    if &header[FV_SIGNATURE_OFFSET..FV_SIGNATURE_OFFSET + 4] != b"_FVH" || (revision != 1 && revision != 2)
+        || header_length < 0x38 || !header_length.is_multiple_of(2) || length < header_length as u64 || length > data.len() as u64 {
+        return None;
+    }
+    let sum = data[..header_length].chunks(2).fold(0u16, |sum, w| sum.wrapping_add(u16::from_le_bytes([w[0], w[1]])));
+    if sum != 0 {
+        return None;
+    }
+    Some(length as usize)
+}
+
+// Offsets of firmware volumes, volumes inside of found ones are not reported
+pub fn find_firmware_volumes(data : &[u8]) -> Vec<usize> {
+    let mut volumes = Vec::new();
+    let mut next = 0;
+    let signatures = data.windows(4).enumerate().filter(|&(_, w)| w == b"_FVH").map(|(i, _)| i);
+    for offset in signatures.filter(|&i| i >= FV_SIGNATURE_OFFSET).map(|i| i - FV_SIGNATURE_OFFSET) {
+        if offset < next {
+            continue;
+        }
+        if let Some(length) = is_firmware_volume(&data[offset..]) {
+            volumes.push(offset);
+            next = offset + length;
+        }
+    }
+    volumes
+}
+
+// Decompressed data is only inflated this far to name a section
+const SECTION_HEAD_SIZE : u64 = 0x10000;
+
+// Name of the section between the last described one and information section
+pub const MODEL_PROPERTIES_NAME : &str = "Model Properties";
+
+fn is_bios(data : &[u8]) -> bool {
+    data.get(FV_SIGNATURE_OFFSET..FV_SIGNATURE_OFFSET + 4) == Some(b"_FVH")
+        || data.get(0x10..0x14) == Some(FLASH_DESCRIPTOR_SIGNATURE) || data.starts_with(FLASH_DESCRIPTOR_SIGNATURE)
+}
+
+// Flash partition table of a full region, code partition directory or manifest of an update image
+fn is_intel_me(data : &[u8]) -> bool {
+    data.get(0x10..0x14) == Some(b"$FPT") || data.starts_with(b"$FPT") || data.starts_with(b"$CPD")
+        || data.get(0x1C..0x20) == Some(b"$MN2") || data.get(0x1C..0x20) == Some(b"$MAN")
+}
+
+// Any ASCII text of even length is valid UTF-16, so the text has to be ASCII in UTF-16 with a key in it
+fn is_model_properties(data : &[u8]) -> bool {
+    is_utf16_text(data) && parser::pfs_model_properties(data).is_ok_and(|(_, properties)| properties.iter().any(|p| !p.key.is_empty()))
+}
+
+type ContentCheck = fn(&[u8]) -> bool;
+
+// Section contents recognized when neither information section nor GUID table names a section,
+// PCR0 data has no signature of its own and is not in here
+const KNOWN_CONTENTS : &[(&str, ContentCheck)] = &[
+    ("Embedded Controller", ec::has_ec_signature),
+    ("Intel Management Engine", is_intel_me),
+    ("System BIOS", is_bios),
+    (MODEL_PROPERTIES_NAME, is_model_properties),
+];
+
+// Friendly name of section data by its content, compressed data is named by what it decompresses to
+pub fn section_name(data : &[u8]) -> Option<&'static str> {
+    let head = match parser::pfs_compressed_section(data) {
+        Ok((_, section)) => {
+            let mut head = Vec::new();
+            let _ = ZlibDecoder::new(section.data).take(SECTION_HEAD_SIZE).read_to_end(&mut head);
+            Cow::Owned(head)
+        }
+        Err(_) if data.len() as u64 > SECTION_HEAD_SIZE => Cow::Borrowed(&data[..SECTION_HEAD_SIZE as usize]),
+        Err(_) => Cow::Borrowed(data),
+    };
+    KNOWN_CONTENTS.iter().find(|&&(_, known)| known(&head)).map(|&(name, _)| name)
+}
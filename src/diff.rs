@@ -0,0 +1,96 @@
+//
+// Section level comparison of two PFS files, nothing is extracted
+//
+use compression;
+use error::PfsError;
+use extract::{self, pfs_set_names};
+use guids::GuidNames;
+use manifest;
+use parser;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+// Section with its data summarized, nested sections follow their parent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffSection {
+    // GUID path from the top level file, repeated GUIDs get #N appended
+    pub key : String,
+    pub name : String,
+    pub version : String,
+    // Size and hash of decompressed data for compressed sections
+    pub size : usize,
+    pub sha256 : String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added(DiffSection),
+    Removed(DiffSection),
+    Changed(DiffSection, DiffSection),
+}
+
+// Damaged compressed data is summarized as it is
+fn decompress(data : &[u8]) -> Option<Vec<u8>> {
+    compression::decompress(data, extract::MAX_DECOMPRESSED_SIZE).ok()
+}
+
+fn pfs_collect(data : &[u8], guid_names : &GuidNames, prefix : &str, depth : usize, sections : &mut Vec<DiffSection>) {
+    let mut file = match parser::pfs_file(data) {
+        Ok((_, file)) => file,
+        _ => return,
+    };
+    pfs_set_names(&mut file, guid_names);
+
+    let mut seen : HashMap<parser::Guid, usize> = HashMap::new();
+    for section in &file.sections {
+        let n = seen.entry(section.guid).or_insert(0);
+        *n += 1;
+        let key = if *n == 1 { format!("{}{}", prefix, section.guid) } else { format!("{}{}#{}", prefix, section.guid, n) };
+
+        let raw = section.data.unwrap_or(&[]);
+        let data = match parser::pfs_compressed_section(raw).ok().and_then(|(_, comp)| decompress(comp.data)) {
+            Some(decompressed) => Cow::Owned(decompressed),
+            None => Cow::Borrowed(raw),
+        };
+        sections.push(DiffSection {
+            key : key.clone(),
+            name : section.name.clone(),
+            version : section.version.to_string(),
+            size : data.len(),
+            sha256 : manifest::sha256(&data),
+        });
+        if depth < extract::MAX_DEPTH {
+            pfs_collect(&data, guid_names, &format!("{}/", key), depth + 1, sections);
+        }
+    }
+}
+
+// Every PFS file found in the input is summarized, files after the first one are prefixed with pfsN/
+pub fn pfs_diff_sections(data : &[u8], guid_names : &GuidNames) -> Result<Vec<DiffSection>, PfsError> {
+    let offsets = parser::find_pfs_offsets(data);
+    if offsets.is_empty() {
+        return Err(PfsError::Parse);
+    }
+    let mut sections = Vec::new();
+    for (n, &offset) in offsets.iter().enumerate() {
+        let prefix = if n == 0 { String::new() } else { format!("pfs{}/", n + 1) };
+        pfs_collect(&data[offset..], guid_names, &prefix, 0, &mut sections);
+    }
+    Ok(sections)
+}
+
+// Removed and changed sections come in the old order, added ones after them
+pub fn diff(old : &[DiffSection], new : &[DiffSection]) -> Vec<Change> {
+    let new_by_key : HashMap<&str, &DiffSection> = new.iter().map(|s| (s.key.as_str(), s)).collect();
+    let old_keys : HashSet<&str> = old.iter().map(|s| s.key.as_str()).collect();
+    let mut changes = Vec::new();
+    for o in old {
+        match new_by_key.get(o.key.as_str()) {
+            None => changes.push(Change::Removed(o.clone())),
+            Some(&n) if n.version != o.version || n.sha256 != o.sha256 || n.name != o.name => changes.push(Change::Changed(o.clone(), n.clone())),
+            Some(_) => (),
+        }
+    }
+    changes.extend(new.iter().filter(|s| !old_keys.contains(s.key.as_str())).map(|s| Change::Added(s.clone())));
+    changes
+}
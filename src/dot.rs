@@ -0,0 +1,108 @@
+use error::PfsError;
+use parser::{PfsFile, PfsSection};
+use walk::{self, PfsVisitor};
+
+//
+// Graphviz graph of PFS files, sections and the payloads nested in them
+//
+
+// Quotes and backslashes are escaped, lines are separated by \n of DOT labels
+fn label(lines : &[String]) -> String {
+    let lines : Vec<String> = lines.iter().map(|line| line.replace('\\', "\\\\").replace('"', "\\\"")).collect();
+    format!("\"{}\"", lines.join("\\n"))
+}
+
+struct DotVisitor {
+    out : String,
+    // Node every PFS file of the current nesting level hangs from, with the input at the bottom
+    parents : Vec<String>,
+    // Node of the current section or of the last payload obtained from it
+    last : String,
+    files : usize,
+}
+
+impl DotVisitor {
+    fn node(&mut self, id : &str, shape : &str, lines : &[String]) {
+        self.out.push_str(&format!("  \"{}\" [shape={}, label={}];\n", id, shape, label(lines)));
+    }
+
+    fn edge(&mut self, from : &str, to : &str) {
+        self.out.push_str(&format!("  \"{}\" -> \"{}\";\n", from, to));
+    }
+
+    // Payload obtained from the current section or from the payload before it
+    fn payload(&mut self, id : String, lines : &[String]) {
+        self.node(&id, "ellipse", lines);
+        let from = self.last.clone();
+        self.edge(&from, &id);
+        self.last = id;
+    }
+}
+
+impl PfsVisitor for DotVisitor {
+    fn on_nesting_enter(&mut self, depth : usize, file : &PfsFile) {
+        self.files += 1;
+        let id = format!("pfs{}", self.files);
+        self.node(&id, "folder", &[
+            String::from("PFS file"),
+            format!("header version {:X}, {} sections", file.header.header_version, file.sections.len()),
+            format!("size 0x{:X}", file.size()),
+        ]);
+        // Files in the input hang from it, nested ones from the payload they were found in
+        let parent = if depth == 0 { self.parents[0].clone() } else { self.last.clone() };
+        self.edge(&parent, &id);
+        self.parents.push(id);
+    }
+
+    fn on_section(&mut self, index : &str, section : &PfsSection) -> bool {
+        let id = format!("section {}", index);
+        let name = if section.name.is_empty() { String::from("Unnamed section") } else { section.name.clone() };
+        let mut lines = vec![format!("{} {}", index, name), section.guid.to_string()];
+        let version = section.version.to_string();
+        if !version.is_empty() {
+            lines.push(format!("version {}", version));
+        }
+        lines.push(format!("data 0x{:X}, signature 0x{:X}, metadata 0x{:X}, metadata signature 0x{:X}",
+            section.data_size, section.data_sig_size, section.meta_size, section.meta_sig_size));
+        self.node(&id, "box", &lines);
+        let parent = self.parents[self.parents.len() - 1].clone();
+        self.edge(&parent, &id);
+        self.last = id;
+        true
+    }
+
+    fn on_compressed_payload(&mut self, _index : &str, data : &[u8]) {
+        let id = format!("{} zlib", self.last);
+        self.payload(id, &[String::from("zlib-compressed"), format!("0x{:X} bytes decompressed", data.len())]);
+    }
+
+    fn on_chunked_payload(&mut self, _index : &str, data : &[u8]) {
+        let id = format!("{} chunks", self.last);
+        self.payload(id, &[String::from("chunked"), format!("0x{:X} bytes assembled", data.len())]);
+    }
+
+    fn on_nesting_leave(&mut self, _depth : usize) {
+        self.parents.pop();
+    }
+
+    fn on_error(&mut self, index : &str, error : &PfsError) {
+        let id = format!("error {}", index);
+        self.out.push_str(&format!("  \"{}\" [shape=note, color=red, label={}];\n", id, label(&[error.to_string()])));
+        let from = self.last.clone();
+        self.edge(&from, &id);
+    }
+}
+
+// Graph of every PFS file found in the input, named by name
+pub fn to_dot(name : &str, data : &[u8]) -> Result<String, PfsError> {
+    let mut visitor = DotVisitor {
+        out : String::from("digraph pfs {\n  rankdir=LR;\n  node [fontname=\"monospace\"];\n"),
+        parents : vec![String::from("input")],
+        last : String::from("input"),
+        files : 0,
+    };
+    visitor.node("input", "cylinder", &[String::from(name), format!("0x{:X} bytes", data.len())]);
+    walk::pfs_walk(data, &mut visitor)?;
+    visitor.out.push_str("}\n");
+    Ok(visitor.out)
+}
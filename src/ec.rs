@@ -0,0 +1,27 @@
+//
+// Embedded Controller firmware detection
+//
+use guids::GuidNames;
+use parser::Guid;
+
+// ITE EC flash signature, found close to the start of ITE EC images
+const ITE_SIGNATURE : &[u8] = b"\xA5\xA5\xA5\xA5\xA5\xA5\xA5\xA5\x85\x12\x5A\x5A\xAA";
+
+// Only this many leading bytes are searched for signatures
+const SIGNATURE_SEARCH_SIZE : usize = 0x10000;
+
+// Dell names EC sections "Embedded Controller" in the information section
+pub fn is_ec_name(name : &str) -> bool {
+    name.contains("Embedded Controller")
+        || name.split(|c : char| !c.is_ascii_alphanumeric()).any(|word| word == "EC")
+}
+
+// Sections named as EC firmware in the GUID table, for files without information section
+pub fn is_ec_guid(guid : &Guid, names : &GuidNames) -> bool {
+    names.get(guid).is_some_and(is_ec_name)
+}
+
+pub fn has_ec_signature(data : &[u8]) -> bool {
+    let head = &data[..data.len().min(SIGNATURE_SEARCH_SIZE)];
+    head.windows(ITE_SIGNATURE.len()).any(|window| window == ITE_SIGNATURE)
+}
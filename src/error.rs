@@ -0,0 +1,101 @@
+use chain::ChainStatus;
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+//
+// Exit codes of the command line tool, scripts can rely on them
+//
+pub const EXIT_SUCCESS : i32 = 0;
+pub const EXIT_USAGE : i32 = 1;   // Invalid command line
+pub const EXIT_IO : i32 = 2;      // Input can't be opened or read
+pub const EXIT_PARSE : i32 = 3;   // Input is not a PFS file or can't be parsed
+pub const EXIT_PARTIAL : i32 = 4; // Extraction was aborted or only partly done
+pub const EXIT_VERIFY : i32 = 5;  // Verification failed
+
+//
+// Errors that abort extraction
+//
+#[derive(Debug)]
+pub enum PfsError {
+    Open(PathBuf, io::Error),  // Input can't be opened
+    Read(PathBuf, io::Error),  // Input can't be read
+    Write(PathBuf, io::Error), // Output directory or file can't be created
+    Parse,                     // Input is not a PFS file
+    Decompress(io::Error),     // Compressed data of a compressed section is damaged
+    DecompressedSize(usize),   // Compressed section expands beyond the limit
+    Layout(&'static str),      // Structure disagrees with its own sizes
+    UnknownSection,            // Section of unknown type with --on-unknown fail
+    Timeout,                   // Extraction took longer than --timeout
+    Selection(usize),          // --to-stdout or cat matched other than one section
+    Stdout(io::Error),         // Selected section can't be written to stdout
+    Checksum { stored : u32, computed : u32 }, // Footer checksum mismatch with --strict
+    Digest(&'static str),      // Section part doesn't match the digest signed for it, with --verify-hashes
+    Signature(&'static str, ChainStatus), // Signature chain of a section part isn't trusted, with --verify-signatures
+    NoRoots,                   // Signature chains are to be checked without roots to end at
+    Sections(usize),           // Sections failed, the rest was extracted
+    Unverified(usize),         // Sections failed, some of them verification, the rest was extracted
+    Truncated(usize),          // Input ends at this offset, sections in front of it were extracted
+}
+
+impl PfsError {
+    // Failures of a single section, extraction goes on with the next one unless --fail-fast is given
+    pub fn is_section_failure(&self) -> bool {
+        matches!(*self, PfsError::Write(..) | PfsError::Decompress(_) | PfsError::DecompressedSize(_) | PfsError::Digest(_) | PfsError::Signature(..))
+    }
+
+    // Failures of checks the user asked for, they exit with EXIT_VERIFY even when extraction goes on
+    pub fn is_verification_failure(&self) -> bool {
+        matches!(*self, PfsError::Checksum { .. } | PfsError::Digest(_) | PfsError::Signature(..))
+    }
+
+    // Process exit code reported by the command line tool
+    pub fn exit_code(&self) -> i32 {
+        match *self {
+            PfsError::Open(..) | PfsError::Read(..) => EXIT_IO,
+            PfsError::Parse => EXIT_PARSE,
+            PfsError::NoRoots => EXIT_USAGE,
+            PfsError::Checksum { .. } | PfsError::Digest(_) | PfsError::Signature(..) | PfsError::Unverified(_) => EXIT_VERIFY,
+            PfsError::Write(..) | PfsError::Sections(_) | PfsError::Truncated(_) | PfsError::UnknownSection | PfsError::Timeout | PfsError::Selection(_) | PfsError::Stdout(_)
+                | PfsError::Decompress(_) | PfsError::Layout(_) | PfsError::DecompressedSize(_) => EXIT_PARTIAL,
+        }
+    }
+}
+
+impl fmt::Display for PfsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PfsError::Open(ref path, ref e) => write!(f, "Can't open {:?}: {}", path, e),
+            PfsError::Read(ref path, ref e) => write!(f, "Can't read {:?}: {}", path, e),
+            PfsError::Write(ref path, ref e) => write!(f, "Can't create {:?}: {}", path, e),
+            PfsError::Parse => write!(f, "PFS file parse error"),
+            PfsError::Decompress(ref e) => write!(f, "Decompression failed: {}", e),
+            PfsError::DecompressedSize(limit) =>
+                write!(f, "Decompressed data exceeds {:#X} bytes, extraction aborted", limit),
+            PfsError::Layout(what) => write!(f, "Inconsistent PFS layout: {}", what),
+            PfsError::UnknownSection => write!(f, "Unknown section type, extraction aborted"),
+            PfsError::Timeout => write!(f, "Extraction timed out, aborted"),
+            PfsError::Selection(n) => write!(f, "Expected exactly one section to match, found {}", n),
+            PfsError::Stdout(ref e) => write!(f, "Can't write to stdout: {}", e),
+            PfsError::Checksum { stored, computed } =>
+                write!(f, "Footer checksum mismatch (stored {:08X}, computed {:08X}), extraction aborted", stored, computed),
+            PfsError::Digest(part) => write!(f, "Section {} doesn't match the digest signed for it", part),
+            PfsError::Signature(part, status) => write!(f, "Signature chain of section {} isn't trusted: {}", part, status),
+            PfsError::NoRoots => write!(f, "No roots are configured to check signature chains against"),
+            PfsError::Sections(n) => write!(f, "Extraction is incomplete, failed sections: {}", n),
+            PfsError::Unverified(n) => write!(f, "Extraction is incomplete, failed sections: {}, verification failed", n),
+            PfsError::Truncated(offset) => write!(f, "Extraction is incomplete, input is truncated at offset {:X}", offset),
+        }
+    }
+}
+
+impl error::Error for PfsError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            PfsError::Open(_, ref e) | PfsError::Read(_, ref e) | PfsError::Write(_, ref e) => Some(e),
+            PfsError::Decompress(ref e) | PfsError::Stdout(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
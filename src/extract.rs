@@ -0,0 +1,2068 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::prelude::*;
+use std::ops::Range;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use flate2::read::ZlibDecoder;
+use log::Level;
+use memmap2::Mmap;
+use archive::{Archive, ArchiveFormat};
+use chain;
+use compression;
+use content::{self, ContentType};
+use ec;
+use error::PfsError;
+use guids::GuidNames;
+use handler::{HandlerOutcome, SectionHandler};
+use manifest::{self, ManifestFile, ManifestSection, ManifestUnparsed};
+use naming::{NameTemplate, SectionFields};
+use parser;
+use progress::{Progress, ProgressMode, SharedProgress};
+use report;
+use rayon::prelude::*;
+use rayon::{ThreadPool, ThreadPoolBuilder};
+use signature;
+use stats::DataStats;
+use verify;
+
+// Number of leading bytes shown when input can't be parsed
+const HEX_DUMP_SIZE: usize = 0x40;
+
+// Compressed sections nested directly into each other are unwrapped up to this depth
+pub const MAX_COMPRESSION_LAYERS: usize = 8;
+
+// Default limit of PFS files nested into each other
+pub const MAX_DEPTH: usize = 16;
+
+// Default limit of decompressed section size, well above any Dell update
+pub const MAX_DECOMPRESSED_SIZE: usize = 0x40000000;
+
+// Section names longer than this are cut in file names
+const MAX_NAME_LENGTH: usize = 64;
+
+// Names Windows reserves for devices, with or without extension
+const RESERVED_NAMES: [&str; 22] = ["CON", "PRN", "AUX", "NUL",
+    "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9",
+    "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9"];
+
+// Bare zlib streams inflating to less than this are taken for chance matches of the header
+const DEEP_SCAN_MIN_SIZE: usize = 0x40;
+
+// Decompressed data is kept in memory until this much of it tells whether it's looked into further
+const STREAM_PREFIX_SIZE: usize = 0x10;
+
+// Large files are written in blocks of this size, progress is updated after each
+const WRITE_BLOCK_SIZE: usize = 0x100000;
+
+// What to do with sections that can't be classified beyond raw data
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownPolicy {
+    #[default]
+    Write,
+    Skip,
+    Fail,
+}
+
+// What to do with files already in the output directory
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum ExistingPolicy {
+    #[default]
+    Fail,
+    Overwrite,
+    Skip,
+    // Write under name-1.ext, name-2.ext and so on
+    Rename,
+}
+
+// Which files extraction writes
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+pub enum ExtractProfile {
+    #[default]
+    Full,
+    // Final data of sections only, without signatures, metadata and data that is extracted further
+    PayloadOnly,
+    // Sections named as BIOS and everything inside them
+    BiosOnly,
+    // Everything, with data left over after parsing, footer checksum results, byte ranges, manifest and hashes
+    Forensic,
+}
+
+// Section selected by GUID or by name, names are matched case-insensitively
+// against a pattern where * is any text and ? is any character
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SectionFilter {
+    Guid(parser::Guid),
+    Name(String),
+}
+
+impl FromStr for SectionFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<SectionFilter, String> {
+        if s.is_empty() {
+            return Err(String::from("expected GUID or name pattern"));
+        }
+        Ok(s.parse().map(SectionFilter::Guid).unwrap_or_else(|_| SectionFilter::Name(s.to_lowercase())))
+    }
+}
+
+impl SectionFilter {
+    pub fn matches(&self, guid: &parser::Guid, name: &str) -> bool {
+        match *self {
+            SectionFilter::Guid(ref g) => g == guid,
+            SectionFilter::Name(ref pattern) => {
+                let pattern: Vec<char> = pattern.chars().collect();
+                let name: Vec<char> = name.to_lowercase().chars().collect();
+                glob_match(&pattern, &name)
+            }
+        }
+    }
+}
+
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    // Position after the last * and text position it was tried at, for backtracking
+    let (mut p, mut t) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p + 1, t));
+            p += 1;
+        } else if let Some((sp, st)) = star {
+            p = sp;
+            t = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+// Extraction settings, obtained from command line by the CLI.
+// Start from Options::default() or Options::builder(), more settings are added over time
+#[non_exhaustive]
+pub struct Options {
+    // Progress of reading, decompressing and writing large data
+    pub progress: ProgressMode,
+    pub components_list: bool,
+    pub on_unknown: UnknownPolicy,
+    pub ec: bool,
+    // Append extension of the detected content type to data files
+    pub extensions: bool,
+    // Write UEFI capsule header and body of payloads into separate files
+    pub split_capsule: bool,
+    pub timeout: Option<Duration>,
+    pub extract_only: Option<parser::Guid>,
+    // Sections to extract with everything inside them, at any nesting level
+    pub only: Vec<SectionFilter>,
+    // Sections not to extract or look into
+    pub exclude: Vec<SectionFilter>,
+    pub to_stdout: bool,
+    pub byte_range: bool,
+    // Print fields of structures that are only interesting when studying the format
+    pub verbose: bool,
+    // Number of the first section in file names, 1 for compatibility with older versions
+    pub index_base: usize,
+    pub checksum_status: bool,
+    pub headerless: bool,
+    // Dry run, sections are only printed
+    pub list: bool,
+    // Collect section tree with hashes of written files
+    pub manifest: bool,
+    // Print SHA-256 of every written file and collect them for SHA256SUMS
+    pub sha256sums: bool,
+    // Abort on footer checksum mismatch or damaged chunks
+    pub strict: bool,
+    // Fail sections whose data or metadata doesn't match the digest signed in their signature
+    pub verify_hashes: bool,
+    // Fail sections whose PKCS#7 signature chain doesn't end at one of these roots, None to not check chains,
+    // there are no built-in roots and extraction is refused without any
+    pub verify_signatures: Option<Vec<chain::Certificate>>,
+    // PFS files and chunk payloads nested deeper than this are not extracted
+    pub max_depth: usize,
+    // Decompression producing more than this is aborted, layers nested directly into each other count together
+    pub max_decompressed_size: usize,
+    // Threads decompressing sections ahead of extraction, 0 for one per CPU, 1 to not decompress ahead
+    pub jobs: usize,
+    // Abort on the first failed section instead of going on with the rest
+    pub fail_fast: bool,
+    // Write files into this zip or tar archive instead of the output directory
+    pub archive: Option<PathBuf>,
+    // Nested PFS files go into subdirectories instead of getting longer name prefixes
+    pub tree: bool,
+    // Names of sections by GUID, for files without a usable information section
+    pub guid_names: GuidNames,
+    // Write data left over after parsed structures into trailing.bin files
+    pub save_unparsed: bool,
+    // Write an HTML report of the section tree into this file, the caller does it with the manifest
+    pub report: Option<PathBuf>,
+    // Write fwupd metainfo and cabinet of the BIOS payload next to the extracted files, the caller does it
+    pub fwupd: bool,
+    // Write guids.csv with names of sections for UEFITool next to the extracted files, the caller does it
+    pub guids_csv: bool,
+    // Write sbom.cdx.json with a CycloneDX component for every section next to the extracted files, the caller does it
+    pub sbom: bool,
+    // Firmware GUID for the metainfo instead of the one found in the payload
+    pub esrt_guid: Option<parser::Guid>,
+    // Look for bare zlib streams in data that isn't parsed further
+    pub deep_scan: bool,
+    // Names of section files instead of number_name_version.kind
+    pub name_template: Option<NameTemplate>,
+    pub on_existing: ExistingPolicy,
+    // Files recorded with the same SHA-256 in manifest.json of the output directory are not written again,
+    // changed ones are written over the old ones
+    pub resume: bool,
+    // Same output for the same input, the caller sets file times and line breaks of text it writes
+    pub deterministic: bool,
+    // Handlers of the caller asked before built-in handling, added with add_handler
+    pub handlers: Vec<Arc<dyn SectionHandler>>,
+    // Set with set_profile, which also turns on the options the forensic profile needs
+    pub profile: ExtractProfile,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            progress: ProgressMode::Off,
+            components_list: false,
+            on_unknown: UnknownPolicy::default(),
+            ec: false,
+            extensions: false,
+            split_capsule: false,
+            timeout: None,
+            extract_only: None,
+            only: Vec::new(),
+            exclude: Vec::new(),
+            to_stdout: false,
+            byte_range: false,
+            verbose: false,
+            index_base: 1,
+            checksum_status: false,
+            headerless: false,
+            list: false,
+            manifest: false,
+            sha256sums: false,
+            strict: false,
+            verify_hashes: false,
+            verify_signatures: None,
+            max_depth: MAX_DEPTH,
+            max_decompressed_size: MAX_DECOMPRESSED_SIZE,
+            jobs: 0,
+            fail_fast: false,
+            archive: None,
+            tree: false,
+            guid_names: GuidNames::default(),
+            save_unparsed: false,
+            report: None,
+            fwupd: false,
+            guids_csv: false,
+            sbom: false,
+            esrt_guid: None,
+            deep_scan: false,
+            name_template: None,
+            on_existing: ExistingPolicy::Fail,
+            deterministic: false,
+            resume: false,
+            handlers: Vec::new(),
+            profile: ExtractProfile::Full,
+        }
+    }
+}
+
+impl Options {
+    pub fn builder() -> OptionsBuilder {
+        OptionsBuilder::default()
+    }
+
+    // Section tree is collected for manifest.json and for the HTML report
+    fn collects_manifest(&self) -> bool {
+        self.manifest || self.report.is_some()
+    }
+
+    // Every section is extracted unless some are selected
+    fn selects_all(&self) -> bool {
+        self.extract_only.is_none() && self.only.is_empty() && self.profile != ExtractProfile::BiosOnly
+    }
+
+    pub fn add_handler<H: SectionHandler + 'static>(&mut self, handler: H) {
+        self.handlers.push(Arc::new(handler));
+    }
+
+    pub fn set_profile(&mut self, profile: ExtractProfile) {
+        self.profile = profile;
+        if profile == ExtractProfile::Forensic {
+            self.save_unparsed = true;
+            self.checksum_status = true;
+            self.byte_range = true;
+            self.manifest = true;
+            self.sha256sums = true;
+        }
+    }
+}
+
+// Options built in one expression, settings not given keep their defaults
+#[derive(Default)]
+pub struct OptionsBuilder {
+    options: Options,
+}
+
+// Setter of every plain field, named after it
+macro_rules! options_setters {
+    ($($field:ident: $type:ty,)*) => {
+        impl OptionsBuilder {
+            $(
+                pub fn $field(mut self, value: $type) -> OptionsBuilder {
+                    self.options.$field = value;
+                    self
+                }
+            )*
+        }
+    };
+}
+
+options_setters! {
+    progress: ProgressMode,
+    components_list: bool,
+    on_unknown: UnknownPolicy,
+    ec: bool,
+    extensions: bool,
+    split_capsule: bool,
+    timeout: Option<Duration>,
+    extract_only: Option<parser::Guid>,
+    only: Vec<SectionFilter>,
+    exclude: Vec<SectionFilter>,
+    to_stdout: bool,
+    byte_range: bool,
+    verbose: bool,
+    index_base: usize,
+    checksum_status: bool,
+    headerless: bool,
+    list: bool,
+    manifest: bool,
+    sha256sums: bool,
+    strict: bool,
+    verify_hashes: bool,
+    verify_signatures: Option<Vec<chain::Certificate>>,
+    max_depth: usize,
+    max_decompressed_size: usize,
+    jobs: usize,
+    fail_fast: bool,
+    archive: Option<PathBuf>,
+    tree: bool,
+    guid_names: GuidNames,
+    save_unparsed: bool,
+    report: Option<PathBuf>,
+    fwupd: bool,
+    guids_csv: bool,
+    sbom: bool,
+    esrt_guid: Option<parser::Guid>,
+    deep_scan: bool,
+    name_template: Option<NameTemplate>,
+    on_existing: ExistingPolicy,
+    resume: bool,
+    deterministic: bool,
+}
+
+impl OptionsBuilder {
+    pub fn handler<H: SectionHandler + 'static>(mut self, handler: H) -> OptionsBuilder {
+        self.options.add_handler(handler);
+        self
+    }
+
+    pub fn profile(mut self, profile: ExtractProfile) -> OptionsBuilder {
+        self.options.set_profile(profile);
+        self
+    }
+
+    pub fn build(self) -> Options {
+        self.options
+    }
+}
+
+// State shared by all nesting levels of an extraction
+struct Context<'a> {
+    options: &'a Options,
+    // Directory all files are written into
+    dir: &'a Path,
+    // Addresses of the input data, to tell offsets of slices borrowed from it
+    input: Range<usize>,
+    // Extraction is aborted after this point in time
+    deadline: Option<Instant>,
+    // Section index and name of every written file
+    components: Vec<(String, String)>,
+    // Whether current section is selected for extraction
+    selected: bool,
+    // Final data of selected sections when writing to stdout
+    captured: Vec<Vec<u8>>,
+    // Every section seen so far, when manifest is requested
+    manifest: Vec<ManifestSection>,
+    // Hash of every written file, when SHA256SUMS is requested
+    hashes: Vec<ManifestFile>,
+    // Data left over at every level, when manifest is requested
+    unparsed: Vec<ManifestUnparsed>,
+    // Threads for decompression ahead of extraction
+    pool: Option<ThreadPool>,
+    // Number of sections extracted, and indexes of failed ones
+    sections: usize,
+    failed: Vec<String>,
+    // Failed sections that failed verification
+    unverified: usize,
+    // Where written files go
+    output: Output,
+    // SHA-256 of files by name from manifest.json of an earlier run, with --resume
+    recorded: HashMap<String, String>,
+    // Files found unchanged and not written again
+    unchanged: usize,
+}
+
+// Files go into the output directory unless an archive or memory is given
+enum Output {
+    Directory,
+    Archive(Archive),
+    // Name and data of every file, for callers without a filesystem like WebAssembly
+    Memory(Vec<(String, Vec<u8>)>),
+}
+
+impl<'a> Context<'a> {
+    fn new(options: &'a Options, data: &[u8], dir: &'a Path, output: Output) -> Context<'a> {
+        Context {
+            options,
+            dir,
+            input: data.as_ptr() as usize..data.as_ptr() as usize + data.len(),
+            deadline: options.timeout.map(|timeout| Instant::now() + timeout),
+            pool: if options.jobs == 1 { None } else { ThreadPoolBuilder::new().num_threads(options.jobs).build().ok() },
+            components: Vec::new(),
+            selected: options.selects_all(),
+            captured: Vec::new(),
+            manifest: Vec::new(),
+            hashes: Vec::new(),
+            unparsed: Vec::new(),
+            sections: 0,
+            failed: Vec::new(),
+            unverified: 0,
+            recorded: if options.resume && matches!(output, Output::Directory) { recorded_files(dir) } else { HashMap::new() },
+            unchanged: 0,
+            output,
+        }
+    }
+
+    // Name with extension of the detected content type, when requested
+    fn typed_name(&self, data: &[u8], name: String) -> String {
+        if !self.options.extensions {
+            return name;
+        }
+        let content = content::content_type(data);
+        info!("Content type of {}: {}", name, content);
+        name + content.extension()
+    }
+
+    // Offset of the data in the input, decompressed data has none
+    fn input_offset(&self, data: &[u8]) -> Option<usize> {
+        let start = data.as_ptr() as usize;
+        if start >= self.input.start && start + data.len() <= self.input.end {
+            Some(start - self.input.start)
+        } else {
+            None
+        }
+    }
+
+    fn check_timeout(&self) -> Result<(), PfsError> {
+        match self.deadline {
+            Some(deadline) if Instant::now() >= deadline => Err(PfsError::Timeout),
+            _ => Ok(())
+        }
+    }
+
+    // Whether files of current section are written to the output directory
+    fn writes_files(&self) -> bool {
+        self.selected && !self.options.to_stdout && !self.options.list
+    }
+
+    // Whether signatures, metadata and data extracted further are written along with final data
+    fn writes_intermediate(&self) -> bool {
+        self.options.profile != ExtractProfile::PayloadOnly
+    }
+
+    // Name a file is written under with the policy for existing files, none if it's skipped
+    fn output_name(&self, filename: &str) -> Option<String> {
+        let exists = |name: &str| match self.output {
+            Output::Memory(ref files) => files.iter().any(|f| f.0 == name),
+            _ => self.dir.join(name).exists(),
+        };
+        match self.options.on_existing {
+            ExistingPolicy::Skip if exists(filename) => None,
+            ExistingPolicy::Rename if exists(filename) => (1..).map(|n| numbered_name(filename, n)).find(|name| !exists(name)),
+            _ => Some(String::from(filename)),
+        }
+    }
+
+    // File of an earlier run with the same data, going by the manifest and the size of the file
+    fn is_unchanged(&self, filename: &str, data: &[u8]) -> bool {
+        match self.recorded.get(filename) {
+            Some(sha256) => fs::metadata(self.dir.join(filename)).is_ok_and(|m| m.len() == data.len() as u64) && *sha256 == manifest::sha256(data),
+            None => false,
+        }
+    }
+
+    // Returns whether the file was written, or is there already and skipped
+    fn write_file(&mut self, index: &str, data: &[u8], filename: &str) -> Result<bool, PfsError> {
+        if !self.writes_files() {
+            if self.options.list && self.selected {
+                list_stats(data, filename);
+            }
+            return Ok(false);
+        }
+        if matches!(self.output, Output::Directory) && self.is_unchanged(filename, data) {
+            debug!("File unchanged, not written again: {}", filename);
+            self.unchanged += 1;
+            self.add_file(index, data, filename);
+            return Ok(true);
+        }
+        // Components, manifest and hashes name files as they are stored
+        let stored = match self.output {
+            Output::Archive(ref mut archive) => {
+                let name = if self.options.tree { String::from(filename) } else { archive_name(index, filename) };
+                archive.add(&name, data).map_err(|e| PfsError::Write(archive.path().to_path_buf(), e))?;
+                name
+            }
+            Output::Memory(_) | Output::Directory => match self.output_name(filename) {
+                None => {
+                    info!("File exists, skipped: {}", filename);
+                    String::from(filename)
+                }
+                Some(name) => {
+                    let overwrite = self.options.on_existing == ExistingPolicy::Overwrite || self.options.resume;
+                    if let Output::Memory(ref mut files) = self.output {
+                        // Same names fail as they would in a directory
+                        match files.iter().position(|f| f.0 == name) {
+                            Some(i) if overwrite => files[i].1 = data.to_vec(),
+                            Some(_) => return Err(PfsError::Write(PathBuf::from(name), io::Error::from(io::ErrorKind::AlreadyExists))),
+                            None => files.push((name.clone(), data.to_vec())),
+                        }
+                    } else {
+                        write_file(data, &self.dir.join(&name), overwrite, self.options.progress)?;
+                    }
+                    name
+                }
+            },
+        };
+        debug!("File written: {}, size {:X}", stored, data.len());
+        self.add_file(index, data, &stored);
+        Ok(true)
+    }
+
+    // Report data left over after parsing, and write it if requested
+    fn save_unparsed(&mut self, index: &str, data: &[u8], filename: &str) -> Result<(), PfsError> {
+        warn!("Unparsed size: {:X}", data.len());
+        let written = self.options.save_unparsed && self.write_file(index, data, filename)?;
+        if self.options.collects_manifest() {
+            self.unparsed.push(ManifestUnparsed {
+                index: String::from(index),
+                offset: self.input_offset(data),
+                size: data.len(),
+                file: if written { Some(String::from(filename)) } else { None },
+            });
+        }
+        Ok(())
+    }
+
+    // Record a file written to the output directory
+    fn add_file(&mut self, index: &str, data: &[u8], filename: &str) {
+        self.components.push((String::from(index), String::from(filename)));
+        if !self.options.collects_manifest() && !self.options.sha256sums {
+            return;
+        }
+        let file = ManifestFile::new(filename, data);
+        if self.options.sha256sums {
+            info!("SHA-256: {} {}", file.sha256, filename);
+            self.hashes.push(file.clone());
+        }
+        if let Some(section) = self.manifest.iter_mut().rev().find(|s| s.index == index) {
+            section.files.push(file);
+        }
+    }
+}
+
+
+// Files of an earlier extraction into the directory, none if it has no readable manifest
+fn recorded_files(dir: &Path) -> HashMap<String, String> {
+    let sections = fs::read_to_string(dir.join(manifest::MANIFEST_JSON)).ok().and_then(|text| manifest::from_json(&text));
+    let recorded: HashMap<String, String> = sections.iter().flatten().flat_map(|s| s.files.iter()).map(|f| (f.name.clone(), f.sha256.clone())).collect();
+    if recorded.is_empty() {
+        info!("No files recorded in {:?}, everything is written", dir.join(manifest::MANIFEST_JSON));
+    }
+    recorded
+}
+
+// Statistics of a file that would be written, random data of no known format is pointed out
+fn list_stats(data: &[u8], filename: &str) {
+    let stats = DataStats::of(data);
+    if stats.is_high_entropy(data.len()) && content::content_type(data) == ContentType::Raw {
+        info!("Statistics: {}: {}, high entropy, likely compressed or encrypted", filename, stats);
+    } else {
+        info!("Statistics: {}: {}", filename, stats);
+    }
+}
+
+// Files of nested sections go into directories named by indexes of the sections they are in
+fn archive_name(index: &str, filename: &str) -> String {
+    match index.rfind('.') {
+        Some(end) => format!("{}/{}", index[..end].replace('.', "/"), filename),
+        None => String::from(filename),
+    }
+}
+
+// Name prefix of files extracted from data nested into base, directories can't end with a dot on Windows
+fn nested_prefix(ctx: &Context, base: &str) -> String {
+    if ctx.options.tree {
+        format!("{}/", base.trim_end_matches('.'))
+    } else {
+        format!("{}_", base)
+    }
+}
+
+// Names of the files of one section, parts found inside its data extend the kind
+#[derive(Clone)]
+struct SectionNames {
+    // Name up to the kind in the built-in layout, prefix included
+    base: String,
+    prefix: String,
+    fields: SectionFields,
+    template: Option<NameTemplate>,
+    kind: String,
+}
+
+impl SectionNames {
+    fn file(&self, kind: &str) -> String {
+        match self.template {
+            Some(ref template) => format!("{}{}", self.prefix, template.render(&self.fields, &format!("{}{}", self.kind, kind))),
+            None => format!("{}{}{}", self.base, self.kind, kind),
+        }
+    }
+
+    fn inner(&self, part: &str) -> SectionNames {
+        let mut inner = self.clone();
+        inner.kind.push_str(part);
+        inner
+    }
+}
+
+// name-1.ext for name.ext, the number goes in front of the last extension
+fn numbered_name(filename: &str, n: usize) -> String {
+    let (dir, file) = filename.split_at(filename.rfind('/').map_or(0, |i| i + 1));
+    match file.rfind('.') {
+        Some(dot) if dot > 0 => format!("{}{}-{}{}", dir, &file[..dot], n, &file[dot..]),
+        _ => format!("{}{}-{}", dir, file, n),
+    }
+}
+
+// Directories of --tree layout are created along with their first file
+fn create_file(path: &Path, overwrite: bool) -> Result<File, PfsError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| PfsError::Write(parent.to_path_buf(), e))?;
+    }
+    OpenOptions::new().write(true)
+                      .create_new(!overwrite)
+                      .create(overwrite)
+                      .truncate(overwrite)
+                      .open(path)
+                      .map_err(|e| PfsError::Write(path.to_path_buf(), e))
+}
+
+// Written block by block to show progress of large files
+fn write_file(data: &[u8], path: &Path, overwrite: bool, progress: ProgressMode) -> Result<(), PfsError> {
+    let mut file = create_file(path, overwrite)?;
+    let label = format!("Writing {}", path.file_name().unwrap_or_default().to_string_lossy());
+    let mut progress = Progress::new(&label, data.len() as u64, progress);
+    let mut written = 0;
+    for block in data.chunks(WRITE_BLOCK_SIZE) {
+        file.write_all(block).map_err(|e| PfsError::Write(path.to_path_buf(), e))?;
+        written += block.len();
+        progress.update(written as u64);
+    }
+    progress.finish();
+    Ok(())
+}
+
+
+// Section names come from the file itself, so anything that could leave the output directory
+// or isn't allowed on Windows is replaced, spaces are replaced as before
+pub fn sanitize_name(name: &str) -> String {
+    let mut safe: String = name.chars()
+        .take(MAX_NAME_LENGTH)
+        .map(|c| if c == ' ' || c.is_control() || "/\\:*?\"<>|".contains(c) { '_' } else { c })
+        .collect();
+    while safe.contains("..") {
+        safe = safe.replace("..", "_");
+    }
+    let stem = safe.split('.').next().unwrap_or("");
+    if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+        safe.insert(0, '_');
+    }
+    safe
+}
+
+
+// Returns whether information section was parsed,
+// sections it doesn't name are named by GUID or content if known
+pub fn pfs_set_names(file: &mut parser::PfsFile, guid_names: &GuidNames) -> bool {
+    let has_info = pfs_set_info_names(file);
+    pfs_set_guid_names(file, guid_names);
+    has_info
+}
+
+// Names sections that have none by GUID, or by content for GUIDs not in the table
+pub fn pfs_set_guid_names(file: &mut parser::PfsFile, guid_names: &GuidNames) {
+    for section in file.sections.iter_mut().filter(|s| s.name.is_empty()) {
+        let by_content = || section.data.and_then(content::section_name);
+        if let Some(name) = guid_names.get(&section.guid).or_else(by_content) {
+            section.name = String::from(name);
+        }
+    }
+}
+
+fn pfs_set_info_names(file: &mut parser::PfsFile) -> bool {
+    // Information section is the last one
+    let (info_section, other_sections) = match file.sections.split_last_mut() {
+        Some(split) => split,
+        None => return false,
+    };
+    if let Some(info_data) = info_section.data {
+        match parser::pfs_info(info_data) {
+            Ok((unp, info)) => {
+                if !unp.is_empty() {
+                    warn!("Unparsed size: {:X}", unp.len());
+                }
+
+                // Set section names, entries are matched to sections by GUID
+                // and by position only when GUID doesn't resolve
+                info_section.name = String::from("Section Info");
+                let mut named = vec![false; other_sections.len()];
+                let count = info.len();
+                for (i, section) in info.into_iter().enumerate() {
+                    if !section.name_terminated {
+                        warn!("Name {:?} in information section is not terminated", section.name);
+                    }
+                    let by_index = i < other_sections.len() && !named[i];
+                    let target = if by_index && other_sections[i].guid == section.guid {
+                        Some(i)
+                    } else {
+                        other_sections.iter().enumerate()
+                            .position(|(j, s)| !named[j] && s.guid == section.guid)
+                            .or(if by_index { Some(i) } else { None })
+                    };
+                    if let Some(j) = target {
+                        other_sections[j].name = section.name.clone();
+                        other_sections[j].info = Some(section);
+                        named[j] = true;
+                    }
+                }
+                if count + 1 == other_sections.len() && !named[count] {
+                    other_sections[count].name = String::from(content::MODEL_PROPERTIES_NAME);
+                }
+                return true;
+            }
+            _ => { warn!("PFS info section parse error, falling back to generic names"); }
+        }
+    }
+    false
+}
+
+
+// Where progress of a decompression goes
+#[derive(Clone, Copy)]
+enum Report<'a> {
+    Own(&'a str),
+    // Sections decompressed ahead in parallel share one progress line
+    Shared(&'a SharedProgress),
+}
+
+// Only the compressed size stored in the section is passed as data,
+// output is capped to not exhaust memory on crafted sections, together with
+// the given size already produced by outer compression layers
+fn decompress(ctx: &Context, data: &[u8], report: Report, used: usize) -> Result<Vec<u8>, PfsError> {
+    let mut decompressed = Vec::new();
+    decompress_with(ctx, data, report, used, |block| {
+        decompressed.extend_from_slice(block);
+        Ok(())
+    })?;
+    Ok(decompressed)
+}
+
+// Decompressed data is passed to output block by block
+fn decompress_with<F>(ctx: &Context, data: &[u8], report: Report, used: usize, mut output: F) -> Result<(), PfsError>
+    where F: FnMut(&[u8]) -> Result<(), PfsError>
+{
+    let mut progress = match report {
+        Report::Own(label) => Progress::new(label, data.len() as u64, ctx.options.progress),
+        _ => Progress::new("", 0, ProgressMode::Off),
+    };
+    let mut reported = 0;
+    let mut size = 0;
+    let limit = ctx.options.max_decompressed_size;
+    compression::decompress_with(data, limit, |block, consumed| {
+        if used + size + block.len() > limit {
+            return Err(PfsError::DecompressedSize(limit));
+        }
+        size += block.len();
+        output(block)?;
+        progress.update(consumed);
+        if let Report::Shared(shared) = report {
+            shared.add(consumed - reported);
+            reported = consumed;
+        }
+        ctx.check_timeout()
+    })?;
+    progress.finish();
+    debug!("Decompressed {:X} bytes into {:X}", data.len(), size);
+    Ok(())
+}
+
+// Where decompressed data goes, only data that can't be a PFS file or another
+// compressed section is written straight to its file
+enum Decompression {
+    Memory(Vec<u8>),
+    File(File, PathBuf),
+}
+
+impl Decompression {
+    fn write(&mut self, block: &[u8], path: &Path) -> Result<(), PfsError> {
+        match *self {
+            Decompression::File(ref mut file, ref path) => {
+                return file.write_all(block).map_err(|e| PfsError::Write(path.clone(), e));
+            }
+            Decompression::Memory(ref mut data) => {
+                data.extend_from_slice(block);
+                if data.len() < STREAM_PREFIX_SIZE || is_nested_prefix(data) {
+                    return Ok(());
+                }
+            }
+        }
+        let mut file = create_file(path, false)?;
+        if let Decompression::Memory(ref data) = *self {
+            file.write_all(data).map_err(|e| PfsError::Write(path.to_path_buf(), e))?;
+        }
+        *self = Decompression::File(file, path.to_path_buf());
+        Ok(())
+    }
+
+    // Decompression failed partway, data written so far isn't left behind as if it was complete
+    fn discard(self) {
+        if let Decompression::File(file, path) = self {
+            drop(file);
+            if let Err(e) = fs::remove_file(&path) {
+                warn!("Can't remove partially decompressed {:?}: {}", path, e);
+            }
+        }
+    }
+}
+
+// Data starting as a PFS file or a compressed section is extracted further
+fn is_nested_prefix(data: &[u8]) -> bool {
+    data.starts_with(b"PFS.HDR.") || data.get(4..4 + parser::PFS_COMPRESSED_SIGNATURE.len()) == Some(parser::PFS_COMPRESSED_SIGNATURE)
+}
+
+// Data written while decompressing is mapped back for content checks,
+// so it doesn't have to stay in memory
+fn pfs_finish_streamed(ctx: &mut Context, index: &str, path: &Path, name: String) -> Result<(), PfsError> {
+    let map = File::open(path).and_then(|file| unsafe { Mmap::map(&file) }).map_err(|e| PfsError::Read(path.to_path_buf(), e))?;
+    let typed = ctx.typed_name(&map, name.clone());
+    if typed != name {
+        let target = ctx.dir.join(&typed);
+        if target.exists() {
+            return Err(PfsError::Write(target, io::Error::from(io::ErrorKind::AlreadyExists)));
+        }
+        fs::rename(path, &target).map_err(|e| PfsError::Write(target, e))?;
+    }
+    ctx.add_file(index, &map, &typed);
+    if parser::classify(&map) != parser::InputKind::Pfs {
+        pfs_report_uefi(ctx, index, &map, &typed)?;
+    }
+    report_parse_error(&map, false);
+    Ok(())
+}
+
+
+// Section data and chunk payloads that are compressed sections or made of chunks give more data
+fn is_extracted_further(data: &[u8]) -> bool {
+    matches!(parser::section_kind(data), parser::SectionKind::Compressed | parser::SectionKind::Chunked)
+}
+
+// Decompressed data is final unless it's compressed once more or a PFS file of its own
+fn is_final_decompressed(data: &[u8]) -> bool {
+    parser::pfs_compressed_section(data).is_err() && parser::classify(data) != parser::InputKind::Pfs
+}
+
+// Result of decompressing a section ahead of its extraction
+type Decompressed = Result<Vec<u8>, PfsError>;
+
+// Data obtained from a section by decompression or chunk assembly
+struct Nested {
+    data: Vec<u8>,
+    // Decompressed data is extracted as PFS file with these name and index prefixes
+    pfs: Option<(String, String)>,
+}
+
+// Firmware volumes and capsule header are reported, so it's clear what to open in UEFITool
+fn pfs_report_uefi(ctx: &mut Context, index: &str, data: &[u8], filename: &str) -> Result<(), PfsError> {
+    let volumes = content::find_firmware_volumes(data);
+    if !volumes.is_empty() {
+        let offsets: Vec<String> = volumes.iter().map(|offset| format!("0x{:X}", offset)).collect();
+        info!("{} contains {} UEFI firmware volumes at offsets {}", filename, volumes.len(), offsets.join(", "));
+    }
+
+    if let Ok((_, header)) = parser::uefi_capsule_header(data) {
+        info!("{} starts with UEFI capsule {}, header size 0x{:X}, image size 0x{:X}", filename, header.guid, header.header_size, header.image_size);
+        let header_size = header.header_size as usize;
+        if ctx.options.split_capsule && header_size <= data.len() {
+            ctx.write_file(index, &data[..header_size], &format!("{}.capsule_header", filename))?;
+            ctx.write_file(index, &data[header_size..], &format!("{}.capsule_body", filename))?;
+        }
+    }
+    Ok(())
+}
+
+// Signature and footer variants of compressed sections, the signature is written like the ones of sections
+fn pfs_compressed_trailer(ctx: &mut Context, index: &str, comp: &parser::PfsCompressedSection, name: &str) -> Result<(), PfsError> {
+    if comp.footer.len() != parser::PFS_COMPRESSED_FOOTER_SIZE {
+        info!("Compressed section footer size: {:X}", comp.footer.len());
+    }
+    if let Some(signature) = comp.signature {
+        info!("Compressed data is signed, signature size: {:X}", signature.len());
+        let name = format!("{}.sig", name);
+        if ctx.writes_intermediate() {
+            ctx.write_file(index, signature, &name)?;
+        }
+        pfs_extract_signature(ctx, index, signature, &name)?;
+    }
+    Ok(())
+}
+
+// Stream at the start of data with its compressed size, when it inflates to its end with a valid checksum
+fn inflate_embedded(ctx: &Context, data: &[u8]) -> Option<(usize, Vec<u8>)> {
+    let limit = ctx.options.max_decompressed_size;
+    let mut decoder = ZlibDecoder::new(data);
+    let mut decompressed = Vec::new();
+    decoder.by_ref().take(limit as u64 + 1).read_to_end(&mut decompressed).ok()?;
+    if decompressed.len() > limit {
+        debug!("Embedded zlib stream is larger than {:X}, skipped", limit);
+        return None;
+    }
+    if decompressed.len() < DEEP_SCAN_MIN_SIZE {
+        return None;
+    }
+    Some((decoder.total_in() as usize, decompressed))
+}
+
+// Bare zlib streams without a compressed section around them, found with --deep-scan.
+// Every stream is written as embedded_N.decompressed and scanned the same way
+fn pfs_deep_scan(ctx: &mut Context, index: &str, data: &[u8], names: &SectionNames, layer: usize) -> Result<(), PfsError> {
+    let mut next = 0;
+    let mut number = 0;
+    for offset in content::find_zlib_headers(data) {
+        if offset < next {
+            continue;
+        }
+        let (size, decompressed) = match inflate_embedded(ctx, &data[offset..]) {
+            Some(stream) => stream,
+            None => continue,
+        };
+        ctx.check_timeout()?;
+        number += 1;
+        next = offset + size;
+        info!("Embedded zlib stream at offset {:X}, compressed size {:X}, decompressed size {:X}", offset, size, decompressed.len());
+        let embedded = names.inner(&format!("embedded_{}.", number));
+        let name = ctx.typed_name(&decompressed, embedded.file("decompressed"));
+        ctx.write_file(index, &decompressed, &name)?;
+        pfs_report_uefi(ctx, index, &decompressed, &name)?;
+        if layer == MAX_COMPRESSION_LAYERS {
+            warn!("Too many compression layers, {} is not scanned", name);
+            continue;
+        }
+        pfs_deep_scan(ctx, index, &decompressed, &embedded, layer + 1)?;
+    }
+    Ok(())
+}
+
+// Files produced from data are named as parts of the section,
+// returns the innermost data obtained by decompression or chunk assembly
+fn pfs_extract_nested(ctx: &mut Context, data: &[u8], names: &SectionNames, index: &str, mut prefetched: Option<Decompressed>) -> Result<Option<Nested>, PfsError> {
+    let mut nested: Option<Nested> = None;
+    let mut names = names.clone();
+    // Chunk payloads made of chunks once more are unwrapped up to the nesting limit
+    let mut chunk_levels = 0;
+    loop {
+        let current = match nested {
+            Some(ref n) => &n.data[..],
+            None => data,
+        };
+
+        // Try parsing as PFS compressed section
+        if let Ok((rest, comp)) = parser::pfs_compressed_section(current) {
+            // This is a PFS compressed section
+            info!("PFS section type: {}-compressed", compression::compression_type(comp.data));
+            if !rest.is_empty() {
+                ctx.save_unparsed(index, rest, &names.file("trailing.bin"))?;
+            }
+            pfs_compressed_trailer(ctx, index, &comp, &names.file("zlib"))?;
+
+            // Decompress section data from Zlib-compressed data, straight into the file
+            // when it's written, can't be extracted further and isn't there already
+            let mut decompressed = match prefetched.take() {
+                Some(result) => result?,
+                None if ctx.writes_files() && matches!(ctx.output, Output::Directory) && !ctx.options.deep_scan
+                    && ctx.options.on_existing == ExistingPolicy::Fail && !ctx.options.resume => {
+                    let name = names.file("decompressed");
+                    let path = ctx.dir.join(&name);
+                    let mut output = Decompression::Memory(Vec::new());
+                    if let Err(e) = decompress_with(ctx, comp.data, Report::Own(&format!("Decompressing {}", names.file(""))), 0, |block| output.write(block, &path)) {
+                        output.discard();
+                        return Err(e);
+                    }
+                    match output {
+                        Decompression::Memory(data) => data,
+                        Decompression::File(file, path) => {
+                            drop(file);
+                            pfs_finish_streamed(ctx, index, &path, name)?;
+                            return Ok(None);
+                        }
+                    }
+                }
+                None => decompress(ctx, comp.data, Report::Own(&format!("Decompressing {}", names.file(""))), 0)?,
+            };
+
+            // Write decompressed data to a file
+            let name = ctx.typed_name(&decompressed, names.file("decompressed"));
+            if ctx.writes_intermediate() || is_final_decompressed(&decompressed) {
+                ctx.write_file(index, &decompressed, &name)?;
+            }
+
+            // Decompressed data can be compressed once more, all layers together are limited
+            // like a single one, so a stack of layers can't exhaust memory either
+            let mut layer = 1;
+            let mut used = decompressed.len();
+            while let Ok((_, inner)) = parser::pfs_compressed_section(&decompressed) {
+                if layer == MAX_COMPRESSION_LAYERS {
+                    warn!("Too many compression layers, stopped at {}", layer);
+                    break;
+                }
+                layer += 1;
+                info!("PFS section type: {}-compressed, layer {}", compression::compression_type(inner.data), layer);
+                pfs_compressed_trailer(ctx, index, &inner, &names.file(&format!("zlib{}", layer)))?;
+                let next = decompress(ctx, inner.data, Report::Own(&format!("Decompressing {} layer {}", names.file(""), layer)), used)?;
+                used += next.len();
+                decompressed = next;
+                let name = ctx.typed_name(&decompressed, names.file(&format!("decompressed{}", layer)));
+                if ctx.writes_intermediate() || is_final_decompressed(&decompressed) {
+                    ctx.write_file(index, &decompressed, &name)?;
+                }
+            }
+
+            if parser::classify(&decompressed) != parser::InputKind::Pfs {
+                pfs_report_uefi(ctx, index, &decompressed, &name)?;
+            }
+
+            // Decompressed data is extracted as PFS file
+            return Ok(Some(Nested {
+                data: decompressed,
+                pfs: Some((nested_prefix(ctx, &names.file("")), format!("{}.", index))),
+            }));
+        }
+
+        // Try parsing as PFS subsection
+        let sub = match parser::pfs_file(current) {
+            Ok((rest, sub)) => {
+                // This is a PFS subsection
+                info!("PFS section type: subsection");
+                if !rest.is_empty() {
+                    ctx.save_unparsed(index, rest, &names.file("trailing.bin"))?;
+                }
+                check_footer(ctx, current, &sub)?;
+                sub
+            }
+            _ => return Ok(nested),
+        };
+
+        // Obtain chunks together with their metadata
+        let mut chunks = Vec::new();
+        for chunk in sub.sections {
+            let chunk_data = match chunk.data {
+                Some(chunk_data) => chunk_data,
+                None => continue,
+            };
+
+            match parser::pfs_chunk(chunk_data) {
+                Ok((_, ch)) => {
+                    chunks.push((ch, chunk.meta.and_then(|m| parser::pfs_chunk_metadata(m).ok()).map(|(_, m)| m)));
+                }
+                _ => {
+                    chunks.clear();
+                    break;
+                }
+            }
+        }
+        if chunks.is_empty() {
+            return Ok(nested);
+        }
+
+        // Sort the obtained chunks
+        chunks.sort_by(|a, b| a.0.cmp(&b.0));
+
+        // Damaged chunk lists still give a payload, it's just reported as such
+        let problems = check_chunks(&chunks);
+        for problem in &problems {
+            warn!("Chunk problem: {}", problem);
+        }
+        if !problems.is_empty() && ctx.options.strict {
+            return Err(PfsError::Layout("chunks are missing, duplicated or out of range"));
+        }
+
+        // Combine sorted chunks into vector, writing their signatures along the way
+        let mut payload = Vec::new();
+        let mut previous = None;
+        for (ch, meta) in &chunks {
+            if ctx.options.verbose {
+                debug!("Chunk {}: GUID {}, header version {:X}, chunk size {:X}, signature size {:X}, payload size {:X}, total chunks {}",
+                      ch.order_number, ch.guid, ch.header_version, ch.chunk_size, ch.signature_size, ch.payload_size, ch.total_chunks);
+            }
+            if let Some(signature) = ch.signature {
+                // Duplicated chunks are reported already, their signatures would overwrite each other
+                if previous != Some(ch.order_number) && ctx.writes_intermediate() {
+                    ctx.write_file(index, signature, &names.file(&format!("chunk{}.sig", ch.order_number)))?;
+                }
+            }
+            previous = Some(ch.order_number);
+            if ctx.options.byte_range {
+                let mut line = format!("Chunk {}: payload range 0x{:X}-0x{:X}", ch.order_number, payload.len(), payload.len() + ch.data.len());
+                if let Some(meta) = meta {
+                    line.push_str(&format!(", flash address 0x{:X}", meta.address));
+                }
+                info!("{}", line);
+            }
+            payload.extend_from_slice(ch.data);
+        }
+
+        // Write payload to file, it's the final one when the nesting limit is reached
+        chunk_levels += 1;
+        let last = chunk_levels == ctx.options.max_depth && is_extracted_further(&payload);
+        if last {
+            warn!("Maximum nesting depth {} reached, chunk payload in section {} is not extracted", ctx.options.max_depth, index);
+        }
+        let name = ctx.typed_name(&payload, names.file("data.payload"));
+        if ctx.writes_intermediate() || last || !is_extracted_further(&payload) {
+            ctx.write_file(index, &payload, &name)?;
+        }
+        pfs_report_uefi(ctx, index, &payload, &name)?;
+        if last {
+            return Ok(Some(Nested { data: payload, pfs: None }));
+        }
+
+        // Payload itself can be compressed or another PFS file
+        names = names.inner("payload.");
+        nested = Some(Nested { data: payload, pfs: None });
+    }
+}
+
+
+// Problems of sorted chunks, both declared values and metadata are checked when present
+fn check_chunks(chunks: &[(parser::PfsChunk, Option<parser::PfsChunkMetadata>)]) -> Vec<String> {
+    let mut problems = Vec::new();
+    for pair in chunks.windows(2) {
+        let (a, b) = (pair[0].0.order_number, pair[1].0.order_number);
+        if a == b {
+            problems.push(format!("chunk {} is duplicated", a));
+        } else if b > a + 1 {
+            problems.push(format!("chunks {}-{} are missing", a + 1, b - 1));
+        }
+    }
+
+    // Declared values have to agree between all chunks to be checked against
+    let total = chunks[0].0.total_chunks;
+    if total != 0 && chunks.iter().all(|c| c.0.total_chunks == total) {
+        let last = chunks[chunks.len() - 1].0.order_number;
+        if last >= total {
+            problems.push(format!("chunk {} is out of range, {} chunks declared", last, total));
+        }
+        if chunks.len() != total as usize {
+            problems.push(format!("{} chunks found, {} declared", chunks.len(), total));
+        }
+    }
+    for (ch, _) in chunks.iter().filter(|c| c.0.chunk_size != 0 && c.0.chunk_size as usize != c.0.data.len()) {
+        problems.push(format!("chunk {} has 0x{:X} bytes, 0x{:X} declared", ch.order_number, ch.data.len(), ch.chunk_size));
+    }
+    let size: usize = chunks.iter().map(|c| c.0.data.len()).sum();
+    let declared = chunks[0].0.payload_size;
+    if declared != 0 && chunks.iter().all(|c| c.0.payload_size == declared) && declared as usize != size {
+        problems.push(format!("payload size is 0x{:X}, 0x{:X} declared", size, declared));
+    }
+
+    // Metadata offsets are compared relative to the first chunk
+    let base = chunks[0].1.map_or(0, |meta| meta.offset as usize);
+    let mut offset = 0;
+    for (ch, meta) in chunks {
+        if let Some(meta) = meta {
+            if meta.data_size as usize != ch.data.len() {
+                problems.push(format!("chunk {} has 0x{:X} bytes, metadata declares 0x{:X}", ch.order_number, ch.data.len(), meta.data_size));
+            }
+            if meta.offset as usize != base + offset {
+                problems.push(format!("chunk {} is at payload offset 0x{:X}, metadata declares 0x{:X}", ch.order_number, base + offset, meta.offset));
+            }
+        }
+        offset += ch.data.len();
+    }
+    problems
+}
+
+
+//
+// Extraction worklist
+//
+
+// Where sections of a frame come from
+enum Source {
+    Pfs,                  // PFS file, possibly followed by more of them
+    Headerless,           // Sections without header and footer
+    Truncated,            // PFS file cut off before its end, sections in front of the cut
+    Embedded(Vec<usize>), // PFS files at given offsets in other data
+}
+
+// Data with PFS files being extracted, files nested into its sections
+// are extracted from frames pushed on top of it
+struct Frame<'a> {
+    data: Cow<'a, [u8]>,
+    source: Source,
+    // Nesting level, 0 for the input itself
+    depth: usize,
+    // Name and index prefixes of everything extracted from data, with --tree name prefix is a directory
+    base_prefix: String,
+    base_index: String,
+    // Whether the section data came from is selected
+    parent_selected: bool,
+    // Current file number, starting from 1, its start in data and prefixes
+    number: usize,
+    start: usize,
+    prefix: String,
+    index_prefix: String,
+    // Header version and section offsets in data of current file, sections are parsed
+    // again from them one by one as they are extracted
+    header_version: u32,
+    sections: Vec<usize>,
+    // Section names of current file and whether they came from information section
+    names: Vec<String>,
+    // Information section records of sections of current file
+    records: Vec<Option<parser::PfsInfoSection>>,
+    has_info: bool,
+    // Next section of current file to extract
+    next: usize,
+    // Sections of current file already decompressed by the thread pool
+    prefetched: Vec<Option<Decompressed>>,
+}
+
+impl<'a> Frame<'a> {
+    fn new(data: Cow<'a, [u8]>, source: Source, depth: usize, prefix: &str, index_prefix: &str, parent_selected: bool) -> Frame<'a> {
+        Frame {
+            data,
+            source,
+            depth,
+            base_prefix: String::from(prefix),
+            base_index: String::from(index_prefix),
+            parent_selected,
+            number: 0,
+            start: 0,
+            prefix: String::new(),
+            index_prefix: String::new(),
+            header_version: 0,
+            sections: Vec::new(),
+            names: Vec::new(),
+            records: Vec::new(),
+            has_info: false,
+            next: 0,
+            prefetched: Vec::new(),
+        }
+    }
+
+    fn file(&self) -> Result<parser::PfsFile<'_>, PfsError> {
+        match self.source {
+            Source::Headerless => Ok(parser::pfs_headerless(&self.data).0),
+            Source::Truncated => parser::pfs_truncated(&self.data)
+                .ok_or(PfsError::Layout("PFS file can't be parsed again")),
+            _ => parser::pfs_file(&self.data[self.start..])
+                .map(|(_, file)| file)
+                .map_err(|_| PfsError::Layout("PFS file can't be parsed again")),
+        }
+    }
+
+    fn section(&self, index: usize) -> Result<parser::PfsSection<'_>, PfsError> {
+        parser::pfs_section(&self.data[self.sections[index]..])
+            .map(|(_, section)| section)
+            .map_err(|_| PfsError::Layout("PFS section can't be parsed again"))
+    }
+}
+
+// Move frame to the next file in its data, returns false if there is none
+fn pfs_next_file(ctx: &mut Context, frame: &mut Frame) -> Result<bool, PfsError> {
+    let number = frame.number + 1;
+    // Data after the last file belongs to the section the frame is in
+    ctx.selected = frame.parent_selected;
+    let index = String::from(frame.base_index.trim_end_matches('.'));
+    match frame.source {
+        Source::Pfs if number == 1 => {
+            frame.prefix = frame.base_prefix.clone();
+            frame.index_prefix = frame.base_index.clone();
+        }
+        Source::Pfs => {
+            // Bytes after the footer can be padding, more PFS files or garbage
+            let end = frame.start + frame.file()?.size();
+            let rest = &frame.data[end..];
+            match parser::trailing_kind(rest) {
+                parser::TrailingKind::Padding => return Ok(false),
+                parser::TrailingKind::Garbage => {
+                    ctx.save_unparsed(&index, rest, &format!("{}trailing.bin", frame.prefix))?;
+                    return Ok(false);
+                }
+                parser::TrailingKind::Pfs(offset) => {
+                    if !parser::is_padding(&rest[..offset]) {
+                        ctx.save_unparsed(&index, &rest[..offset], &format!("{}trailing.bin", frame.prefix))?;
+                    }
+                    info!("");
+                    info!("PFS file {} found after the footer", number);
+                    frame.start = end + offset;
+                    frame.prefix = nested_prefix(ctx, &format!("{}pfs{}", frame.base_prefix, number));
+                    frame.index_prefix = format!("{}pfs{}.", frame.base_index, number);
+                }
+            }
+        }
+        Source::Headerless | Source::Truncated if number == 1 => {
+            frame.prefix = frame.base_prefix.clone();
+            frame.index_prefix = frame.base_index.clone();
+        }
+        Source::Headerless => {
+            let rest = parser::pfs_headerless(&frame.data).1;
+            if !rest.is_empty() {
+                ctx.save_unparsed(&index, rest, &format!("{}trailing.bin", frame.prefix))?;
+            }
+            return Ok(false);
+        }
+        Source::Truncated => return Ok(false),
+        Source::Embedded(ref offsets) => {
+            let offset = match offsets.get(number - 1) {
+                Some(&offset) => offset,
+                None => return Ok(false),
+            };
+            info!("");
+            info!("PFS file {} found at offset {:X}", number, offset);
+            frame.start = offset;
+            frame.prefix = nested_prefix(ctx, &format!("{}embedded{}", frame.base_prefix, number));
+            frame.index_prefix = format!("{}embedded{}.", frame.base_index, number);
+        }
+    }
+    frame.number = number;
+    frame.next = 0;
+
+    // Check the file as a whole before going through its sections
+    let mut file = frame.file()?;
+    if let Source::Headerless = frame.source {
+        info!("Headerless sections: {}", file.sections.len());
+    } else if let Source::Truncated = frame.source {
+        info!("Sections in front of truncation: {}", file.sections.len());
+    } else {
+        let blob = &frame.data[frame.start..];
+        if frame.depth == 0 {
+            info!("Capsule type: {}", file.capsule_type());
+            write_checksum_status(ctx, blob, &file, &frame.prefix)?;
+        }
+        check_footer(ctx, blob, &file)?;
+    }
+
+    // Parse information section to obtain proper section names,
+    // a truncated file only has it if its sections end where the header says
+    let cut = match frame.source {
+        Source::Truncated => parser::pfs_header(&frame.data).is_ok_and(|(_, h)| h.data_size != file.header.data_size),
+        _ => false,
+    };
+    let has_info = if cut {
+        pfs_set_guid_names(&mut file, &ctx.options.guid_names);
+        false
+    } else {
+        pfs_set_names(&mut file, &ctx.options.guid_names)
+    };
+    let names = file.sections.iter().map(|s| s.name.clone()).collect();
+    let records = file.sections.iter().map(|s| s.info.clone()).collect();
+    let header_version = file.header.header_version;
+    let sections = file.sections.iter().scan(frame.start + file.sections_range().start, |offset, s| {
+        let start = *offset;
+        *offset += s.size();
+        Some(start)
+    }).collect();
+
+    // Decompress all compressed sections in parallel, output stays in section order
+    let prefetched = match ctx.pool {
+        Some(ref pool) => {
+            let ctx: &Context = ctx;
+            let compressed = |section| prefetch_data(ctx, section);
+            let total = file.sections.iter().filter_map(compressed).map(|data| data.len() as u64).sum();
+            let progress = SharedProgress::new("Decompressing sections", total, ctx.options.progress);
+            let prefetched = pool.install(|| file.sections.par_iter()
+                .map(|section| compressed(section).map(|data| decompress(ctx, data, Report::Shared(&progress), 0)))
+                .collect());
+            progress.finish();
+            prefetched
+        }
+        None => Vec::new(),
+    };
+    frame.has_info = has_info;
+    frame.header_version = header_version;
+    frame.sections = sections;
+    frame.names = names;
+    frame.records = records;
+    frame.prefetched = prefetched;
+    Ok(true)
+}
+
+// Compressed data of a section decompressed ahead, excluded sections are not
+fn prefetch_data<'a>(ctx: &Context, section: &parser::PfsSection<'a>) -> Option<&'a [u8]> {
+    if ctx.options.exclude.iter().any(|f| f.matches(&section.guid, &section.name)) {
+        return None;
+    }
+    parser::pfs_compressed_section(section.data?).ok().map(|(_, comp)| comp.data)
+}
+
+// Known metadata layout has text fields only, other blobs are left alone
+fn pfs_print_metadata(meta: &[u8]) {
+    if meta.len() != parser::PFS_SECTION_METADATA_SIZE {
+        return;
+    }
+    if let Ok((_, m)) = parser::pfs_section_metadata(meta) {
+        let fields = [("model IDs", &m.model_ids), ("file name", &m.file_name), ("file version", &m.file_version), ("date", &m.date),
+                      ("brand", &m.brand), ("model file", &m.model_file), ("model name", &m.model_name), ("model version", &m.model_version)];
+        for &(label, value) in fields.iter().filter(|f| !f.1.is_empty()) {
+            info!("Metadata {}: {}", label, value);
+        }
+    }
+}
+
+// Parts without a signature or with digests of other algorithms than SHA-2 are reported only
+fn pfs_verify_hashes(section: &parser::PfsSection) -> Result<(), PfsError> {
+    for &(part, data, sig) in &[("data", section.data, section.data_sig), ("metadata", section.meta, section.meta_sig)] {
+        if data.is_none() {
+            continue;
+        }
+        match verify::check_signed_digests(data.unwrap_or_default(), sig) {
+            verify::DigestStatus::Pass => info!("Signed digest of section {}: PASS", part),
+            verify::DigestStatus::Fail => return Err(PfsError::Digest(part)),
+            status => info!("Signed digest of section {}: {}", part, status),
+        }
+    }
+    Ok(())
+}
+
+// Parts without a PKCS#7 signature are reported only, signing time is taken from the signature when it has one
+fn pfs_verify_signatures(section: &parser::PfsSection, roots: &[chain::Certificate]) -> Result<(), PfsError> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    for &(part, data, sig) in &[("data", section.data, section.data_sig), ("metadata", section.meta, section.meta_sig)] {
+        let report = chain::validate_signature(data.unwrap_or_default(), sig, roots, now);
+        match report.status {
+            chain::ChainStatus::Missing => (),
+            chain::ChainStatus::Trusted => info!("Signature chain of section {}: trusted, signer {}", part, report.signer.unwrap_or_default()),
+            status => return Err(PfsError::Signature(part, status)),
+        }
+    }
+    Ok(())
+}
+
+// Returns frame for the PFS file nested into the section, if any
+fn pfs_extract_section<'a>(ctx: &mut Context, frame: &Frame<'a>, section: &parser::PfsSection, prefetched: Option<Decompressed>) -> Result<Option<Frame<'a>>, PfsError> {
+    let i = frame.next + 1;
+    let data_offset = frame.sections[frame.next] - frame.start + parser::PFS_SECTION_HEADER_SIZE;
+    let section_display_name = &frame.names[frame.next];
+    info!("");
+
+    // Sections inside of a selected one are selected too
+    let matched = !frame.parent_selected && ctx.options.extract_only.as_ref() == Some(&section.guid);
+    let only = !frame.parent_selected && ctx.options.only.iter().any(|f| f.matches(&section.guid, section_display_name));
+    let bios = !frame.parent_selected && ctx.options.profile == ExtractProfile::BiosOnly && section_display_name.to_uppercase().contains("BIOS");
+    ctx.selected = frame.parent_selected || matched || only || bios;
+
+    let number = i - 1 + ctx.options.index_base;
+    let index = format!("{}{}", frame.index_prefix, number);
+
+    // Print infomation
+    if ctx.options.list {
+        info!("Section: {} {}", index, section_display_name);
+    }
+    info!("GUID: {}", section.guid);
+    info!("Header version: {:X}", section.header_version);
+    info!("Data size: {:X}", section.data_size);
+    info!("Data signature size: {:X}", section.data_sig_size);
+    info!("Metadata size: {:X}", section.meta_size);
+    info!("Metadata signature size: {:X}", section.meta_sig_size);
+    trace!("Reserved: {:X}, unknown fields: {:08X?}", section.reserved, section.decode_unknown().dwords);
+    let parts = [("Data", section.data), ("Data signature", section.data_sig), ("Metadata", section.meta), ("Metadata signature", section.meta_sig)];
+    let offsets: Vec<Option<usize>> = parts.iter().map(|&(_, part)| part.and_then(|part| ctx.input_offset(part))).collect();
+    if ctx.options.byte_range {
+        info!("Data range: 0x{:X}-0x{:X}", data_offset, data_offset + section.data_size as usize);
+
+        // Positions in the input file, for parts not coming from decompressed data
+        for (&(label, part), offset) in parts.iter().zip(offsets.iter()) {
+            if let (Some(part), Some(offset)) = (part, offset) {
+                info!("{}: offset=0x{:X} len=0x{:X}", label, offset, part.len());
+            }
+        }
+    }
+
+    // Print version, components of unknown type are shown in hex
+    for t in section.version.unknown_types() {
+        warn!("Unknown version type found: {:X}", t);
+    }
+    let mut version = section.version.to_string();
+    if !version.is_empty() {
+        info!("Version: {}.", version);
+    }
+    else {
+        version.push('0');
+    }
+    version.push('.');
+
+    if ctx.options.collects_manifest() {
+        ctx.manifest.push(ManifestSection {
+            index: index.clone(),
+            depth: frame.depth,
+            guid: section.guid.to_string(),
+            name: section_display_name.clone(),
+            version: String::from(version.trim_end_matches('.')),
+            data_size: section.data_size,
+            data_sig_size: section.data_sig_size,
+            meta_size: section.meta_size,
+            meta_sig_size: section.meta_sig_size,
+            data_offset: offsets[0],
+            data_sig_offset: offsets[1],
+            meta_offset: offsets[2],
+            meta_sig_offset: offsets[3],
+            pfs_header_version: frame.header_version,
+            header_version: section.header_version,
+            version_type: section.version.types,
+            version_numbers: section.version.numbers,
+            reserved: section.reserved,
+            unknown: section.unknown,
+            info_header_version: frame.records[frame.next].as_ref().map(|r| r.header_version),
+            info_extra: frame.records[frame.next].as_ref().map(|r| r.extra.clone()),
+            data_file: None,
+            data_sig_file: None,
+            meta_file: None,
+            meta_sig_file: None,
+            files: Vec::new(),
+            data_preview: section.data.map_or(Vec::new(), |data| data[..data.len().min(report::PREVIEW_SIZE)].to_vec()),
+        });
+    }
+
+    // Excluded sections are neither written nor looked into
+    if ctx.options.exclude.iter().any(|f| f.matches(&section.guid, section_display_name)) {
+        info!("Section excluded");
+        return Ok(None);
+    }
+
+    // Save components into files
+    let section_data = match section.data {
+        Some(data) => data,
+        None => return Ok(None),
+    };
+
+    let section_name =
+    if section_display_name.is_empty() {
+        format!("section_{}", number)
+    } else {
+        format!("{}_{}", number, sanitize_name(section_display_name))
+    };
+
+    // Information section is parsed already, everything else is classified by content
+    let kind = parser::section_kind(section_data);
+    if kind == parser::SectionKind::Unknown && !(frame.has_info && i == frame.sections.len()) {
+        match ctx.options.on_unknown {
+            UnknownPolicy::Write => (),
+            UnknownPolicy::Skip => {
+                warn!("Unknown section type, skipped");
+                return Ok(None);
+            }
+            UnknownPolicy::Fail => return Err(PfsError::UnknownSection),
+        }
+    }
+
+    let names = SectionNames {
+        base: format!("{}{}_{}", frame.prefix, section_name, version),
+        prefix: frame.prefix.clone(),
+        fields: SectionFields {
+            index: index.clone(),
+            number,
+            guid: section.guid.to_string(),
+            name: if section_display_name.is_empty() { String::new() } else { sanitize_name(section_display_name) },
+            version: section.version.to_string(),
+        },
+        template: ctx.options.name_template.clone(),
+        kind: String::new(),
+    };
+
+    // Write EC firmware with its own extension
+    let is_ec = ec::is_ec_name(section_display_name) || ec::is_ec_guid(&section.guid, &ctx.options.guid_names) || ec::has_ec_signature(section_data);
+    let data_name = if ctx.options.ec && is_ec {
+        info!("Embedded controller firmware");
+        names.file("data.ec")
+    }
+    else {
+        ctx.typed_name(section_data, names.file("data"))
+    };
+    let mut part_files = [None, None, None, None];
+    let write_data = ctx.writes_intermediate() || !is_extracted_further(section_data);
+    if write_data && ctx.write_file(&index, section_data, &data_name)? {
+        part_files[0] = Some(data_name);
+    }
+
+    // Model properties are also written in readable form
+    if section_display_name == content::MODEL_PROPERTIES_NAME {
+        if let Ok((_, properties)) = parser::pfs_model_properties(section_data) {
+            for property in &properties {
+                info!("Model property: {} = {}", property.key, property.value);
+            }
+            let json = manifest::model_properties_json(&properties);
+            if ctx.writes_intermediate() {
+                ctx.write_file(&index, json.as_bytes(), &format!("{}model_properties.json", frame.prefix))?;
+            }
+        }
+    }
+
+    if let Some(data_sig) = section.data_sig {
+        let name = names.file("data.sig");
+        if ctx.writes_intermediate() && ctx.write_file(&index, data_sig, &name)? {
+            part_files[1] = Some(name.clone());
+        }
+        pfs_extract_signature(ctx, &index, data_sig, &name)?;
+    }
+    if let Some(meta) = section.meta {
+        let name = names.file("meta");
+        if ctx.writes_intermediate() && ctx.write_file(&index, meta, &name)? {
+            part_files[2] = Some(name);
+        }
+        pfs_print_metadata(meta);
+    }
+    if let Some(meta_sig) = section.meta_sig {
+        let name = names.file("meta.sig");
+        if ctx.writes_intermediate() && ctx.write_file(&index, meta_sig, &name)? {
+            part_files[3] = Some(name.clone());
+        }
+        pfs_extract_signature(ctx, &index, meta_sig, &name)?;
+    }
+
+    // Manifest tells which file holds which part, so the section can be packed again
+    if let Some(entry) = ctx.manifest.iter_mut().rev().find(|s| s.index == index) {
+        let [data_file, data_sig_file, meta_file, meta_sig_file] = part_files;
+        entry.data_file = data_file;
+        entry.data_sig_file = data_sig_file;
+        entry.meta_file = meta_file;
+        entry.meta_sig_file = meta_sig_file;
+    }
+
+    // Metadata has no digest of the data, the one signed in the data signature is used instead,
+    // files are kept, but nothing is extracted from data that doesn't match
+    if ctx.options.verify_hashes {
+        pfs_verify_hashes(section)?;
+    }
+    if let Some(ref roots) = ctx.options.verify_signatures {
+        pfs_verify_signatures(section, roots)?;
+    }
+
+    // Check data to determine if and how it can be parsed further, handlers of the caller come first
+    let nested = match pfs_run_handlers(ctx, frame, section, &index, &names, section_data)? {
+        HandlerOutcome::Declined => pfs_extract_nested(ctx, section_data, &names, &index, prefetched)?,
+        HandlerOutcome::Handled => None,
+        HandlerOutcome::Nested(data) => Some(Nested { data, pfs: Some((nested_prefix(ctx, &names.file("")), format!("{}.", index))) }),
+    };
+    if matched && ctx.options.to_stdout {
+        ctx.captured.push(nested.as_ref().map_or_else(|| section_data.to_vec(), |n| n.data.clone()));
+    }
+
+    // PFS files are scanned section by section once extracted
+    if ctx.options.deep_scan {
+        let innermost = nested.as_ref().map_or(section_data, |n| &n.data[..]);
+        if parser::classify(innermost) != parser::InputKind::Pfs {
+            pfs_deep_scan(ctx, &index, innermost, &names, 1)?;
+        }
+    }
+
+    // Decompressed data is extracted after the section, deeper levels only up to the limit
+    match nested {
+        Some(Nested { data, pfs: Some((prefix, index_prefix)) }) => {
+            if parser::pfs_file(&data).is_err() {
+                report_parse_error(&data, false);
+                Ok(None)
+            }
+            else if frame.depth == ctx.options.max_depth {
+                warn!("Maximum nesting depth {} reached, PFS file in section {} is not extracted", ctx.options.max_depth, index);
+                Ok(None)
+            }
+            else {
+                Ok(Some(Frame::new(Cow::Owned(data), Source::Pfs, frame.depth + 1, &prefix, &index_prefix, ctx.selected)))
+            }
+        }
+        _ => Ok(None),
+    }
+}
+
+// What handlers of the caller get to work with a section
+pub struct HandlerContext<'c, 'a: 'c> {
+    ctx: &'c mut Context<'a>,
+    index: &'c str,
+    names: &'c SectionNames,
+}
+
+impl<'c, 'a> HandlerContext<'c, 'a> {
+    // Index of the section as extraction numbers it, like 2 or 1.3
+    pub fn index(&self) -> &str {
+        self.index
+    }
+
+    pub fn options(&self) -> &Options {
+        self.ctx.options
+    }
+
+    // Writes a file named like the other files of the section with the given kind, like "decoded" or "data.key",
+    // returns whether it was written; nothing is written when listing or when the section isn't selected
+    pub fn write_file(&mut self, kind: &str, data: &[u8]) -> Result<bool, PfsError> {
+        let name = self.ctx.typed_name(data, self.names.file(kind));
+        self.ctx.write_file(self.index, data, &name)
+    }
+}
+
+// First outcome of a matching handler other than declining
+fn pfs_run_handlers(ctx: &mut Context, frame: &Frame, section: &parser::PfsSection, index: &str, names: &SectionNames, data: &[u8]) -> Result<HandlerOutcome, PfsError> {
+    if ctx.options.handlers.is_empty() {
+        return Ok(HandlerOutcome::Declined);
+    }
+    // Sections of files parsed again for extraction have no names, handlers get them
+    let named = parser::PfsSection {
+        name: frame.names[frame.next].clone(),
+        info: frame.records[frame.next].clone(),
+        guid: section.guid,
+        header_version: section.header_version,
+        version: section.version,
+        reserved: section.reserved,
+        data_size: section.data_size,
+        data_sig_size: section.data_sig_size,
+        meta_size: section.meta_size,
+        meta_sig_size: section.meta_sig_size,
+        unknown: section.unknown,
+        data: section.data,
+        data_sig: section.data_sig,
+        meta: section.meta,
+        meta_sig: section.meta_sig,
+    };
+    let handlers = ctx.options.handlers.clone();
+    for handler in handlers.iter().filter(|h| h.matches(&named)) {
+        let outcome = handler.handle(&mut HandlerContext { ctx, index, names }, data)?;
+        if outcome != HandlerOutcome::Declined {
+            info!("Section handled by a custom handler");
+            return Ok(outcome);
+        }
+    }
+    Ok(HandlerOutcome::Declined)
+}
+
+// Print PKCS#7 signer details and write its certificates next to the signature file
+fn pfs_extract_signature(ctx: &mut Context, index: &str, data: &[u8], filename: &str) -> Result<(), PfsError> {
+    let sig = match signature::parse_pkcs7(data) {
+        Some(sig) => sig,
+        None => return Ok(()),
+    };
+    info!("PKCS#7 signature in {} at offset {:X}, digest algorithm: {}", filename, sig.offset, sig.digest_algorithms.join(", "));
+    for (n, cert) in sig.certificates.iter().enumerate() {
+        info!("Certificate {}: subject {}, issuer {}", n + 1, cert.subject, cert.issuer);
+        if ctx.writes_intermediate() {
+            ctx.write_file(index, cert.pem.as_bytes(), &format!("{}.cert{}.pem", filename, n + 1))?;
+        }
+    }
+    Ok(())
+}
+
+// Extract every file of the root frame and everything nested into them
+fn pfs_extract_frames(ctx: &mut Context, root: Frame) -> Result<(), PfsError> {
+    let mut stack = vec![root];
+    while let Some(mut frame) = stack.pop() {
+        ctx.check_timeout()?;
+
+        // Go to the next file when the current one is done
+        if frame.number == 0 || frame.next == frame.names.len() {
+            if pfs_next_file(ctx, &mut frame)? {
+                stack.push(frame);
+            }
+            continue;
+        }
+
+        let result = {
+            let prefetched = frame.prefetched.get_mut(frame.next).and_then(Option::take);
+            let section = frame.section(frame.next)?;
+            pfs_extract_section(ctx, &frame, &section, prefetched)
+        };
+        let child = match result {
+            Ok(child) => {
+                ctx.sections += 1;
+                child
+            }
+            Err(e) => {
+                if ctx.options.fail_fast || !e.is_section_failure() {
+                    return Err(e);
+                }
+                let index = format!("{}{}", frame.index_prefix, frame.next + ctx.options.index_base);
+                error!("Section {} failed: {}", index, e);
+                ctx.failed.push(index);
+                if e.is_verification_failure() {
+                    ctx.unverified += 1;
+                }
+                None
+            }
+        };
+        frame.next += 1;
+        stack.push(frame);
+        if let Some(child) = child {
+            stack.push(child);
+        }
+    }
+    Ok(())
+}
+
+
+fn hex_dump(data: &[u8]) {
+    for (i, line) in data.chunks(16).enumerate() {
+        let hex: Vec<String> = line.iter().map(|b| format!("{:02X}", b)).collect();
+        let ascii: String = line.iter().map(|&b| if (0x20..0x7F).contains(&b) { b as char } else { '.' }).collect();
+        error!("{:08X}: {:<47}  {}", i * 16, hex.join(" "), ascii);
+    }
+}
+
+
+// Returns whether data was parsed as PFS file, or an error if extraction was aborted
+// Top-level input is a PFS file or, with --headerless, a bare sections region
+fn pfs_extract_input(ctx: &mut Context, data: &[u8]) -> Result<bool, PfsError> {
+    let selected = ctx.selected;
+    let source = if ctx.options.headerless {
+        if parser::pfs_headerless(data).0.sections.is_empty() {
+            error!("No PFS sections found, this file can't be parsed");
+            error!("First bytes of the file:");
+            hex_dump(&data[..data.len().min(HEX_DUMP_SIZE)]);
+            return Ok(false);
+        }
+        Source::Headerless
+    }
+    else if parser::pfs_file(data).is_ok() {
+        Source::Pfs
+    }
+    // Truncated file is extracted as far as it goes unless --strict is given
+    else if let Some(file) = parser::pfs_truncated(data).filter(|_| !ctx.options.strict) {
+        warn!("PFS file is truncated at offset {:X}, sections end at offset {:X}",
+            data.len(), parser::PFS_HEADER_SIZE + file.header.data_size as usize);
+        Source::Truncated
+    }
+    else {
+        // Not a PFS file itself, but can have some inside, as they are or compressed in a container
+        let offsets = parser::find_pfs_offsets(data);
+        if offsets.is_empty() {
+            if let Some((offset, decompressed)) = pfs_container(ctx, data) {
+                if offset == 0 {
+                    info!("Input is a compressed PFS file");
+                } else {
+                    info!("PFS file found in compressed data at offset {:X}, container header in front of it skipped", offset);
+                }
+                pfs_extract_frames(ctx, Frame::new(Cow::Owned(decompressed), Source::Pfs, 0, "", "", selected))?;
+                return Ok(true);
+            }
+            report_parse_error(data, true);
+            return Ok(false);
+        }
+        Source::Embedded(offsets)
+    };
+    let truncated = matches!(source, Source::Truncated);
+    pfs_extract_frames(ctx, Frame::new(Cow::Borrowed(data), source, 0, "", "", selected))?;
+    if truncated {
+        return Err(PfsError::Truncated(data.len()));
+    }
+    Ok(true)
+}
+
+// First compressed section holding a PFS file, compressed data that is damaged
+// or something else is not a container
+fn pfs_container(ctx: &Context, data: &[u8]) -> Option<(usize, Vec<u8>)> {
+    for offset in parser::find_compressed_offsets(data) {
+        let (_, comp) = parser::pfs_compressed_section(&data[offset..]).ok()?;
+        match decompress(ctx, comp.data, Report::Own("Decompressing container"), 0) {
+            Ok(decompressed) if parser::pfs_file(&decompressed).is_ok() => return Some((offset, decompressed)),
+            Ok(_) => debug!("Compressed data at offset {:X} is not a PFS file", offset),
+            Err(e) => debug!("Compressed data at offset {:X} can't be decompressed: {}", offset, e),
+        }
+    }
+    None
+}
+
+// Print footer checksum result, mismatch aborts extraction in strict mode
+fn check_footer(ctx: &Context, data: &[u8], file: &parser::PfsFile) -> Result<(), PfsError> {
+    let computed = verify::pfs_file_checksum(data, file);
+    if computed == Some(file.footer.checksum) {
+        info!("Footer checksum: PASS");
+        return Ok(());
+    }
+    let (stored, computed) = (file.footer.checksum, computed.unwrap_or(0));
+    warn!("Footer checksum: FAIL (stored {:08X}, computed {:08X})", stored, computed);
+    if ctx.options.strict {
+        return Err(PfsError::Checksum { stored, computed });
+    }
+    Ok(())
+}
+
+// Record the footer checksum result next to the extracted files of a top-level PFS file
+fn write_checksum_status(ctx: &mut Context, data: &[u8], file: &parser::PfsFile, prefix: &str) -> Result<(), PfsError> {
+    if !ctx.options.checksum_status {
+        return Ok(());
+    }
+    let expected = file.footer.checksum;
+    let computed = verify::pfs_file_checksum(data, file);
+    let status = if computed == Some(expected) { "PASS" } else { "FAIL" };
+    let computed = computed.map_or(String::from("none"), |c| format!("{:08X}", c));
+    let text = format!("Algorithm: inverted CRC32 of section data\nExpected: {:08X}\nComputed: {}\nStatus: {}\n",
+        expected, computed, status);
+    ctx.write_file("", text.as_bytes(), &format!("{}footer_checksum.txt", prefix)).map(|_| ())
+}
+
+// Damaged input is an error, damaged nested data only a warning,
+// and nested data that isn't PFS at all is nothing unusual
+fn report_parse_error(data: &[u8], top_level: bool) {
+    let (level, other_level) = if top_level { (Level::Error, Level::Error) } else { (Level::Warn, Level::Info) };
+    // Tell apart a file that is not PFS at all from a damaged PFS file
+    match parser::pfs_header(data) {
+        Ok((_, ref header)) if header.is_valid() => {
+            match parser::pfs_error_offset(data) {
+                Some(offset) => log!(level, "PFS file parse error at offset {:X}, this file can't be parsed", offset),
+                None => log!(level, "PFS file parse error, this file can't be parsed"),
+            }
+            let size = parser::PFS_HEADER_SIZE + header.data_size as usize + parser::PFS_FOOTER_SIZE;
+            if data.len() < size {
+                log!(level, "PFS file is truncated at offset {:X}, its header declares size {:X}", data.len(), size);
+            }
+        }
+        Ok((_, header)) => {
+            log!(other_level, "Not a valid PFS header (header version {:X}), this file can't be parsed", header.header_version);
+        }
+        _ => { log!(other_level, "Not a valid PFS header, this file can't be parsed"); }
+    }
+
+    // Show what the input starts with to make bug reports useful
+    if top_level {
+        let kind = parser::classify(data);
+        let hint = match kind {
+            parser::InputKind::CompressedSection => Some("it has to be decompressed first"),
+            parser::InputKind::UefiCapsule => Some("extract the capsule payload first"),
+            parser::InputKind::PeImage => Some("extract the PFS file from the executable first"),
+            parser::InputKind::Gzip | parser::InputKind::Zip => Some("unpack the archive first"),
+            parser::InputKind::Pfs | parser::InputKind::Unknown => None,
+        };
+        if let Some(hint) = hint {
+            log!(level, "Input looks like a {}, {}", kind, hint);
+        }
+        log!(level, "First bytes of the file:");
+        hex_dump(&data[..data.len().min(HEX_DUMP_SIZE)]);
+    }
+}
+
+
+//
+// Library entry point
+//
+pub struct Extraction {
+    // Ok(false) if the input is not a PFS file, Err if extraction was aborted
+    pub result: Result<bool, PfsError>,
+    // Section index and name of every written file
+    pub components: Vec<(String, String)>,
+    // Final data of selected sections when writing to stdout
+    pub captured: Vec<Vec<u8>>,
+    // Section tree, empty unless manifest is requested
+    pub manifest: Vec<ManifestSection>,
+    // Hash of every written file, empty unless SHA256SUMS is requested
+    pub hashes: Vec<ManifestFile>,
+    // Data left over after parsed structures, empty unless manifest is requested
+    pub unparsed: Vec<ManifestUnparsed>,
+    // Archive with extracted files, to be finished by the caller
+    pub archive: Option<Archive>,
+    // Name and data of every file, empty unless extracted into memory
+    pub files: Vec<(String, Vec<u8>)>,
+}
+
+impl Extraction {
+    // Extraction aborted before the input was looked at
+    pub fn failed(error: PfsError) -> Extraction {
+        Extraction {
+            result: Err(error),
+            components: Vec::new(),
+            captured: Vec::new(),
+            manifest: Vec::new(),
+            hashes: Vec::new(),
+            unparsed: Vec::new(),
+            archive: None,
+            files: Vec::new(),
+        }
+    }
+}
+
+// Extract PFS file contents into an existing directory
+pub fn extract(options: &Options, data: &[u8], dir: &Path) -> Extraction {
+    let output = match options.archive {
+        Some(ref path) => {
+            let format = ArchiveFormat::from_path(path).unwrap_or(ArchiveFormat::Zip);
+            match Archive::create(path, format) {
+                Ok(archive) => Output::Archive(archive),
+                Err(e) => return Extraction::failed(PfsError::Write(path.clone(), e)),
+            }
+        }
+        None => Output::Directory,
+    };
+    extract_into(options, data, dir, output)
+}
+
+// Extract PFS file contents into Extraction::files without touching the filesystem,
+// the archive option is ignored
+pub fn extract_in_memory(options: &Options, data: &[u8]) -> Extraction {
+    extract_into(options, data, Path::new(""), Output::Memory(Vec::new()))
+}
+
+fn extract_into(options: &Options, data: &[u8], dir: &Path, output: Output) -> Extraction {
+    if options.verify_signatures.as_ref().is_some_and(Vec::is_empty) {
+        return Extraction::failed(PfsError::NoRoots);
+    }
+    let mut ctx = Context::new(options, data, dir, output);
+    let mut result = pfs_extract_input(&mut ctx, data);
+    if options.resume && !ctx.recorded.is_empty() {
+        info!("Files unchanged since the earlier run: {}", ctx.unchanged);
+    }
+    if result.is_ok() && !ctx.failed.is_empty() {
+        warn!("");
+        warn!("{} sections extracted, {} failed ({})", ctx.sections, ctx.failed.len(), ctx.failed.join(", "));
+        result = Err(if ctx.unverified > 0 { PfsError::Unverified(ctx.failed.len()) } else { PfsError::Sections(ctx.failed.len()) });
+    }
+    let (archive, files) = match ctx.output {
+        Output::Directory => (None, Vec::new()),
+        Output::Archive(archive) => (Some(archive), Vec::new()),
+        Output::Memory(files) => (None, files),
+    };
+    Extraction {
+        result,
+        components: ctx.components,
+        captured: ctx.captured,
+        manifest: ctx.manifest,
+        hashes: ctx.hashes,
+        unparsed: ctx.unparsed,
+        archive,
+        files,
+    }
+}
@@ -0,0 +1,274 @@
+//
+// C interface, include/pfsextractor.h is generated from this file with cbindgen
+//
+// Doc comments of exported items end up in the header, so they are written for C callers
+//
+use error::{PfsError, EXIT_PARSE, EXIT_SUCCESS, EXIT_USAGE};
+use extract::{self, Extraction, Options};
+use guids::GuidNames;
+use parser;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::os::raw::c_char;
+use std::path::Path;
+use std::ptr;
+use std::slice;
+
+/// GUID in the layout of EFI_GUID
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PfsGuid {
+    pub data1 : u32,
+    pub data2 : u16,
+    pub data3 : u16,
+    pub data4 : [u8; 8],
+}
+
+/// Section of a parsed PFS file, strings are UTF-8 and owned by the handle
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PfsSectionInfo {
+    pub guid : PfsGuid,
+    /// Name from the information section, empty if there is none
+    pub name : *const c_char,
+    /// Version as printed by the command line tool, like "1.2.3"
+    pub version : *const c_char,
+    pub header_version : u32,
+    pub data_size : u32,
+    pub data_sig_size : u32,
+    pub meta_size : u32,
+    pub meta_sig_size : u32,
+    /// Offset of section data in the parsed buffer
+    pub data_offset : u64,
+}
+
+// Section with the strings its info points to
+struct Section {
+    info : PfsSectionInfo,
+    _name : CString,
+    _version : CString,
+}
+
+/// Parsed PFS file, doesn't borrow the buffer it was parsed from
+pub struct PfsHandle {
+    sections : Vec<Section>,
+}
+
+/// Files extracted into memory
+pub struct PfsFiles {
+    status : i32,
+    names : Vec<CString>,
+    data : Vec<Vec<u8>>,
+}
+
+// Names may contain anything, but not inside of a C string
+fn c_string(text : &str) -> CString {
+    CString::new(text.replace('\0', "")).unwrap_or_default()
+}
+
+unsafe fn input<'a>(data : *const u8, len : usize) -> Option<&'a [u8]> {
+    if data.is_null() {
+        return None;
+    }
+    Some(slice::from_raw_parts(data, len))
+}
+
+/// Parses a PFS file, returns NULL if the data is not a valid PFS file.
+/// The handle has to be released with pfs_free.
+///
+/// # Safety
+/// `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn pfs_parse(data : *const u8, len : usize) -> *mut PfsHandle {
+    let data = match input(data, len) {
+        Some(data) => data,
+        None => return ptr::null_mut(),
+    };
+    let mut file = match parser::pfs_file(data) {
+        Ok((_, file)) => file,
+        Err(_) => return ptr::null_mut(),
+    };
+    extract::pfs_set_names(&mut file, &GuidNames::default());
+
+    let mut offset = file.sections_range().start;
+    let mut sections = Vec::with_capacity(file.sections.len());
+    for section in &file.sections {
+        let name = c_string(&section.name);
+        let version = c_string(&section.version.to_string());
+        let guid = section.guid;
+        let info = PfsSectionInfo {
+            guid : PfsGuid { data1 : guid.data1, data2 : guid.data2, data3 : guid.data3, data4 : guid.data4 },
+            name : name.as_ptr(),
+            version : version.as_ptr(),
+            header_version : section.header_version,
+            data_size : section.data_size,
+            data_sig_size : section.data_sig_size,
+            meta_size : section.meta_size,
+            meta_sig_size : section.meta_sig_size,
+            data_offset : (offset + parser::PFS_SECTION_HEADER_SIZE) as u64,
+        };
+        offset += section.size();
+        // Strings live on the heap, moving CString doesn't move them
+        sections.push(Section { info, _name : name, _version : version });
+    }
+    Box::into_raw(Box::new(PfsHandle { sections }))
+}
+
+/// Releases a handle returned by pfs_parse, NULL is ignored.
+///
+/// # Safety
+/// `handle` must be NULL or a handle not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn pfs_free(handle : *mut PfsHandle) {
+    if !handle.is_null() {
+        drop(Box::from_raw(handle));
+    }
+}
+
+/// Number of sections of the parsed file, 0 for NULL.
+///
+/// # Safety
+/// `handle` must be NULL or a handle returned by pfs_parse.
+#[no_mangle]
+pub unsafe extern "C" fn pfs_section_count(handle : *const PfsHandle) -> usize {
+    handle.as_ref().map_or(0, |h| h.sections.len())
+}
+
+/// Fills `out` with the section at `index`, returns false if there is no such section.
+/// Strings stay valid until the handle is released.
+///
+/// # Safety
+/// `handle` must be NULL or a handle returned by pfs_parse, `out` must be NULL or writable.
+#[no_mangle]
+pub unsafe extern "C" fn pfs_section_info(handle : *const PfsHandle, index : usize, out : *mut PfsSectionInfo) -> bool {
+    let section = match handle.as_ref().and_then(|h| h.sections.get(index)) {
+        Some(section) => section,
+        None => return false,
+    };
+    match out.as_mut() {
+        Some(out) => {
+            *out = section.info;
+            true
+        }
+        None => false,
+    }
+}
+
+/// Extracts everything the command line tool would into `dir`, which is created if needed.
+/// Returns the exit code the command line tool would, 0 on success, see README.md for the others.
+///
+/// # Safety
+/// `data` must point to `len` readable bytes, `dir` must be a NUL-terminated UTF-8 path.
+#[no_mangle]
+pub unsafe extern "C" fn pfs_extract_to_dir(data : *const u8, len : usize, dir : *const c_char) -> i32 {
+    let data = match input(data, len) {
+        Some(data) => data,
+        None => return EXIT_USAGE,
+    };
+    let dir = match dir.as_ref().map(|dir| CStr::from_ptr(dir).to_str()) {
+        Some(Ok(dir)) => Path::new(dir),
+        _ => return EXIT_USAGE,
+    };
+    if let Err(e) = fs::create_dir_all(dir) {
+        return PfsError::Write(dir.to_path_buf(), e).exit_code();
+    }
+    status(&extract::extract(&Options::default(), data, dir))
+}
+
+fn status(extraction : &Extraction) -> i32 {
+    match extraction.result {
+        Ok(true) => EXIT_SUCCESS,
+        Ok(false) => EXIT_PARSE,
+        Err(ref e) => e.exit_code(),
+    }
+}
+
+/// Extracts everything the command line tool would into memory, for callers without a filesystem.
+/// Returns NULL if `data` is NULL, files have to be released with pfs_files_free.
+///
+/// # Safety
+/// `data` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn pfs_extract_to_memory(data : *const u8, len : usize) -> *mut PfsFiles {
+    let data = match input(data, len) {
+        Some(data) => data,
+        None => return ptr::null_mut(),
+    };
+    let extraction = extract::extract_in_memory(&Options::default(), data);
+    let status = status(&extraction);
+    let (names, data) = extraction.files.into_iter().map(|(name, data)| (c_string(&name), data)).unzip();
+    Box::into_raw(Box::new(PfsFiles { status, names, data }))
+}
+
+/// Exit code the command line tool would return for the extraction, 0 for NULL.
+///
+/// # Safety
+/// `files` must be NULL or returned by pfs_extract_to_memory.
+#[no_mangle]
+pub unsafe extern "C" fn pfs_files_status(files : *const PfsFiles) -> i32 {
+    files.as_ref().map_or(EXIT_SUCCESS, |f| f.status)
+}
+
+/// Number of extracted files, 0 for NULL.
+///
+/// # Safety
+/// `files` must be NULL or returned by pfs_extract_to_memory.
+#[no_mangle]
+pub unsafe extern "C" fn pfs_files_count(files : *const PfsFiles) -> usize {
+    files.as_ref().map_or(0, |f| f.names.len())
+}
+
+/// Name of the file at `index`, NULL if there is none.
+/// Nested sections are written with their parent names as prefix, like in the output directory.
+///
+/// # Safety
+/// `files` must be NULL or returned by pfs_extract_to_memory.
+#[no_mangle]
+pub unsafe extern "C" fn pfs_files_name(files : *const PfsFiles, index : usize) -> *const c_char {
+    files.as_ref().and_then(|f| f.names.get(index)).map_or(ptr::null(), |name| name.as_ptr())
+}
+
+/// Data of the file at `index` with its size stored into `size`, NULL if there is none.
+///
+/// # Safety
+/// `files` must be NULL or returned by pfs_extract_to_memory, `size` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn pfs_files_data(files : *const PfsFiles, index : usize, size : *mut usize) -> *const u8 {
+    let data = match files.as_ref().and_then(|f| f.data.get(index)) {
+        Some(data) => data,
+        None => return ptr::null(),
+    };
+    if let Some(size) = size.as_mut() {
+        *size = data.len();
+    }
+    data.as_ptr()
+}
+
+/// Releases files returned by pfs_extract_to_memory, NULL is ignored.
+///
+/// # Safety
+/// `files` must be NULL or not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn pfs_files_free(files : *mut PfsFiles) {
+    if !files.is_null() {
+        drop(Box::from_raw(files));
+    }
+}
+
+/// Allocates `len` bytes to pass input data in, for WebAssembly callers that can't allocate
+/// in its memory otherwise. Released with pfs_buffer_free.
+#[no_mangle]
+pub extern "C" fn pfs_buffer_alloc(len : usize) -> *mut u8 {
+    Box::into_raw(vec![0u8; len].into_boxed_slice()) as *mut u8
+}
+
+/// Releases a buffer returned by pfs_buffer_alloc with the same `len`, NULL is ignored.
+///
+/// # Safety
+/// `buffer` must be NULL or returned by pfs_buffer_alloc with this `len` and not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn pfs_buffer_free(buffer : *mut u8, len : usize) {
+    if !buffer.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(buffer, len)));
+    }
+}
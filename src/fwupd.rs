@@ -0,0 +1,123 @@
+use archive;
+use error::PfsError;
+use parser::{self, Guid, PfsSection, PfsVersion, SectionKind};
+use std::io;
+use walk::{self, PfsVisitor};
+
+//
+// fwupd metainfo and cabinet for the BIOS payload of a PFS file
+//
+
+// Final data of a section, after decompression and chunk assembly
+pub struct FwupdPayload {
+    pub index : String,
+    pub name : String,
+    pub guid : Guid,
+    pub version : PfsVersion,
+    pub data : Vec<u8>,
+}
+
+// Names of the files in the cabinet
+pub const FWUPD_FIRMWARE : &str = "firmware.bin";
+pub const FWUPD_METAINFO : &str = "firmware.metainfo.xml";
+
+// Sections named as BIOS with their final data, in the order they are found
+#[derive(Default)]
+struct BiosVisitor {
+    found : Vec<FwupdPayload>,
+}
+
+impl PfsVisitor for BiosVisitor {
+    fn on_section(&mut self, index : &str, section : &PfsSection) -> bool {
+        if section.name.to_uppercase().contains("BIOS") {
+            self.found.push(FwupdPayload {
+                index : String::from(index),
+                name : section.name.clone(),
+                guid : section.guid,
+                version : section.version,
+                data : section.data.unwrap_or_default().to_vec(),
+            });
+        }
+        true
+    }
+
+    fn on_compressed_payload(&mut self, index : &str, data : &[u8]) {
+        self.on_chunked_payload(index, data);
+    }
+
+    fn on_chunked_payload(&mut self, index : &str, data : &[u8]) {
+        if let Some(payload) = self.found.iter_mut().rev().find(|p| p.index == index) {
+            payload.data = data.to_vec();
+        }
+    }
+}
+
+// First BIOS section whose final data is not another PFS file, compressed section or subsection
+pub fn find_bios(data : &[u8]) -> Result<Option<FwupdPayload>, PfsError> {
+    let mut visitor = BiosVisitor::default();
+    walk::pfs_walk(data, &mut visitor)?;
+    Ok(visitor.found.into_iter().find(|p| !p.data.is_empty() && parser::section_kind(&p.data) == SectionKind::Unknown))
+}
+
+// GUID fwupd matches devices by: given one, type of the FMP capsule payload, or section GUID as the last resort
+pub fn component_guid(payload : &FwupdPayload, esrt_guid : Option<Guid>) -> Guid {
+    if let Some(guid) = esrt_guid {
+        return guid;
+    }
+    if let Some(guid) = parser::uefi_fmp_image_type(&payload.data) {
+        info!("ESRT GUID from the FMP capsule header: {}", guid);
+        return guid;
+    }
+    warn!("BIOS payload is not an FMP capsule, section GUID {} is used in place of the ESRT GUID", payload.guid);
+    payload.guid
+}
+
+fn escape(text : &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+// Version format tells fwupd how many components the version has
+fn version_format(version : &PfsVersion) -> &'static str {
+    match version.components().len() {
+        2 => "pair",
+        3 => "triplet",
+        4 => "quad",
+        _ => "plain",
+    }
+}
+
+// Metainfo has everything the PFS file tells, release notes are left for the author to write
+pub fn metainfo(payload : &FwupdPayload, guid : &Guid, input : &str) -> String {
+    let id = format!("{:08x}", guid.data1);
+    let version = payload.version.to_string();
+    let version = if version.is_empty() { String::from("0") } else { version };
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    out.push_str(&format!("<!-- Generated by PFSExtractor-RS {} from {}, check everything before uploading -->\n",
+        env!("CARGO_PKG_VERSION"), escape(input)));
+    out.push_str("<component type=\"firmware\">\n");
+    out.push_str(&format!("  <id>com.dell.uefi{}.firmware</id>\n", id));
+    out.push_str(&format!("  <name>{}</name>\n", escape(&payload.name)));
+    out.push_str(&format!("  <summary>{} extracted from {}</summary>\n", escape(&payload.name), escape(input)));
+    out.push_str("  <description>\n    <p>System firmware update.</p>\n  </description>\n");
+    out.push_str(&format!("  <provides>\n    <firmware type=\"flashed\">{}</firmware>\n  </provides>\n", guid.to_string().to_lowercase()));
+    out.push_str("  <url type=\"homepage\">https://www.dell.com/support</url>\n");
+    out.push_str("  <metadata_license>CC0-1.0</metadata_license>\n");
+    out.push_str("  <project_license>proprietary</project_license>\n");
+    out.push_str("  <developer_name>Dell Inc.</developer_name>\n");
+    out.push_str("  <categories>\n    <category>X-System</category>\n  </categories>\n");
+    out.push_str("  <custom>\n");
+    out.push_str(&format!("    <value key=\"LVFS::VersionFormat\">{}</value>\n", version_format(&payload.version)));
+    out.push_str("    <value key=\"LVFS::UpdateProtocol\">org.uefi.capsule</value>\n");
+    out.push_str("  </custom>\n");
+    out.push_str("  <releases>\n");
+    out.push_str(&format!("    <release urgency=\"medium\" version=\"{}\">\n", escape(&version)));
+    out.push_str(&format!("      <checksum filename=\"{}\" target=\"content\"/>\n", FWUPD_FIRMWARE));
+    out.push_str("      <description>\n        <p>Release notes are not part of the PFS file, write them here.</p>\n      </description>\n");
+    out.push_str("    </release>\n  </releases>\n</component>\n");
+    out
+}
+
+// Cabinet with the payload and the metainfo
+pub fn cabinet(payload : &FwupdPayload, metainfo : &str) -> io::Result<Vec<u8>> {
+    archive::cab(&[(FWUPD_FIRMWARE, &payload.data), (FWUPD_METAINFO, metainfo.as_bytes())])
+}
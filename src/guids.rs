@@ -0,0 +1,52 @@
+//
+// Section names by GUID, used when information section is missing or can't be parsed
+//
+use parser::Guid;
+use std::collections::HashMap;
+
+// Only names given by the caller, there is no built-in table as section GUIDs differ between models
+// and none are confirmed; sections without a name here are named by content::section_name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GuidNames {
+    names : HashMap<Guid, String>,
+}
+
+impl GuidNames {
+    pub fn get(&self, guid : &Guid) -> Option<&str> {
+        self.names.get(guid).map(String::as_str)
+    }
+
+    // Added names replace earlier ones with the same GUID
+    pub fn insert(&mut self, guid : Guid, name : &str) {
+        self.names.insert(guid, String::from(name));
+    }
+
+    // One GUID,name pair per line, names may be quoted and contain commas,
+    // blank lines, # comments and a GUID,name header line are ignored
+    // Returns the number of names read
+    pub fn add_csv(&mut self, text : &str) -> Result<usize, String> {
+        let mut count = 0;
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (guid, name) = match line.split_once(',') {
+                Some((guid, name)) => (guid.trim(), name.trim()),
+                None => return Err(format!("line {}: expected GUID,name", i + 1)),
+            };
+            let name = name.strip_prefix('"').and_then(|n| n.strip_suffix('"')).map_or(name, str::trim);
+            if i == 0 && guid.eq_ignore_ascii_case("guid") {
+                continue;
+            }
+            let guid = guid.trim_matches(|c| c == '{' || c == '}').parse()
+                .map_err(|e| format!("line {}: {}", i + 1, e))?;
+            if name.is_empty() {
+                return Err(format!("line {}: name is empty", i + 1));
+            }
+            self.insert(guid, name);
+            count += 1;
+        }
+        Ok(count)
+    }
+}
@@ -0,0 +1,27 @@
+//
+// Section handlers of the caller, for formats the extractor doesn't know
+//
+use error::PfsError;
+use parser::PfsSection;
+
+pub use extract::HandlerContext;
+
+// What extraction does with a section after its handler
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HandlerOutcome {
+    // Next matching handler is asked, then built-in handling looks into the data as usual
+    Declined,
+    // Section is done, nothing looks into its data any more
+    Handled,
+    // Data decoded from the section is extracted as a PFS file nested into it, like decompressed data
+    Nested(Vec<u8>),
+}
+
+// Handlers are asked in the order they are added to Options, before built-in handling of compressed
+// sections and subsections; section data, signatures and metadata are written before that as usual
+pub trait SectionHandler : Send + Sync {
+    // Section comes with its name from the information section or the GUID database
+    fn matches(&self, section : &PfsSection) -> bool;
+
+    fn handle(&self, ctx : &mut HandlerContext, data : &[u8]) -> Result<HandlerOutcome, PfsError>;
+}
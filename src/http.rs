@@ -0,0 +1,57 @@
+use reqwest::blocking::Client;
+use reqwest::redirect::Policy;
+use std::io::{self, Read};
+use std::time::Duration;
+
+//
+// HTTP and HTTPS downloads of inputs and update packages
+//
+
+// Redirects followed before giving up
+const MAX_REDIRECTS : usize = 5;
+
+const TIMEOUT : Duration = Duration::from_secs(60);
+
+fn invalid(message : String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+// Request errors only say which URL failed, the reason is in their sources
+fn request_error(e : reqwest::Error) -> io::Error {
+    let mut message = e.to_string();
+    let mut source = std::error::Error::source(&e);
+    while let Some(cause) = source {
+        message.push_str(&format!(": {}", cause));
+        source = cause.source();
+    }
+    io::Error::other(message)
+}
+
+// Data at the URL, following redirects, the body is read no further than max_size bytes
+pub fn get(url : &str, max_size : usize) -> io::Result<Vec<u8>> {
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(invalid(format!("{} is not an HTTP URL", url)));
+    }
+    let client = Client::builder()
+        .user_agent(concat!("PFSExtractor-RS/", env!("CARGO_PKG_VERSION")))
+        .redirect(Policy::limited(MAX_REDIRECTS))
+        .timeout(TIMEOUT)
+        .build()
+        .map_err(request_error)?;
+    let response = client.get(url).send().map_err(request_error)?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(io::Error::other(format!("{} answered with status {}", url, status.as_u16())));
+    }
+    if response.content_length().is_some_and(|length| length > max_size as u64) {
+        return Err(invalid(format!("response is larger than {} bytes", max_size)));
+    }
+
+    // Length may be missing or wrong, one byte more than allowed tells the body is too large
+    let mut body = Vec::new();
+    response.take(max_size as u64 + 1).read_to_end(&mut body)?;
+    if body.len() > max_size {
+        return Err(invalid(format!("response is larger than {} bytes", max_size)));
+    }
+    Ok(body)
+}
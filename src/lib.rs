@@ -0,0 +1,74 @@
+//
+// Parser
+//
+#[macro_use]
+extern crate log;
+extern crate lzma_rs;
+extern crate nom;
+extern crate quick_xml;
+extern crate cms;
+extern crate flate2;
+extern crate memmap2;
+extern crate rayon;
+#[cfg(feature = "http")]
+extern crate reqwest;
+extern crate rsa;
+extern crate serde;
+extern crate serde_json;
+#[cfg(feature = "serde")]
+extern crate base64ct;
+extern crate sha2;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+extern crate x509_cert;
+
+pub mod annotate;
+pub mod archive;
+#[cfg(feature = "tokio")]
+pub mod asynchronous;
+pub mod builder;
+pub mod cat;
+pub mod catalog;
+pub mod chain;
+pub mod compression;
+pub mod content;
+pub mod diff;
+pub mod dot;
+pub mod ec;
+pub mod error;
+pub mod ffi;
+pub mod fwupd;
+pub mod guids;
+pub mod handler;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod manifest;
+pub mod naming;
+pub mod parser;
+pub mod progress;
+pub mod reader;
+pub mod report;
+pub mod sbom;
+#[cfg(feature = "serde")]
+mod serialize;
+pub mod signature;
+pub mod summary;
+pub mod stats;
+pub mod uefitool;
+pub mod verify;
+pub mod walk;
+
+//
+// Extraction
+//
+pub mod extract;
+
+pub use parser::{PfsFile, PfsLayout, PfsSection, PfsHeader, PfsFooter, PfsInfoSection, PfsVersion, Guid, InputKind, classify};
+pub use error::PfsError;
+pub use builder::{PfsBuilder, PfsBuilderSection};
+pub use reader::PfsReader;
+pub use extract::{extract, extract_in_memory, ExistingPolicy, ExtractProfile, Extraction, Options, UnknownPolicy};
+pub use walk::{pfs_walk, PfsVisitor};
+pub use handler::{HandlerContext, HandlerOutcome, SectionHandler};
+#[cfg(feature = "tokio")]
+pub use asynchronous::{extract_async, ExtractionTask};
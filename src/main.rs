@@ -1,262 +1,1570 @@
-//
-// Parser
-//
-#[macro_use]
-extern crate nom;
-pub mod parser;
-
 //
 // Main
 //
-extern crate flate2;
+extern crate clap;
+#[macro_use]
+extern crate log;
+extern crate memmap2;
+extern crate pfsextractor;
+extern crate rayon;
 
+use std::cell::RefCell;
+use std::ffi::OsString;
 use std::fs::File;
 use std::io::prelude::*;
-use std::path::Path;
-use std::error::Error;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use std::fs::DirBuilder;
-use std::fs::OpenOptions;
-use flate2::read::ZlibDecoder;
+use clap::{ArgAction, Args, Parser, Subcommand};
+use clap::error::ErrorKind;
+use log::{Level, LevelFilter, Metadata, Record};
+use memmap2::Mmap;
+use rayon::prelude::*;
+use rayon::ThreadPoolBuilder;
+use pfsextractor::{annotate, cat, chain, diff, dot, extract, fwupd, manifest, parser, report, sbom, uefitool, verify, ExistingPolicy, ExtractProfile, Extraction, Options, PfsBuilder, PfsBuilderSection, PfsError, UnknownPolicy};
+use pfsextractor::error::{EXIT_IO, EXIT_PARSE, EXIT_PARTIAL, EXIT_SUCCESS, EXIT_USAGE, EXIT_VERIFY};
+use pfsextractor::extract::{pfs_set_names, SectionFilter};
+use pfsextractor::naming::NameTemplate;
+use pfsextractor::guids::GuidNames;
+use pfsextractor::progress::{Progress, ProgressMode};
+use pfsextractor::catalog::{Catalog, CatalogFilter, CatalogPackage};
+use pfsextractor::summary::{self, InputSummary};
+
+//
+// Command line
+//
+#[derive(Parser)]
+#[command(name = "pfsextractor", version, about = "Extracts contents of Dell firmware update files in PFS format")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// CSV file with GUID,name lines naming sections of files without a usable information section
+    #[arg(long, global = true, value_name = "guids.csv")]
+    guid_db: Option<PathBuf>,
+    /// Keep inputs downloaded from URLs in this directory and take them from there next time
+    #[arg(long, global = true, value_name = "path")]
+    url_cache: Option<PathBuf>,
+    /// Only print warnings and errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Print more details, like all chunk header fields, -vv for even more
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Extract sections into files (default when no command is given)
+    Extract(ExtractArgs),
+    /// Print every section, including nested ones, without writing files
+    List(ParseArgs),
+    /// Print PFS headers and section headers of the input only
+    Info(InfoArgs),
+    /// Check footer checksums and section signatures
+    Verify(VerifyArgs),
+    /// Build a PFS file from a directory extracted with --manifest
+    Repack(RepackArgs),
+    /// Replace data of sections and write the result as a new file
+    Edit(EditArgs),
+    /// Compare sections of two PFS files without extracting them
+    Diff(DiffArgs),
+    /// Write a Kaitai Struct or 010 Editor description with the offsets of headers, sections and footers of the input
+    Annotate(AnnotateArgs),
+    /// Write one part of one section to stdout, payloads decompressed and assembled
+    Cat(CatArgs),
+    /// Pick update packages from a Dell catalog by model or system ID, fetch and extract them
+    Catalog(CatalogArgs),
+}
+
+// Options shared by commands that walk the whole section tree
+#[derive(Args)]
+struct ParseArgs {
+    /// PFS files, Dell update executables, section dumps or directories with them
+    #[arg(required_unless_present = "input_list", value_name = "INPUT")]
+    inputs: Vec<PathBuf>,
+    /// File with one input path per line, blank lines and # comments are ignored
+    #[arg(long, value_name = "list.txt")]
+    input_list: Option<PathBuf>,
+    /// Also take files from subdirectories of input directories
+    #[arg(short, long)]
+    recursive: bool,
+    /// Treat input as sections without PFS header and footer
+    #[arg(long)]
+    headerless: bool,
+    /// Show progress of reading, decompressing and writing large data, as a bar or as percentage lines for logs
+    #[arg(long, value_name = "bar|plain", num_args = 0..=1, require_equals = true, default_missing_value = "bar", value_parser = parse_progress)]
+    progress: Option<ProgressMode>,
+    /// Print byte ranges of section data and chunks
+    #[arg(long)]
+    byte_range: bool,
+    /// Abort on the first section that can't be extracted instead of going on with the rest
+    #[arg(long)]
+    fail_fast: bool,
+    /// Abort on footer checksum mismatch or missing, duplicated and out of range chunks
+    #[arg(long)]
+    strict: bool,
+    /// Fail sections whose data or metadata doesn't match the SHA-2 digest signed in their signature
+    #[arg(long)]
+    verify_hashes: bool,
+    /// Fail sections whose PKCS#7 signature chain doesn't end at one of the roots of --ca-bundle, which it needs
+    #[arg(long)]
+    verify_signatures: bool,
+    /// PEM bundle of roots for --verify-signatures
+    #[arg(long, value_name = "roots.pem", requires = "verify_signatures")]
+    ca_bundle: Option<PathBuf>,
+    /// What to do with sections of unknown type
+    #[arg(long, value_name = "skip|write|fail", default_value = "write", value_parser = parse_unknown_policy)]
+    on_unknown: UnknownPolicy,
+    /// Write embedded controller firmware, found by section name, GUID or ITE signature, with .ec extension
+    #[arg(long)]
+    ec: bool,
+    /// Print content type of data files and append a matching extension to their names
+    #[arg(long)]
+    extensions: bool,
+    /// Write UEFI capsule header and body of payloads into separate files
+    #[arg(long)]
+    split_capsule: bool,
+    /// Abort extraction after this many seconds
+    #[arg(long, value_name = "seconds")]
+    timeout: Option<u64>,
+    /// Only extract the section with this GUID and everything inside it
+    #[arg(long, value_name = "GUID", value_parser = parse_guid)]
+    extract_only: Option<parser::Guid>,
+    /// Only extract sections with this GUID or name pattern and everything inside them, at any level
+    #[arg(long, value_name = "GUID|pattern")]
+    only: Vec<SectionFilter>,
+    /// Don't extract or look into sections with this GUID or name pattern
+    #[arg(long, value_name = "GUID|pattern")]
+    exclude: Vec<SectionFilter>,
+    /// Number of the first section in file names
+    #[arg(long, value_name = "0|1", default_value_t = 1, value_parser = parse_index_base)]
+    section_index_base: usize,
+    /// Don't extract PFS files or chunk payloads nested deeper than this
+    #[arg(long, value_name = "N", default_value_t = pfsextractor::extract::MAX_DEPTH)]
+    max_depth: usize,
+    /// Abort if a section decompresses to more than this, all compression layers together
+    #[arg(long, value_name = "bytes", default_value_t = pfsextractor::extract::MAX_DECOMPRESSED_SIZE)]
+    max_decompressed_size: usize,
+    /// Threads decompressing sections in parallel, 0 for one per CPU;
+    /// with 1, decompressed data is written to its file without being kept in memory
+    #[arg(short, long, value_name = "N", default_value_t = 0)]
+    jobs: usize,
+    /// Map input file into memory instead of reading it, the file must not change meanwhile
+    #[arg(long)]
+    mmap: bool,
+    /// Inputs of a batch processed at the same time, 0 for one per CPU; output of each input is printed once it's done
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    batch_jobs: usize,
+}
+
+#[derive(Args)]
+struct ExtractArgs {
+    #[command(flatten)]
+    parse: ParseArgs,
+    /// Directory to extract into, <input>.extracted by default
+    #[arg(short, long, value_name = "path")]
+    output_dir: Option<PathBuf>,
+    /// Write all files into a .zip, .tar or .tar.gz archive instead of a directory, nested sections get subdirectories
+    #[arg(long, value_name = "file", conflicts_with_all = ["output_dir", "to_stdout"], value_parser = parse_archive)]
+    archive: Option<PathBuf>,
+    /// Put files of nested PFS files into a subdirectory per section instead of prefixing their names
+    #[arg(long)]
+    tree: bool,
+    /// Print which file came from which section
+    #[arg(long)]
+    components_list: bool,
+    /// Write manifest.json describing every section and file
+    #[arg(long)]
+    manifest: bool,
+    /// Print SHA-256 of every written file and list them in SHA256SUMS
+    #[arg(long)]
+    sha256sums: bool,
+    /// Write data of the --extract-only section to stdout instead of files
+    #[arg(long, requires = "extract_only", conflicts_with = "input_list")]
+    to_stdout: bool,
+    /// Record footer checksum result in footer_checksum.txt
+    #[arg(long)]
+    write_footer_checksum_status: bool,
+    /// Write data left over after the parsed structures of every level into trailing.bin files
+    #[arg(long)]
+    save_unparsed: bool,
+    /// Write an HTML page with the section tree, sizes, hashes and data previews, linking extracted files
+    #[arg(long, value_name = "report.html", conflicts_with = "to_stdout")]
+    report: Option<PathBuf>,
+    /// Also write firmware.metainfo.xml and firmware.cab with the BIOS payload for fwupd and LVFS
+    #[arg(long, conflicts_with = "to_stdout")]
+    fwupd: bool,
+    /// Firmware GUID from /sys/firmware/efi/esrt of target systems, when the payload doesn't tell it
+    #[arg(long, value_name = "GUID", requires = "fwupd", value_parser = parse_guid)]
+    esrt_guid: Option<parser::Guid>,
+    /// Also write guids.csv naming section GUIDs for UEFITool
+    #[arg(long, conflicts_with = "to_stdout")]
+    guids_csv: bool,
+    /// Also write sbom.cdx.json, a CycloneDX SBOM with every section as a component with its version and hashes
+    #[arg(long, conflicts_with = "to_stdout")]
+    sbom: bool,
+    /// Files to write: full for all of them, payload-only for final section data without signatures, metadata
+    /// and data extracted further, bios-only for sections named as BIOS, forensic for everything with
+    /// --save-unparsed, --write-footer-checksum-status, --byte-range, --manifest and --sha256sums
+    #[arg(long, value_name = "full|payload-only|bios-only|forensic", default_value = "full", value_parser = parse_profile)]
+    profile: ExtractProfile,
+    /// Don't write files again that manifest.json of an earlier run in the output directory records
+    /// with the same SHA-256, write the others over the old ones; implies --manifest
+    #[arg(long, conflicts_with_all = ["archive", "to_stdout", "force", "skip_existing", "on_existing"])]
+    resume: bool,
+    /// Make output the same for the same input on any machine: file times are set to 1980-01-01
+    /// and line breaks taken from the input are made \n in generated text files
+    #[arg(long)]
+    deterministic: bool,
+    /// Look for bare zlib streams inside sections and write them decompressed as embedded_N.decompressed files
+    #[arg(long)]
+    deep_scan: bool,
+    /// Name section files by a template of {index}, {number}, {guid}, {name}, {version} and {kind},
+    /// separators in front of empty fields are dropped
+    #[arg(long, value_name = "template")]
+    name_template: Option<NameTemplate>,
+    /// What to do with files already in the output directory, which is reused unless this is fail
+    #[arg(long, value_name = "fail|overwrite|skip|rename", default_value = "fail", value_parser = parse_existing_policy)]
+    on_existing: ExistingPolicy,
+    /// Overwrite files already in the output directory, same as --on-existing overwrite
+    #[arg(long, conflicts_with_all = ["on_existing", "skip_existing"])]
+    force: bool,
+    /// Keep files already in the output directory, same as --on-existing skip
+    #[arg(long, conflicts_with = "on_existing")]
+    skip_existing: bool,
+    /// Only tell whether inputs are valid PFS files, one line each, exit code is the highest of all inputs
+    #[arg(long)]
+    check: bool,
+}
+
+#[derive(Args)]
+struct InfoArgs {
+    input: PathBuf,
+    /// Also write a Graphviz graph of PFS files, sections and compressed or chunked payloads at all nesting levels
+    #[arg(long, value_name = "out.dot")]
+    dot: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct VerifyArgs {
+    input: PathBuf,
+    /// RSA public key to check section signatures with
+    #[arg(long, value_name = "key.pem")]
+    pubkey: Option<PathBuf>,
+    /// PEM bundle of roots PKCS#7 signature chains have to end at, chains are not checked without it
+    #[arg(long, value_name = "roots.pem")]
+    ca_bundle: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct RepackArgs {
+    /// Directory with extracted files and manifest.json
+    dir: PathBuf,
+    /// PFS file to create
+    #[arg(short, long, value_name = "file")]
+    output: PathBuf,
+    /// Manifest to take sections from, <dir>/manifest.json by default
+    #[arg(long, value_name = "manifest.json")]
+    manifest: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct EditArgs {
+    input: PathBuf,
+    /// Section to replace and file with its new data, compressed sections are compressed again
+    #[arg(long, value_name = "GUID=file", required = true, value_parser = parse_replacement)]
+    replace: Vec<(parser::Guid, PathBuf)>,
+    /// PFS file to create
+    #[arg(short, long, value_name = "file")]
+    output: PathBuf,
+}
+
+#[derive(Args)]
+struct DiffArgs {
+    old: PathBuf,
+    new: PathBuf,
+}
+
+#[derive(Args)]
+struct AnnotateArgs {
+    input: PathBuf,
+    #[arg(long, value_name = "ksy|bt", default_value = "ksy")]
+    format: annotate::AnnotateFormat,
+    /// Description to write, input name with .ksy or .bt added by default
+    #[arg(short, long, value_name = "file")]
+    output: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct CatArgs {
+    input: PathBuf,
+    /// GUID of the section at any nesting level, or its index like 2 or 1.3
+    #[arg(long, value_name = "GUID|N")]
+    section: cat::SectionSelector,
+    /// Part to write, payload is data after decompression and chunk assembly
+    #[arg(long, value_name = "data|meta|payload", default_value = "payload")]
+    kind: cat::SectionPart,
+}
+
+#[derive(Args)]
+struct CatalogArgs {
+    /// CatalogPC.xml or Catalog.xml, taken out of the .cab Dell publishes it in
+    catalog: PathBuf,
+    /// Only packages for systems whose brand and model contain this, like "Latitude 7420"
+    #[arg(long, value_name = "name")]
+    model: Option<String>,
+    /// Only packages for the system with this ID, the four hex digits the BIOS reports
+    #[arg(long, value_name = "ID")]
+    system_id: Option<String>,
+    /// Only packages of this component type, all for every type
+    #[arg(long, value_name = "type", default_value = "BIOS")]
+    component_type: String,
+    /// Directory with packages under their catalog paths or file names, looked into before downloading
+    #[arg(long, value_name = "path")]
+    mirror: Option<PathBuf>,
+    /// Download packages found neither in the mirror nor in the output directory
+    #[arg(long)]
+    download: bool,
+    /// Directory packages are saved and extracted into, <catalog>.extracted by default
+    #[arg(short, long, value_name = "path")]
+    output_dir: Option<PathBuf>,
+    /// Only list the packages that would be fetched and extracted
+    #[arg(long)]
+    dry_run: bool,
+}
+
+fn parse_archive(value: &str) -> Result<PathBuf, String> {
+    let path = PathBuf::from(value);
+    match pfsextractor::archive::ArchiveFormat::from_path(&path) {
+        Some(_) => Ok(path),
+        None => Err(String::from("expected .zip, .tar, .tar.gz or .tgz file name")),
+    }
+}
+
+fn parse_progress(value: &str) -> Result<ProgressMode, String> {
+    match value {
+        "bar" => Ok(ProgressMode::Bar),
+        "plain" => Ok(ProgressMode::Plain),
+        _ => Err(String::from("expected bar or plain")),
+    }
+}
 
-const VERSION: Option<&'static str> = option_env!("CARGO_PKG_VERSION");
+fn parse_unknown_policy(value: &str) -> Result<UnknownPolicy, String> {
+    match value {
+        "write" => Ok(UnknownPolicy::Write),
+        "skip" => Ok(UnknownPolicy::Skip),
+        "fail" => Ok(UnknownPolicy::Fail),
+        _ => Err(String::from("expected skip, write or fail")),
+    }
+}
+
+fn parse_profile(value: &str) -> Result<ExtractProfile, String> {
+    match value {
+        "full" => Ok(ExtractProfile::Full),
+        "payload-only" => Ok(ExtractProfile::PayloadOnly),
+        "bios-only" => Ok(ExtractProfile::BiosOnly),
+        "forensic" => Ok(ExtractProfile::Forensic),
+        _ => Err(String::from("expected full, payload-only, bios-only or forensic")),
+    }
+}
+
+fn parse_existing_policy(value: &str) -> Result<ExistingPolicy, String> {
+    match value {
+        "fail" => Ok(ExistingPolicy::Fail),
+        "overwrite" => Ok(ExistingPolicy::Overwrite),
+        "skip" => Ok(ExistingPolicy::Skip),
+        "rename" => Ok(ExistingPolicy::Rename),
+        _ => Err(String::from("expected fail, overwrite, skip or rename")),
+    }
+}
+
+fn parse_guid(value: &str) -> Result<parser::Guid, String> {
+    value.parse().map_err(|e: parser::ParseGuidError| e.to_string())
+}
+
+fn parse_replacement(value: &str) -> Result<(parser::Guid, PathBuf), String> {
+    match value.split_once('=') {
+        Some((guid, path)) if !path.is_empty() => Ok((parse_guid(guid)?, PathBuf::from(path))),
+        _ => Err(String::from("expected GUID=file")),
+    }
+}
+
+fn parse_index_base(value: &str) -> Result<usize, String> {
+    match value {
+        "0" => Ok(0),
+        "1" => Ok(1),
+        _ => Err(String::from("expected 0 or 1")),
+    }
+}
+
+impl ParseArgs {
+    fn options(&self) -> Options {
+        Options::builder()
+            .progress(self.progress.unwrap_or_default())
+            .on_unknown(self.on_unknown)
+            .ec(self.ec)
+            .extensions(self.extensions)
+            .split_capsule(self.split_capsule)
+            .timeout(self.timeout.map(Duration::from_secs))
+            .extract_only(self.extract_only)
+            .only(self.only.clone())
+            .exclude(self.exclude.clone())
+            .byte_range(self.byte_range)
+            .index_base(self.section_index_base)
+            .headerless(self.headerless)
+            .strict(self.strict)
+            .verify_hashes(self.verify_hashes)
+            .verify_signatures(self.verify_signatures.then(|| signature_roots(self.ca_bundle.as_deref())))
+            .max_depth(self.max_depth)
+            .max_decompressed_size(self.max_decompressed_size)
+            .jobs(self.jobs)
+            .fail_fast(self.fail_fast)
+            .build()
+    }
+}
+
+//
+// Logging
+//
+// Output goes to stdout, diagnostics to stderr, so scripts can take one without the other
+struct Logger {
+    to_stderr: bool,
+}
+
+thread_local! {
+    // Lines logged for the batch input this thread works on, printed together once it's done
+    static LOG_BUFFER: RefCell<Option<Vec<(Level, String)>>> = const { RefCell::new(None) };
+}
+
+impl Logger {
+    fn print(&self, level: Level, line: &str) {
+        // Failing to print is not worth aborting extraction for
+        if level == Level::Info && !self.to_stderr {
+            let _ = writeln!(std::io::stdout().lock(), "{}", line);
+        } else {
+            let _ = writeln!(std::io::stderr().lock(), "{}", line);
+        }
+    }
+}
+
+// Runs f with everything it logs kept back, then prints it in one piece. Threads waiting for
+// other work can pick up another input meanwhile, lines of the one they were busy with are kept aside
+fn buffered_log<T, F: FnOnce() -> T>(f: F) -> T {
+    let outer = LOG_BUFFER.with(|buffer| buffer.borrow_mut().replace(Vec::new()));
+    let result = f();
+    let lines = LOG_BUFFER.with(|buffer| std::mem::replace(&mut *buffer.borrow_mut(), outer)).unwrap_or_default();
+    let (_stdout, _stderr) = (std::io::stdout().lock(), std::io::stderr().lock());
+    let logger = Logger { to_stderr: false };
+    for (level, line) in lines {
+        logger.print(level, &line);
+    }
+    result
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let kept = LOG_BUFFER.with(|buffer| match *buffer.borrow_mut() {
+            Some(ref mut lines) => {
+                lines.push((record.level(), record.args().to_string()));
+                true
+            }
+            None => false,
+        });
+        if !kept {
+            self.print(record.level(), &record.args().to_string());
+        }
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stdout().flush();
+    }
+}
 
 fn main() {
-    // Obtain program arguments
-    let mut args = std::env::args_os();
+    // Arguments without a command mean extraction, as in older versions
+    let mut args: Vec<OsString> = std::env::args_os().collect();
+    let commands = ["extract", "list", "info", "verify", "repack", "edit", "diff", "annotate", "cat", "catalog", "help", "-h", "--help", "-V", "--version"];
+    if let Some(first) = args.get(1).map(|a| a.to_string_lossy().into_owned()) {
+        if !commands.contains(&first.as_str()) {
+            args.insert(1, OsString::from("extract"));
+        }
+    }
+
+    // Usage errors exit with 1, help and version output with 0
+    let cli = match Cli::try_parse_from(args) {
+        Ok(cli) => cli,
+        Err(e) => {
+            let _ = e.print();
+            match e.kind() {
+                ErrorKind::DisplayHelp | ErrorKind::DisplayVersion => std::process::exit(EXIT_SUCCESS),
+                _ => std::process::exit(EXIT_USAGE),
+            }
+        }
+    };
 
-    // Check if we have none
-    if args.len() <= 1 {
-        println!("
-PFSExtractor v{} - extracts contents of Dell firmware update files in PFS format
-Usage: pfsextractor pfs_file.bin", VERSION.unwrap_or("1.0.2"));
-        std::process::exit(1);
+    // Extracted data written to stdout leaves no room for anything else there
+    let to_stdout = matches!(cli.command, Command::Extract(ref args) if args.to_stdout) || matches!(cli.command, Command::Cat(_));
+    let level = match cli.verbose {
+        _ if cli.quiet => LevelFilter::Warn,
+        0 => LevelFilter::Info,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    log::set_max_level(level);
+    if log::set_boxed_logger(Box::new(Logger { to_stderr: to_stdout })).is_err() {
+        std::process::exit(EXIT_USAGE);
     }
-    
-    // The only expected argument is a path to input file
-    let arg = args.nth(1).expect("Failed to obtain file path");
-    let path = Path::new(&arg);
-    println!("Obtained file path: {:?}", path);
-    
-    // Open input file
-    let mut file = match File::open(&path) {
-        Err(e) => {println!("Can't open {:?}: {}", path, e.description()); std::process::exit(2);}
-        Ok(f) => f
+
+    // Names of sections by GUID only come from the file
+    let mut guid_names = GuidNames::default();
+    if let Some(ref path) = cli.guid_db {
+        let text = std::fs::read_to_string(path).unwrap_or_else(|e| {error!("Can't read {:?}: {}", path, e); std::process::exit(EXIT_IO)});
+        if let Err(e) = guid_names.add_csv(&text) {
+            error!("GUID database {:?} can't be parsed, {}", path, e);
+            std::process::exit(EXIT_USAGE);
+        }
+    }
+
+    if let Some(cache) = cli.url_cache.clone() {
+        let _ = URL_CACHE.set(cache);
+    }
+
+    match cli.command {
+        Command::Extract(ref args) if args.check => check_main(&args.parse),
+        Command::Extract(args) => {
+            let mut options = args.parse.options();
+            options.components_list = args.components_list;
+            options.manifest = args.manifest;
+            options.sha256sums = args.sha256sums;
+            options.to_stdout = args.to_stdout;
+            options.checksum_status = args.write_footer_checksum_status;
+            options.archive = args.archive.clone();
+            options.tree = args.tree;
+            options.save_unparsed = args.save_unparsed;
+            options.report = args.report.clone();
+            options.fwupd = args.fwupd;
+            options.esrt_guid = args.esrt_guid;
+            options.guids_csv = args.guids_csv;
+            options.sbom = args.sbom;
+            options.deep_scan = args.deep_scan;
+            options.name_template = args.name_template.clone();
+            options.on_existing = match (args.force, args.skip_existing) {
+                (true, _) => ExistingPolicy::Overwrite,
+                (_, true) => ExistingPolicy::Skip,
+                _ => args.on_existing,
+            };
+            options.guid_names = guid_names;
+            options.verbose = cli.verbose > 0;
+            options.set_profile(args.profile);
+            options.deterministic = args.deterministic;
+            options.resume = args.resume;
+            options.manifest |= args.resume;
+            // Files skipped or renamed depend on what an earlier run left behind
+            if options.deterministic && matches!(options.on_existing, ExistingPolicy::Skip | ExistingPolicy::Rename) {
+                error!("--deterministic can't be combined with --skip-existing or --on-existing skip and rename");
+                std::process::exit(EXIT_USAGE);
+            }
+            extract_main(&options, &args.parse, args.output_dir);
+        }
+        Command::List(args) => {
+            let mut options = args.options();
+            options.list = true;
+            options.guid_names = guid_names;
+            options.verbose = cli.verbose > 0;
+            extract_main(&options, &args, None);
+        }
+        Command::Info(args) => info_main(args, &guid_names),
+        Command::Verify(args) => verify_main(args, &guid_names),
+        Command::Repack(args) => repack_main(args),
+        Command::Edit(args) => edit_main(args, &guid_names),
+        Command::Diff(args) => diff_main(args, &guid_names),
+        Command::Annotate(args) => annotate_main(args),
+        Command::Cat(args) => cat_main(args),
+        Command::Catalog(args) => catalog_main(args, guid_names, cli.verbose > 0),
+    }
+}
+
+fn extract_main(options: &Options, args: &ParseArgs, output_dir: Option<PathBuf>) {
+    let mut paths: Vec<PathBuf> = args.inputs.clone();
+
+    // Input list has one path per line, blank lines and # comments are ignored
+    if let Some(ref list) = args.input_list {
+        match std::fs::read_to_string(list) {
+            Err(e) => {error!("Can't read {:?}: {}", list, e); std::process::exit(EXIT_IO);}
+            Ok(text) => {
+                paths.extend(text.lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(PathBuf::from));
+            }
+        }
+    }
+
+    // Single input file keeps its exit code, batch reports a summary instead
+    let batch = args.input_list.is_some() || paths.len() != 1 || paths[0].is_dir();
+    if !batch {
+        let may_exist = output_dir.is_some();
+        let dir = output_dir.unwrap_or_else(|| default_output_dir(&paths[0]));
+        match extract_input(options, &paths[0], &dir, may_exist, args.mmap) {
+            Ok(true) => (),
+            Ok(false) => std::process::exit(EXIT_PARSE),
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(e.exit_code());
+            }
+        }
+        return;
+    }
+    if options.to_stdout || options.archive.is_some() || options.report.is_some() {
+        error!("--to-stdout, --archive and --report need a single input file");
+        std::process::exit(EXIT_USAGE);
+    }
+
+    // Directories are replaced by files in them, names are kept relative to the directory
+    let mut inputs: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            if let Err(e) = collect_dir(&path, Path::new(""), args.recursive, &mut inputs) {
+                error!("Can't read {:?}: {}", path, e);
+                std::process::exit(EXIT_IO);
+            }
+        } else {
+            let name = PathBuf::from(path.file_name().unwrap_or_default());
+            inputs.push((path, name));
+        }
+    }
+
+    if inputs.is_empty() {
+        error!("Missing input file");
+        std::process::exit(EXIT_USAGE);
+    }
+
+    // Every input of a batch gets its own directory inside the output one
+    if let Some(ref out) = output_dir {
+        if let Err(e) = DirBuilder::new().recursive(true).create(out) {
+            error!("Can't create {:?}: {}", out, e);
+            std::process::exit(EXIT_PARTIAL);
+        }
+    }
+    let extract_one = |input: &Path, name: &Path| -> (Result<bool, PfsError>, InputSummary) {
+        let dir = match output_dir {
+            Some(ref out) => {
+                let dir = default_output_dir(&out.join(name));
+                if let Some(parent) = dir.parent() {
+                    if let Err(e) = DirBuilder::new().recursive(true).create(parent) {
+                        return (Err(PfsError::Write(parent.to_path_buf(), e)), InputSummary::default());
+                    }
+                }
+                dir
+            }
+            None => default_output_dir(input),
+        };
+        let data = match read_input(input, args.mmap, options.progress) {
+            Ok(data) => data,
+            Err(e) => return (Err(e), InputSummary::default()),
+        };
+        let summary = summary::summarize(&data, &options.guid_names);
+        (extract_data(options, input, &data, &dir, false), summary)
     };
-    
-    // Read the whole file as binary data
-    let mut data = Vec::new();
-    match file.read_to_end(&mut data) {
-        Err(e) => {println!("Can't read {:?}: {}", path, e.description()); std::process::exit(3);}
-        Ok(_) => {println!("Bytes read: 0x{:X}", &data.len());}
+    // One failing input doesn't stop the others, not even when extraction panics
+    let run = |(input, name): &(PathBuf, PathBuf)| -> (Option<Result<bool, PfsError>>, InputSummary) {
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| extract_one(input, name))) {
+            Ok((result, summary)) => (Some(result.map_err(|e| {error!("{}", e); e})), summary),
+            Err(_) => {
+                error!("Extraction of {:?} crashed", input);
+                (None, InputSummary::default())
+            }
+        }
+    };
+    let results: Vec<_> = if args.batch_jobs == 1 {
+        inputs.iter().map(run).collect()
+    } else {
+        let pool = ThreadPoolBuilder::new().num_threads(args.batch_jobs).build().unwrap_or_else(|e| {error!("Can't start threads: {}", e); std::process::exit(EXIT_PARTIAL)});
+        pool.install(|| inputs.par_iter().map(|input| buffered_log(|| run(input))).collect())
+    };
+
+    info!("");
+    info!("Summary:");
+    info!("{:<12} {:<32} {:<16} File", "Status", "Models", "BIOS version");
+    for ((input, _), (result, summary)) in inputs.iter().zip(results.iter()) {
+        let status = match *result {
+            Some(Ok(true)) => "OK",
+            Some(Ok(false)) => "PARSE ERROR",
+            Some(Err(PfsError::Open(..))) | Some(Err(PfsError::Read(..))) | Some(Err(PfsError::Write(..))) => "I/O ERROR",
+            Some(Err(PfsError::Sections(_))) => "INCOMPLETE",
+            Some(Err(PfsError::Unverified(_))) => "UNVERIFIED",
+            Some(Err(_)) => "ABORTED",
+            None => "CRASHED",
+        };
+        let models = if summary.models.is_empty() { String::from("-") } else { summary.models.join(", ") };
+        info!("{:<12} {:<32} {:<16} {:?}", status, models, summary.bios_version.as_deref().unwrap_or("-"), input);
+    }
+    let failed = results.iter().filter(|r| !matches!(r.0, Some(Ok(true)))).count();
+    info!("{} of {} files extracted, {} failed", inputs.len() - failed, inputs.len(), failed);
+    if failed > 0 {
+        std::process::exit(EXIT_PARTIAL);
     }
+}
+
 
-    // Create directory for extracted components
-    let mut new_arg = arg.clone();
-    new_arg.push(".extracted");
-    let dir = Path::new(&new_arg);
-    match DirBuilder::new().create(&dir) {
-        Err(e) => {println!("Can't create {:?}: {}", dir, e.description()); std::process::exit(4);}
-        Ok(_) => {println!("Directory created: {:?}", &dir);}
+// Valid PFS file has to parse and match its footer checksum, nothing else is printed
+fn check_main(args: &ParseArgs) {
+    let mut paths = args.inputs.clone();
+    if let Some(ref list) = args.input_list {
+        let text = std::fs::read_to_string(list).unwrap_or_else(|e| {error!("Can't read {:?}: {}", list, e); std::process::exit(EXIT_IO)});
+        paths.extend(text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')).map(PathBuf::from));
     }
-    
-    // Set that created directory as current 
-    match std::env::set_current_dir(&dir) {
-        Err(e) => {println!("Can't change current directory: {}", e.description()); std::process::exit(5);}
-        Ok(_) => {println!("Current directory changed")} 
+
+    let mut code = EXIT_SUCCESS;
+    for path in &paths {
+        let (status, result) = match std::fs::read(path) {
+            Err(e) => (format!("can't read: {}", e), EXIT_IO),
+            Ok(data) => match parser::pfs_file(&data) {
+                Err(_) => (String::from("not a valid PFS file"), EXIT_PARSE),
+                Ok((_, ref file)) if verify::pfs_file_checksum(&data, file) != Some(file.footer.checksum) =>
+                    (String::from("footer checksum mismatch"), EXIT_VERIFY),
+                Ok(_) => (String::from("valid PFS file"), EXIT_SUCCESS),
+            },
+        };
+        info!("{}: {}", path.display(), status);
+        code = code.max(result);
+    }
+    std::process::exit(code);
+}
+
+
+// Files of a directory in name order, output directories of earlier runs are left out
+fn collect_dir(dir: &Path, prefix: &Path, recursive: bool, inputs: &mut Vec<(PathBuf, PathBuf)>) -> std::io::Result<()> {
+    let mut entries = std::fs::read_dir(dir)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        let name = prefix.join(entry.file_name());
+        if path.is_dir() {
+            if recursive && path.extension().is_none_or(|ext| ext != "extracted") {
+                collect_dir(&path, &name, recursive, inputs)?;
+            }
+        } else {
+            inputs.push((path, name));
+        }
+    }
+    Ok(())
+}
+
+
+// Extracted files go next to the input by default, into the current directory for URLs
+fn default_output_dir(input: &Path) -> PathBuf {
+    if let Some(url) = input_url(input) {
+        let name = url.split(['?', '#']).next().unwrap_or_default().trim_end_matches('/').rsplit('/').next().unwrap_or_default();
+        return PathBuf::from(format!("{}.extracted", name));
     }
+    let mut dir = input.as_os_str().to_os_string();
+    dir.push(".extracted");
+    PathBuf::from(dir)
+}
+
+// Returns whether the input was parsed as PFS file
+fn extract_input(options: &Options, input: &Path, dir: &Path, may_exist: bool, mmap: bool) -> Result<bool, PfsError> {
+    let data = read_input(input, mmap, options.progress)?;
+    extract_data(options, input, &data, dir, may_exist)
+}
+
+fn extract_data(options: &Options, input: &Path, data: &[u8], dir: &Path, may_exist: bool) -> Result<bool, PfsError> {
+
+    // Nothing is written to disk when selected section goes to stdout
+    if options.to_stdout {
+        let extraction = extract(options, data, dir);
+        let parsed = extraction.result?;
+        if extraction.captured.len() != 1 {
+            return Err(PfsError::Selection(extraction.captured.len()));
+        }
+        let stdout = std::io::stdout();
+        stdout.lock().write_all(&extraction.captured[0]).map_err(PfsError::Stdout)?;
+        return Ok(parsed);
+    }
+
+    // List mode doesn't touch the filesystem
+    if options.list {
+        return extract(options, data, dir).result;
+    }
+
+    // Archive gets the same files as the directory would
+    if let Some(ref path) = options.archive {
+        let mut extraction = extract(options, data, dir);
+        let mut archive = match extraction.archive.take() {
+            Some(archive) => archive,
+            None => return extraction.result,
+        };
+        info!("Archive written: {:?}", path);
+        if options.components_list {
+            info!("");
+            info!("Components:");
+            for (index, filename) in &extraction.components {
+                info!("{:<12} {}", index, filename);
+            }
+        }
+        let write = |e| PfsError::Write(path.clone(), e);
+        if options.manifest {
+            archive.add(manifest::MANIFEST_JSON, manifest::to_json(&extraction.manifest, &extraction.unparsed).as_bytes()).map_err(write)?;
+        }
+        if options.sha256sums {
+            archive.add("SHA256SUMS", manifest::sha256sums(&extraction.hashes).as_bytes()).map_err(write)?;
+        }
+        for (name, file) in fwupd_files(options, input, data)?.into_iter().chain(guids_files(options, input, data)?).chain(sbom_files(options, input, data)?) {
+            archive.add(name, &file).map_err(write)?;
+        }
+        archive.finish().map_err(write)?;
+        write_report(options, input, data, &extraction, None)?;
+        set_deterministic_times(options, std::iter::once(path.clone()))?;
+        return extraction.result;
+    }
+
+    // Create directory for extracted components, output directory given by user may already exist,
+    // any other is reused only when there is a policy for the files in it
+    let reuse = may_exist || options.on_existing != ExistingPolicy::Fail || options.resume;
+    if !reuse && dir.exists() {
+        warn!("{:?} exists, --force, --skip-existing or --on-existing rename extract into it anyway", dir);
+    }
+    DirBuilder::new().recursive(reuse).create(dir).map_err(|e| PfsError::Write(dir.to_path_buf(), e))?;
+    info!("Directory created: {:?}", dir);
 
     // Call extraction function
-    pfs_extract(&data, "");
+    let extraction = extract(options, data, dir);
+
+    // Print which file came from which section
+    if options.components_list {
+        info!("");
+        info!("Components:");
+        for (index, filename) in &extraction.components {
+            info!("{:<12} {}", index, filename);
+        }
+    }
+
+    // Files written here besides the extracted ones
+    let mut written = Vec::new();
+
+    // Machine-readable description of everything extracted
+    if options.manifest {
+        let path = dir.join(manifest::MANIFEST_JSON);
+        std::fs::write(&path, manifest::to_json(&extraction.manifest, &extraction.unparsed)).map_err(|e| PfsError::Write(path.clone(), e))?;
+        written.push(path);
+    }
+
+    // Hashes of written files, checkable with sha256sum -c from the output directory
+    if options.sha256sums {
+        let path = dir.join("SHA256SUMS");
+        std::fs::write(&path, manifest::sha256sums(&extraction.hashes)).map_err(|e| PfsError::Write(path.clone(), e))?;
+        written.push(path);
+    }
+    for (name, file) in fwupd_files(options, input, data)?.into_iter().chain(guids_files(options, input, data)?).chain(sbom_files(options, input, data)?) {
+        let path = dir.join(name);
+        std::fs::write(&path, file).map_err(|e| PfsError::Write(path.clone(), e))?;
+        written.push(path);
+    }
+    write_report(options, input, data, &extraction, Some(dir))?;
+    set_deterministic_times(options, extraction.components.iter().map(|c| dir.join(&c.1)).chain(written))?;
+    extraction.result
+}
+
+// Shown in reports and generated files instead of the whole path
+fn input_name(input: &Path) -> String {
+    input.file_name().unwrap_or(input.as_os_str()).to_string_lossy().into_owned()
 }
 
+// Line breaks in names and versions taken from the input are made \n with --deterministic, generated text has no others
+fn text_file(options: &Options, text: String) -> String {
+    if options.deterministic && text.contains('\r') {
+        text.replace("\r\n", "\n").replace('\r', "\n")
+    } else {
+        text
+    }
+}
 
-fn write_file(data: &[u8], filename: &str) -> () {
-    let mut file = OpenOptions::new().write(true)   
-                             .create_new(true)
-                             .open(filename)
-                             .expect(&format!("Can't create file {:?}", filename));
+// Written files get the time entries of zip archives have, 1980-01-01
+const DETERMINISTIC_TIME: u64 = 315532800;
 
-    file.write(data).expect("Can't write data into file");
+fn set_deterministic_time(path: &Path) -> Result<(), PfsError> {
+    let time = UNIX_EPOCH + Duration::from_secs(DETERMINISTIC_TIME);
+    File::options().write(true).open(path).and_then(|file| file.set_modified(time)).map_err(|e| PfsError::Write(path.to_path_buf(), e))
+}
+
+// Only files of this run are changed, anything else already in the output directory keeps its time,
+// directories keep theirs as not every system lets them be changed
+fn set_deterministic_times(options: &Options, files: impl Iterator<Item = PathBuf>) -> Result<(), PfsError> {
+    if !options.deterministic {
+        return Ok(());
+    }
+    for path in files {
+        set_deterministic_time(&path)?;
+    }
+    options.report.as_deref().map_or(Ok(()), set_deterministic_time)
+}
+
+// Metainfo and cabinet for fwupd, none if they aren't requested or there is no BIOS payload
+fn fwupd_files(options: &Options, input: &Path, data: &[u8]) -> Result<Vec<(&'static str, Vec<u8>)>, PfsError> {
+    if !options.fwupd {
+        return Ok(Vec::new());
+    }
+    let payload = match fwupd::find_bios(data) {
+        Ok(Some(payload)) => payload,
+        _ => {
+            warn!("No BIOS payload found, fwupd files are not written");
+            return Ok(Vec::new());
+        }
+    };
+    info!("fwupd payload: section {} {}, size {:X}", payload.index, payload.name, payload.data.len());
+    let guid = fwupd::component_guid(&payload, options.esrt_guid);
+    let metainfo = text_file(options, fwupd::metainfo(&payload, &guid, &input_name(input)));
+    let cabinet = fwupd::cabinet(&payload, &metainfo).map_err(|e| PfsError::Write(PathBuf::from("firmware.cab"), e))?;
+    Ok(vec![(fwupd::FWUPD_METAINFO, metainfo.into_bytes()), ("firmware.cab", cabinet)])
+}
+
+// GUID database for UEFITool, none if it isn't requested
+fn guids_files(options: &Options, input: &Path, data: &[u8]) -> Result<Vec<(&'static str, Vec<u8>)>, PfsError> {
+    if !options.guids_csv {
+        return Ok(Vec::new());
+    }
+    let csv = uefitool::guids_csv(data, &input_name(input))?;
+    Ok(vec![(uefitool::GUIDS_CSV, text_file(options, csv).into_bytes())])
+}
+
+// CycloneDX SBOM, none if it isn't requested
+fn sbom_files(options: &Options, input: &Path, data: &[u8]) -> Result<Vec<(&'static str, Vec<u8>)>, PfsError> {
+    if !options.sbom {
+        return Ok(Vec::new());
+    }
+    let json = sbom::sbom_json(data, &input_name(input))?;
+    Ok(vec![(sbom::SBOM_JSON, text_file(options, json).into_bytes())])
+}
+
+// Files in the report are linked relative to it when the output directory is next to it or below
+fn report_links(report: &Path, dir: &Path) -> String {
+    let base = match report.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let relative = base.canonicalize().ok().and_then(|base| dir.strip_prefix(base).ok().map(Path::to_path_buf));
+    relative.unwrap_or(dir).to_string_lossy().into_owned()
+}
+
+// HTML report of the extraction, archives have no files to link
+fn write_report(options: &Options, input: &Path, data: &[u8], extraction: &Extraction, dir: Option<&Path>) -> Result<(), PfsError> {
+    let path = match options.report {
+        Some(ref path) => path,
+        None => return Ok(()),
+    };
+    let status = match extraction.result {
+        Ok(true) => String::from("Extracted"),
+        Ok(false) => String::from("Not a PFS file"),
+        Err(ref e) => e.to_string(),
+    };
+    let links = dir.map(|dir| report_links(path, dir));
+    let html = text_file(options, report::to_html(&input_name(input), data, &status, &extraction.manifest, &extraction.unparsed, links.as_deref()));
+    std::fs::write(path, html).map_err(|e| PfsError::Write(path.clone(), e))?;
+    info!("Report written: {:?}", path);
+    Ok(())
 }
 
 
-fn pfs_extract(data: &[u8], prefix: &str) -> () {
-    match parser::pfs_file(data) {
-        Ok((unp, mut file)) => {
-            if unp.len() > 0 {
-                println!("Unparsed size: {:X}", unp.len());
+// Input data either read into memory or mapped from the file
+enum Input {
+    Read(Vec<u8>),
+    Mapped(Mmap),
+}
+
+impl Deref for Input {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match *self {
+            Input::Read(ref data) => data,
+            Input::Mapped(ref map) => map,
+        }
+    }
+}
+
+// Input is read in blocks of this size, progress is updated after each
+const READ_BLOCK_SIZE: usize = 0x100000;
+
+// Downloads larger than this are dropped, Dell update packages stay far below it
+#[cfg(feature = "http")]
+const MAX_DOWNLOAD_SIZE: usize = 0x40000000;
+
+// Directory given with --url-cache, set once before any input is read
+static URL_CACHE: OnceLock<PathBuf> = OnceLock::new();
+
+// Inputs given as http:// or https:// URLs are downloaded instead of read
+fn input_url(path: &Path) -> Option<&str> {
+    path.to_str().filter(|p| p.starts_with("http://") || p.starts_with("https://"))
+}
+
+// Cache keeps downloads in files named by their SHA-256, urls.txt tells which URL gave which file;
+// a cached file whose hash doesn't match any more is downloaded again
+fn cached_url(cache: &Path, url: &str) -> Option<Vec<u8>> {
+    let index = std::fs::read_to_string(cache.join("urls.txt")).ok()?;
+    let sha256 = index.lines().rev().filter_map(|line| line.split_once(' ')).find(|l| l.1 == url)?.0;
+    let data = std::fs::read(cache.join(sha256)).ok()?;
+    Some(data).filter(|data| manifest::sha256(data) == sha256)
+}
+
+#[cfg(feature = "http")]
+fn cache_url(cache: &Path, url: &str, data: &[u8]) -> std::io::Result<()> {
+    DirBuilder::new().recursive(true).create(cache)?;
+    let sha256 = manifest::sha256(data);
+    let path = cache.join(&sha256);
+    if !path.is_file() {
+        std::fs::write(path, data)?;
+    }
+    let mut index = std::fs::OpenOptions::new().create(true).append(true).open(cache.join("urls.txt"))?;
+    writeln!(index, "{} {}", sha256, url)
+}
+
+fn download_input(url: &str) -> Result<Input, PfsError> {
+    let cache = URL_CACHE.get();
+    if let Some(data) = cache.and_then(|cache| cached_url(cache, url)) {
+        info!("Bytes taken from the URL cache: 0x{:X}", data.len());
+        return Ok(Input::Read(data));
+    }
+    #[cfg(feature = "http")]
+    {
+        info!("Downloading {}", url);
+        let data = pfsextractor::http::get(url, MAX_DOWNLOAD_SIZE).map_err(|e| PfsError::Read(PathBuf::from(url), e))?;
+        info!("Bytes downloaded: 0x{:X}", data.len());
+        if let Some(cache) = cache {
+            if let Err(e) = cache_url(cache, url, &data) {
+                warn!("Can't keep the download in {:?}: {}", cache, e);
             }
+        }
+        Ok(Input::Read(data))
+    }
+    #[cfg(not(feature = "http"))]
+    Err(PfsError::Read(PathBuf::from(url), std::io::Error::new(std::io::ErrorKind::Unsupported, "this build has no http feature")))
+}
 
-            // Parse information section to obtain proper section names
-            {
-                // Information section is the last one
-                let (info_section, other_sections) = (&mut file.sections).split_last_mut().unwrap();
-                if info_section.data_size != 0 {
-                    match parser::pfs_info(info_section.data.unwrap()) {
-                        Ok((unp, info)) => {
-                            if unp.len() > 0 {
-                                println!("Unparsed size: {:X}", unp.len());
-                            }
-
-                            // Set section names
-                            info_section.name = String::from("Section Info");
-                            let mut i = 0;
-                            for section in info {
-                                if i < other_sections.len() {
-                                    other_sections[i].name = section.name;
-                                    i += 1;
-                                }
-                                else {
-                                    break;
-                                }
-                            }
-                            if i == other_sections.len() - 1 {
-                                other_sections[i].name =  String::from("Model Properties");
-                            }
-                        }
-                        _ => { println!("PFS info section parse error, falling back to generic names"); }
-                    }
+fn read_input(path: &Path, mmap: bool, progress: ProgressMode) -> Result<Input, PfsError> {
+    info!("Obtained file path: {:?}", path);
+    if let Some(url) = input_url(path) {
+        return download_input(url);
+    }
+    
+    // Open input file
+    let mut file = File::open(path).map_err(|e| PfsError::Open(path.to_path_buf(), e))?;
+    
+    // Mapped input is paged in on demand, so huge images don't need the memory to hold them
+    if mmap {
+        // Safety: the file is only read, and the flag documents that it must not change meanwhile
+        let map = unsafe { Mmap::map(&file) }.map_err(|e| PfsError::Read(path.to_path_buf(), e))?;
+        info!("Bytes mapped: 0x{:X}", map.len());
+        return Ok(Input::Mapped(map));
+    }
+
+    // Read the whole file as binary data, block by block to show progress
+    let size = file.metadata().map_or(0, |m| m.len());
+    let mut progress = Progress::new("Reading", size, progress);
+    let mut data = Vec::with_capacity(size as usize);
+    let mut block = vec![0u8; READ_BLOCK_SIZE];
+    loop {
+        let n = match file.read(&mut block) {
+            Ok(0) => break,
+            Ok(n) => n,
+            Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(PfsError::Read(path.to_path_buf(), e)),
+        };
+        data.extend_from_slice(&block[..n]);
+        progress.update(data.len() as u64);
+    }
+    progress.finish();
+    info!("Bytes read: 0x{:X}", &data.len());
+    Ok(Input::Read(data))
+}
+
+
+fn info_main(args: InfoArgs, guid_names: &GuidNames) {
+    let data = read_input(&args.input, false, ProgressMode::Off).unwrap_or_else(|e| {error!("{}", e); std::process::exit(e.exit_code())});
+    let (mut rest, mut file) = match parser::pfs_file(&data) {
+        Ok(parsed) => parsed,
+        _ => {error!("PFS file parse error, this file can't be parsed"); std::process::exit(PfsError::Parse.exit_code());}
+    };
+
+    // Headers of every PFS file in the input, nested data is not looked into
+    let mut blob: &[u8] = &data;
+    let mut n = 1;
+    loop {
+        pfs_set_names(&mut file, guid_names);
+        info!("");
+        info!("PFS file {}:", n);
+        info!("Header version: {:X}", file.header.header_version);
+        info!("Data size: {:X}", file.header.data_size);
+        info!("Capsule type: {}", file.capsule_type());
+        info!("Footer checksum: {:08X}{}", file.footer.checksum,
+            if verify::pfs_file_checksum(blob, &file) == Some(file.footer.checksum) { "" } else { " (mismatch)" });
+        info!("Sections: {}", file.sections.len());
+        info!("{:<4} {:<36} {:<8} {:<6} {:<6} {:<6} {}", "#", "GUID", "Data", "Sig", "Meta", "MSig", "Name");
+        for (i, section) in file.iter().enumerate() {
+            let name = if section.name.is_empty() { format!("section_{}", i + 1) } else { section.name.clone() };
+            info!("{:<4} {} {:<8X} {:<6X} {:<6X} {:<6X} {}", i + 1, section.guid,
+                section.data_size, section.data_sig_size, section.meta_size, section.meta_sig_size, name);
+        }
+
+        match parser::trailing_kind(rest) {
+            parser::TrailingKind::Pfs(offset) => {
+                blob = &rest[offset..];
+                match parser::pfs_file(blob) {
+                    Ok((next_rest, next)) => { rest = next_rest; file = next; }
+                    _ => break,
                 }
+                n += 1;
             }
+            _ => break,
+        }
+    }
 
-            let mut i = 0;
-            for section in file.sections {
-                println!("");
-                i += 1;
-                
-                // Print infomation
-                println!("GUID: {:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}",
-                        section.guid.data1,
-                        section.guid.data2,
-                        section.guid.data3,
-                        section.guid.data4[0], section.guid.data4[1], section.guid.data4[2], section.guid.data4[3],
-                        section.guid.data4[4], section.guid.data4[5], section.guid.data4[6], section.guid.data4[7]);
-                println!("Header version: {:X}", section.header_version);
-                println!("Data size: {:X}", section.data_size);
-                println!("Data signature size: {:X}", section.data_sig_size);
-                println!("Metadata size: {:X}", section.meta_size);
-                println!("Metadata signature size: {:X}", section.meta_sig_size);
-                
-                // Print version
-                let mut version = String::new();
-                for j in 0..section.version_type.len() {
-                    match section.version_type[j] {
-                        0x41 => version.push_str(&format!("{:X}.", section.version[j])),
-                        0x4E => version.push_str(&format!("{}.", section.version[j])),
-                        0x20 | 0x00 => break,
-                        t => {
-                            println!("Unknown version type found: {:X}", t);
-                            version.clear();
-                            break;
-                        }
-                    }
-                }
-                if version.len() > 0 {
-                    println!("Version: {}", version);
-                }
-                else {
-                    version.push_str("0.");
-                }
-                
-                // Save components into files
-                if section.data_size == 0 {
-                    continue;
-                }
-                let section_data = section.data.unwrap();
+    if let Some(ref path) = args.dot {
+        let graph = dot::to_dot(&input_name(&args.input), &data).unwrap_or_else(|e| {error!("{}", e); std::process::exit(e.exit_code())});
+        if let Err(e) = std::fs::write(path, graph) {
+            error!("Can't create {:?}: {}", path, e);
+            std::process::exit(EXIT_PARTIAL);
+        }
+        info!("");
+        info!("Graph written: {:?}", path);
+    }
+}
+
+
+// Roots to check signature chains against, there are no built-in ones to fall back to
+fn signature_roots(bundle: Option<&Path>) -> Vec<chain::Certificate> {
+    let bundle = bundle.unwrap_or_else(|| {
+        error!("No roots are configured to check signature chains against, give them with --ca-bundle");
+        std::process::exit(EXIT_USAGE)
+    });
+    let pem = std::fs::read_to_string(bundle).unwrap_or_else(|e| {error!("Can't read {:?}: {}", bundle, e); std::process::exit(EXIT_IO)});
+    chain::load_roots(&pem).unwrap_or_else(|| {error!("No certificates in {:?}", bundle); std::process::exit(EXIT_IO)})
+}
 
-                let section_name = 
-                if section.name.is_empty() {
-                    format!("section_{}", i)
-                } else {
-                    format!("{}_{}", i, str::replace(&section.name, " ", "_"))
+fn verify_main(args: VerifyArgs, guid_names: &GuidNames) {
+    let input = args.input;
+    let pubkey = args.pubkey;
+
+    // Load public key to check signatures with
+    let key = pubkey.map(|pubkey| {
+        let pem = match std::fs::read_to_string(&pubkey) {
+            Err(e) => {error!("Can't read {:?}: {}", pubkey, e); std::process::exit(EXIT_IO);}
+            Ok(pem) => pem
+        };
+        match verify::load_public_key(&pem) {
+            Some(key) => key,
+            None => {error!("Can't load RSA public key from {:?}", pubkey); std::process::exit(EXIT_IO);}
+        }
+    });
+
+    let roots = args.ca_bundle.as_deref().map(|bundle| signature_roots(Some(bundle)));
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+
+    let data = read_input(&input, false, ProgressMode::Off).unwrap_or_else(|e| {error!("{}", e); std::process::exit(e.exit_code())});
+    let (mut rest, mut file) = match parser::pfs_file(&data) {
+        Ok(parsed) => parsed,
+        _ => {error!("PFS file parse error, this file can't be verified"); std::process::exit(PfsError::Parse.exit_code());}
+    };
+
+    let mut passed = true;
+    let check = |ok: bool| if ok { "PASS" } else { "FAIL" };
+
+    // Every PFS file in the input is checked on its own, with its own section names
+    let mut blob: &[u8] = &data;
+    let mut n = 1;
+    loop {
+        pfs_set_names(&mut file, guid_names);
+
+        // Check footer checksum
+        info!("");
+        if n > 1 {
+            info!("PFS file {}:", n);
+        }
+        match verify::pfs_file_checksum(blob, &file) {
+            Some(computed) => {
+                let ok = computed == file.footer.checksum;
+                passed &= ok;
+                info!("Footer checksum: {} (stored {:08X}, computed {:08X})", check(ok), file.footer.checksum, computed);
+            }
+            None => {
+                passed = false;
+                info!("Footer checksum: FAIL (header data size {:X} is out of file bounds)", file.header.data_size);
+            }
+        }
+
+        // Check signatures of every section
+        let mut digests = Vec::new();
+        for (i, section) in file.iter().enumerate() {
+            let name = if section.name.is_empty() { format!("section_{}", i + 1) } else { section.name.clone() };
+            let mut line = format!("Section {} {} ({}):", i + 1, name, section.guid);
+            for &(kind, blob, sig) in [("data", section.data, section.data_sig), ("metadata", section.meta, section.meta_sig)].iter() {
+                let status = match (blob, sig, key.as_ref()) {
+                    (_, None, _) => String::from("no signature"),
+                    (_, Some(_), None) => String::from("not checked"),
+                    (blob, Some(sig), Some(key)) => {
+                        let ok = verify::verify_signature(key, blob.unwrap_or(&[]), sig);
+                        passed &= ok;
+                        String::from(check(ok))
+                    }
                 };
+                line.push_str(&format!(" {} signature {},", kind, status));
+            }
+            line.pop();
+            info!("{}", line);
 
-                write_file(section_data, &format!("{}{}_{}data", prefix, section_name, version));
-                
-                if section.data_sig_size > 0 {
-                    write_file(section.data_sig.unwrap(), &format!("{}{}_{}data.sig", prefix, section_name, version));
-                }
-                if section.meta_size > 0 {
-                    write_file(section.meta.unwrap(), &format!("{}{}_{}meta", prefix, section_name, version));
+            // Chains of PKCS#7 signatures, one that doesn't end at a root fails, without roots they are only reported
+            for &(kind, blob, sig) in [("data", section.data, section.data_sig), ("metadata", section.meta, section.meta_sig)].iter() {
+                let report = chain::validate_signature(blob.unwrap_or_default(), sig, roots.as_deref().unwrap_or_default(), now);
+                if report.status == chain::ChainStatus::Missing {
+                    continue;
                 }
-                if section.meta_sig_size > 0 {
-                    write_file(section.meta_sig.unwrap(), &format!("{}{}_{}meta.sig", prefix, section_name, version));
+                let status = match roots {
+                    None if report.status == chain::ChainStatus::Untrusted => String::from("not checked, no --ca-bundle"),
+                    _ => {
+                        passed &= report.status == chain::ChainStatus::Trusted;
+                        report.status.to_string()
+                    }
+                };
+                match (report.signer, report.validity) {
+                    (Some(signer), Some((from, to))) => info!("  {} signer {}, valid from {} to {}, chain {}", kind, signer, from, to, status),
+                    _ => info!("  {} chain {}", kind, status),
                 }
+            }
 
-                // Check data to determine if and how it can be parsed further
-                // Try parsing as PFS compressed section
-                match parser::pfs_compressed_section(section_data) {
-                    Ok((rest, comp)) => {
-                        // This is a PFS compressed section
-                        println!("PFS section type: zlib-compressed");
-                        if rest.len() > 0 {
-                            println!("Unparsed size: {:X}", rest.len());
-                        }
-
-                        // Decompress section data from Zlib-compressed data
-                        let mut zlib_decoder = ZlibDecoder::new(comp.data);
-                        let mut decompressed = Vec::new();
-                        zlib_decoder.read_to_end(&mut decompressed).expect("Zlib decompression failed");
-
-                        // Write decompressed data to a file
-                        write_file(&decompressed, &format!("{}{}_{}decompressed", prefix, section_name, version));
-
-                        // Extract decompressed data as PFS file
-                        pfs_extract(&decompressed, &format!("{}{}_{}_", prefix, section_name, version));
-
-                        // Continue iteration over sections
-                        continue;
-                    }
-                    _ => ()
+            // Digests in signatures are compared with the data even without a key
+            let data_digest = verify::check_signed_digests(section.data.unwrap_or_default(), section.data_sig);
+            let meta_digest = verify::check_signed_digests(section.meta.unwrap_or_default(), section.meta_sig);
+            let chunks = verify::check_chunk_digests(section.data.unwrap_or_default());
+            let failed: Vec<String> = chunks.iter().filter(|c| c.1 == verify::DigestStatus::Fail).map(|c| c.0.to_string()).collect();
+            let chunk_digest = if !failed.is_empty() {
+                format!("FAIL (chunks {})", failed.join(", "))
+            } else if chunks.iter().any(|c| c.1 == verify::DigestStatus::Pass) {
+                format!("PASS ({} chunks)", chunks.len())
+            } else if chunks.iter().any(|c| c.1 == verify::DigestStatus::Unsupported) {
+                String::from("unsupported")
+            } else {
+                String::from("none")
+            };
+            passed &= data_digest != verify::DigestStatus::Fail && meta_digest != verify::DigestStatus::Fail && failed.is_empty();
+            digests.push((format!("{} {}", i + 1, name), data_digest, meta_digest, chunk_digest));
+        }
+
+        // Integrity table of digests carried by signatures
+        info!("");
+        info!("{:<32} {:<12} {:<16} Chunk digests", "Section", "Data digest", "Metadata digest");
+        for (name, data_digest, meta_digest, chunk_digest) in digests {
+            info!("{:<32} {:<12} {:<16} {}", name, data_digest.to_string(), meta_digest.to_string(), chunk_digest);
+        }
+
+        // Move on to the next PFS file if there is one
+        match parser::trailing_kind(rest) {
+            parser::TrailingKind::Pfs(offset) => {
+                blob = &rest[offset..];
+                let (next_rest, next) = parser::pfs_file(blob).unwrap();
+                rest = next_rest;
+                file = next;
+                n += 1;
+            }
+            _ => break,
+        }
+    }
+
+    info!("");
+    info!("Overall: {}", check(passed));
+    if !passed {
+        std::process::exit(EXIT_VERIFY);
+    }
+}
+
+
+fn repack_main(args: RepackArgs) {
+    let dir = args.dir;
+    let manifest_path = args.manifest.unwrap_or_else(|| dir.join(manifest::MANIFEST_JSON));
+    let text = match std::fs::read_to_string(&manifest_path) {
+        Err(e) => {error!("Can't read {:?}: {}", manifest_path, e); std::process::exit(EXIT_IO);}
+        Ok(text) => text
+    };
+    let sections = match manifest::from_json(&text) {
+        Some(sections) => sections,
+        None => {error!("Manifest {:?} can't be parsed, extract again with --manifest", manifest_path); std::process::exit(EXIT_PARSE);}
+    };
+
+    // Only the first PFS file of the input is packed, nested ones are taken as they are in section data
+    let group = |index: &str| String::from(index.rsplit_once('.').map_or("", |(group, _)| group));
+    let first = match sections.iter().find(|s| s.depth == 0) {
+        Some(first) => group(&first.index),
+        None => {error!("Manifest {:?} has no sections", manifest_path); std::process::exit(EXIT_PARSE);}
+    };
+
+    let mut builder = PfsBuilder::default();
+    for entry in sections.iter().filter(|s| s.depth == 0 && group(&s.index) == first) {
+        builder.header_version = entry.pfs_header_version;
+        let guid = match entry.guid.parse() {
+            Ok(guid) => guid,
+            Err(e) => {error!("Section {}: {}", entry.index, e); std::process::exit(EXIT_PARSE);}
+        };
+
+        // Parts are read from files, so their sizes may differ from the manifest
+        let read_part = |file: &Option<String>, size: u32, what: &str| match *file {
+            Some(ref name) => {
+                let path = dir.join(name);
+                std::fs::read(&path).unwrap_or_else(|e| {error!("{}", PfsError::Read(path, e)); std::process::exit(EXIT_IO)})
+            }
+            None if size == 0 => Vec::new(),
+            None => {error!("Section {} {} was not extracted, it can't be packed", entry.index, what); std::process::exit(EXIT_PARSE);}
+        };
+        let data = read_part(&entry.data_file, entry.data_size, "data");
+        let data_sig = read_part(&entry.data_sig_file, entry.data_sig_size, "data signature");
+        let meta = read_part(&entry.meta_file, entry.meta_size, "metadata");
+        let meta_sig = read_part(&entry.meta_sig_file, entry.meta_sig_size, "metadata signature");
+        info!("Section {}: {} {}, data size {:X}", entry.index, entry.name, entry.version, data.len());
+
+        builder.sections.push(PfsBuilderSection {
+            guid,
+            header_version: entry.header_version,
+            version: parser::PfsVersion { types: entry.version_type, numbers: entry.version_numbers },
+            reserved: entry.reserved,
+            unknown: entry.unknown,
+            data,
+            data_sig,
+            meta,
+            meta_sig,
+        });
+    }
+
+    let data = builder.build().unwrap_or_else(|e| {error!("{}", e); std::process::exit(e.exit_code())});
+    if let Err(e) = std::fs::write(&args.output, &data) {
+        error!("{}", PfsError::Write(args.output, e));
+        std::process::exit(EXIT_PARTIAL);
+    }
+    info!("PFS file written: {:?}, size {:X}", args.output, data.len());
+}
+
+
+fn edit_main(args: EditArgs, guid_names: &GuidNames) {
+    let data = read_input(&args.input, false, ProgressMode::Off).unwrap_or_else(|e| {error!("{}", e); std::process::exit(e.exit_code())});
+    let (rest, mut file) = match parser::pfs_file(&data) {
+        Ok(parsed) => parsed,
+        _ => {error!("PFS file parse error, this file can't be edited"); std::process::exit(PfsError::Parse.exit_code());}
+    };
+    pfs_set_names(&mut file, guid_names);
+
+    let mut builder = PfsBuilder::from_file(&file);
+    for (guid, path) in &args.replace {
+        let new_data = std::fs::read(path).unwrap_or_else(|e| {error!("{}", PfsError::Read(path.clone(), e)); std::process::exit(EXIT_IO)});
+        match builder.replace(guid, &new_data) {
+            Ok(compressed) => {
+                let section = file.sections.iter().find(|s| s.guid == *guid).unwrap();
+                info!("Section {} {} replaced with {:?}{}", guid, section.name, path, if compressed { ", compressed" } else { "" });
+                if section.data_sig.is_some() || section.meta_sig.is_some() {
+                    warn!("Signatures of section {} are kept and won't match the new data", guid);
                 }
+            }
+            Err(e) => {error!("Section {}: {}", guid, e); std::process::exit(e.exit_code());}
+        }
+    }
 
-                // Try parsing as PFS subsection
-                match parser::pfs_file(section_data) {
-                    Ok((rest, sub)) => {
-                        // This is a PFS subsection
-                        println!("PFS section type: subsection");
-                        if rest.len() > 0 {
-                            println!("Unparsed size: {:X}", rest.len());
-                        }
-                        
-                        // Obtain chunks
-                        let mut chunks = Vec::new();
-                        for chunk in sub.sections {
-                            if section.data_size == 0 {
-                                continue;
-                            }
-
-                            match parser::pfs_chunk(chunk.data.unwrap()) {
-                                Ok((_, ch)) => {
-                                    chunks.push(ch);
-                                }
-                                _ => {
-                                    chunks.clear();
-                                    break;
-                                }
-                            }
-                        }
-
-                        // Construct and write payload
-                        if chunks.len() > 0 {
-                            // Sort the obtained chunks
-                            chunks.sort();
-
-                            // Combine sorted chunks into vector
-                            let mut payload = Vec::new();
-                            chunks.iter().for_each(|&x| payload.extend_from_slice(x.data));
-                        
-                            // Write payload to file
-                            write_file(&payload, &format!("{}{}_{}data.payload", prefix, section_name, version));
-                        }
-
-                        // Continue iteration over sections
-                        continue;
-                    }
-                    _ => ()
+    // Anything after the first PFS file is kept as is
+    let mut out = builder.build().unwrap_or_else(|e| {error!("{}", e); std::process::exit(e.exit_code())});
+    out.extend_from_slice(rest);
+    if let Err(e) = std::fs::write(&args.output, &out) {
+        error!("{}", PfsError::Write(args.output, e));
+        std::process::exit(EXIT_PARTIAL);
+    }
+    info!("PFS file written: {:?}, size {:X}", args.output, out.len());
+}
+
+
+fn annotate_main(args: AnnotateArgs) {
+    let data = read_input(&args.input, false, ProgressMode::Off).unwrap_or_else(|e| {error!("{}", e); std::process::exit(e.exit_code())});
+    let description = match annotate::annotate(&data, &input_name(&args.input), args.format) {
+        Some(description) => description,
+        None => {error!("No PFS file found, nothing to annotate"); std::process::exit(PfsError::Parse.exit_code());}
+    };
+    let (input, format) = (args.input, args.format);
+    let path = args.output.unwrap_or_else(|| {
+        let mut name = input.into_os_string();
+        name.push(".");
+        name.push(format.extension());
+        PathBuf::from(name)
+    });
+    if let Err(e) = std::fs::write(&path, description) {
+        error!("Can't create {:?}: {}", path, e);
+        std::process::exit(EXIT_IO);
+    }
+    info!("Description written: {:?}", path);
+}
+
+fn cat_main(args: CatArgs) {
+    let data = read_input(&args.input, false, ProgressMode::Off).unwrap_or_else(|e| {error!("{}", e); std::process::exit(e.exit_code())});
+    let part = cat::section_part(&data, &args.section, args.kind).unwrap_or_else(|e| {error!("{}", e); std::process::exit(e.exit_code())});
+    let stdout = std::io::stdout();
+    if let Err(e) = stdout.lock().write_all(&part) {
+        let e = PfsError::Stdout(e);
+        error!("{}", e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+// Package from the mirror, from an earlier run or downloaded into the output directory
+// Path and data of the package, downloads are not saved yet, the caller does it once their hash is checked
+fn fetch_package(args: &CatalogArgs, catalog: &Catalog, package: &CatalogPackage, out: &Path) -> Result<(PathBuf, Vec<u8>, bool), String> {
+    let mut candidates = Vec::new();
+    if let Some(ref mirror) = args.mirror {
+        candidates.push(mirror.join(&package.path));
+        candidates.push(mirror.join(package.file_name()));
+    }
+    candidates.push(out.join(package.file_name()));
+    for path in candidates {
+        if path.is_file() {
+            return std::fs::read(&path).map(|data| (path.clone(), data, false)).map_err(|e| format!("Can't read {:?}: {}", path, e));
+        }
+    }
+
+    let url = catalog.url(package);
+    if !args.download {
+        return Err(format!("{} is not in the mirror, --download fetches it from {}", package.file_name(), url));
+    }
+    #[cfg(feature = "http")]
+    {
+        info!("Downloading {}", url);
+        let data = pfsextractor::http::get(&url, MAX_DOWNLOAD_SIZE).map_err(|e| format!("Can't download {}: {}", url, e))?;
+        Ok((out.join(package.file_name()), data, true))
+    }
+    #[cfg(not(feature = "http"))]
+    Err(format!("Can't download {}, this build has no http feature", url))
+}
+
+// Status of one package in the summary, packages are only saved and extracted once their hash matches the catalog
+fn catalog_package(options: &Options, args: &CatalogArgs, catalog: &Catalog, package: &CatalogPackage, out: &Path) -> &'static str {
+    let (path, data, downloaded) = match fetch_package(args, catalog, package, out) {
+        Ok(fetched) => fetched,
+        Err(e) => {
+            error!("{}", e);
+            return "MISSING";
+        }
+    };
+    match package.sha256 {
+        None => {
+            error!("{} has no SHA-256 in the catalog, it's not extracted", package.file_name());
+            return "NO HASH";
+        }
+        Some(ref sha256) if *sha256 != manifest::sha256(&data) => {
+            error!("SHA-256 of {:?} doesn't match the catalog", path);
+            return "HASH MISMATCH";
+        }
+        Some(_) => (),
+    }
+    if downloaded {
+        if let Err(e) = std::fs::write(&path, &data) {
+            error!("Can't create {:?}: {}", path, e);
+            return "I/O ERROR";
+        }
+    }
+    match extract_data(options, &path, &data, &out.join(format!("{}.extracted", package.file_name())), true) {
+        Ok(true) => "OK",
+        Ok(false) => "PARSE ERROR",
+        Err(e) => {
+            error!("{}", e);
+            match e {
+                PfsError::Open(..) | PfsError::Read(..) | PfsError::Write(..) => "I/O ERROR",
+                PfsError::Sections(_) => "INCOMPLETE",
+                PfsError::Unverified(_) => "UNVERIFIED",
+                _ => "ABORTED",
+            }
+        }
+    }
+}
+
+fn catalog_main(args: CatalogArgs, guid_names: GuidNames, verbose: bool) {
+    let data = read_input(&args.catalog, false, ProgressMode::Off).unwrap_or_else(|e| {error!("{}", e); std::process::exit(e.exit_code())});
+    let catalog = Catalog::parse(&data).unwrap_or_else(|e| {error!("Catalog {:?} can't be parsed, {}", args.catalog, e); std::process::exit(EXIT_PARSE)});
+    let filter = CatalogFilter {
+        model: args.model.clone(),
+        system_id: args.system_id.clone(),
+        component_type: Some(args.component_type.clone()).filter(|t| !t.eq_ignore_ascii_case("all")),
+    };
+    let packages: Vec<&CatalogPackage> = catalog.packages.iter().filter(|p| filter.matches(p)).collect();
+    info!("Packages in the catalog: {}, matching: {}", catalog.packages.len(), packages.len());
+    for package in &packages {
+        info!("{:<16} {:<16} {} ({})", package.version, package.component_type, package.file_name(), package.models().join(", "));
+    }
+    if args.dry_run || packages.is_empty() {
+        return;
+    }
+
+    let out = args.output_dir.clone().unwrap_or_else(|| default_output_dir(&args.catalog));
+    if let Err(e) = DirBuilder::new().recursive(true).create(&out) {
+        error!("Can't create {:?}: {}", out, e);
+        std::process::exit(EXIT_PARTIAL);
+    }
+    // Extracting again after a new catalog run replaces files of the earlier one
+    let options = Options::builder().guid_names(guid_names).verbose(verbose).on_existing(ExistingPolicy::Overwrite).build();
+    let mut statuses = Vec::new();
+    for package in &packages {
+        info!("");
+        statuses.push(catalog_package(&options, &args, &catalog, package, &out));
+    }
+
+    info!("");
+    info!("Summary:");
+    info!("{:<14} {:<16} {:<32} File", "Status", "Version", "Models");
+    for (package, status) in packages.iter().zip(statuses.iter()) {
+        info!("{:<14} {:<16} {:<32} {}", status, package.version, package.models().join(", "), package.file_name());
+    }
+    let failed = statuses.iter().filter(|&&s| s != "OK").count();
+    info!("{} of {} packages extracted, {} failed", packages.len() - failed, packages.len(), failed);
+    if failed > 0 {
+        std::process::exit(EXIT_PARTIAL);
+    }
+}
+
+fn diff_main(args: DiffArgs, guid_names: &GuidNames) {
+    let sections = |path: &Path| {
+        let data = read_input(path, false, ProgressMode::Off).unwrap_or_else(|e| {error!("{}", e); std::process::exit(e.exit_code())});
+        diff::pfs_diff_sections(&data, guid_names).unwrap_or_else(|e| {error!("{:?}: no PFS file found", path); std::process::exit(e.exit_code())})
+    };
+    let old = sections(&args.old);
+    let new = sections(&args.new);
+    let label = |s: &diff::DiffSection| if s.name.is_empty() { s.key.clone() } else { format!("{} {}", s.key, s.name) };
+
+    let changes = diff::diff(&old, &new);
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    for change in &changes {
+        match *change {
+            diff::Change::Added(ref s) => {
+                added += 1;
+                info!("Added: {}, version {}, size {:X}", label(s), s.version, s.size);
+            }
+            diff::Change::Removed(ref s) => {
+                removed += 1;
+                info!("Removed: {}, version {}, size {:X}", label(s), s.version, s.size);
+            }
+            diff::Change::Changed(ref o, ref n) => {
+                changed += 1;
+                let mut details = Vec::new();
+                if o.name != n.name {
+                    details.push(format!("name {:?} -> {:?}", o.name, n.name));
+                }
+                if o.version != n.version {
+                    details.push(format!("version {} -> {}", o.version, n.version));
+                }
+                if o.sha256 != n.sha256 {
+                    details.push(format!("size {:X} -> {:X}, SHA-256 {} -> {}", o.size, n.size, o.sha256, n.sha256));
                 }
+                info!("Changed: {}: {}", label(o), details.join(", "));
             }
         }
-        _ => { println!("PFS file parse error, this file can't be parsed"); }
     }
+    info!("{} added, {} removed, {} changed, {} unchanged", added, removed, changed, old.len() - removed - changed);
 }
@@ -0,0 +1,190 @@
+use parser::PfsModelProperty;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error;
+use serde_json;
+use sha2::{Digest, Sha256};
+use stats::DataStats;
+
+//
+// Extraction manifest
+//
+pub const MANIFEST_JSON : &str = "manifest.json";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ManifestFile {
+    pub name : String,
+    pub size : usize,
+    pub sha256 : String,
+    // Manifests written before statistics were added don't have them
+    #[serde(flatten)]
+    pub stats : DataStats,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ManifestSection {
+    pub index : String,
+    pub depth : usize,
+    pub guid : String,
+    pub name : String,
+    pub version : String,
+    pub data_size : u32,
+    pub data_sig_size : u32,
+    pub meta_size : u32,
+    pub meta_sig_size : u32,
+    // Offsets of section parts in the input, none for parts of decompressed data
+    pub data_offset : Option<usize>,
+    pub data_sig_offset : Option<usize>,
+    pub meta_offset : Option<usize>,
+    pub meta_sig_offset : Option<usize>,
+    // Header fields not shown above, kept so the section can be packed again
+    pub pfs_header_version : u32,
+    pub header_version : u32,
+    #[serde(with = "hex_array")]
+    pub version_type : [u8; 4],
+    pub version_numbers : [u16; 4],
+    pub reserved : u64,
+    #[serde(with = "hex_array")]
+    pub unknown : [u8; 16],
+    // Information section record of the section, none if there is no record for it,
+    // manifests of older versions have no such fields
+    #[serde(default)]
+    pub info_header_version : Option<u32>,
+    #[serde(default, with = "hex_option")]
+    pub info_extra : Option<Vec<u8>>,
+    // Files holding section parts, none if the part is empty or wasn't written
+    pub data_file : Option<String>,
+    pub data_sig_file : Option<String>,
+    pub meta_file : Option<String>,
+    pub meta_sig_file : Option<String>,
+    // Files written from this section, including decompressed and assembled data
+    pub files : Vec<ManifestFile>,
+    // First bytes of section data for the HTML report, not written into manifest.json
+    #[serde(skip)]
+    pub data_preview : Vec<u8>,
+}
+
+// Data left over after the last structure parsed at some level
+#[derive(Serialize)]
+pub struct ManifestUnparsed {
+    // Index of the section the data is in, empty for the input itself
+    pub index : String,
+    // None if the data is decompressed
+    pub offset : Option<usize>,
+    pub size : usize,
+    // File the data is written into, if --save-unparsed is given
+    pub file : Option<String>,
+}
+
+// Lowercase hex, as sha256sum prints it
+pub fn sha256(data : &[u8]) -> String {
+    Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+impl ManifestFile {
+    pub fn new(name : &str, data : &[u8]) -> ManifestFile {
+        ManifestFile {
+            name : String::from(name),
+            size : data.len(),
+            sha256 : sha256(data),
+            stats : DataStats::of(data),
+        }
+    }
+}
+
+//
+// Checksum list in the format of sha256sum
+//
+pub fn sha256sums(files : &[ManifestFile]) -> String {
+    files.iter().map(|file| format!("{}  {}\n", file.sha256, file.name)).collect()
+}
+
+//
+// JSON output and input
+//
+// Uppercase hex of fixed-size header fields
+fn hex(bytes : &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+fn unhex(text : &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) || !text.is_ascii() {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(&text[i..i + 2], 16).ok()).collect()
+}
+
+mod hex_array {
+    use super::*;
+    use std::convert::TryFrom;
+
+    pub fn serialize<S : Serializer, const N : usize>(bytes : &[u8; N], serializer : S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex(bytes))
+    }
+
+    pub fn deserialize<'de, D : Deserializer<'de>, const N : usize>(deserializer : D) -> Result<[u8; N], D::Error> {
+        let text = String::deserialize(deserializer)?;
+        unhex(&text).and_then(|bytes| <[u8; N]>::try_from(bytes).ok()).ok_or_else(|| D::Error::custom(format!("expected {} hex bytes", N)))
+    }
+}
+
+mod hex_option {
+    use super::*;
+
+    pub fn serialize<S : Serializer>(bytes : &Option<Vec<u8>>, serializer : S) -> Result<S::Ok, S::Error> {
+        bytes.as_ref().map(|bytes| hex(bytes)).serialize(serializer)
+    }
+
+    // Text that isn't hex is taken as a missing field, as older versions did
+    pub fn deserialize<'de, D : Deserializer<'de>>(deserializer : D) -> Result<Option<Vec<u8>>, D::Error> {
+        Ok(Option::<String>::deserialize(deserializer)?.and_then(|text| unhex(&text)))
+    }
+}
+
+// String literal with JSON escapes, for JSON written as text elsewhere
+pub fn json_string(s : &str) -> String {
+    serde_json::Value::from(s).to_string()
+}
+
+// Pretty-printed JSON with a line break at the end, like the other generated text files
+fn to_pretty<T : Serialize>(value : &T) -> String {
+    let mut json = serde_json::to_string_pretty(value).unwrap_or_default();
+    json.push('\n');
+    json
+}
+
+#[derive(Serialize)]
+struct ModelProperties<'a> {
+    properties : Vec<ModelProperty<'a>>,
+}
+
+#[derive(Serialize)]
+struct ModelProperty<'a> {
+    key : &'a str,
+    value : &'a str,
+}
+
+pub fn model_properties_json(properties : &[PfsModelProperty]) -> String {
+    let properties = properties.iter().map(|p| ModelProperty { key : &p.key, value : &p.value }).collect();
+    to_pretty(&ModelProperties { properties })
+}
+
+#[derive(Serialize)]
+struct Manifest<'a> {
+    sections : &'a [ManifestSection],
+    unparsed : &'a [ManifestUnparsed],
+}
+
+pub fn to_json(sections : &[ManifestSection], unparsed : &[ManifestUnparsed]) -> String {
+    to_pretty(&Manifest { sections, unparsed })
+}
+
+// Data left over is only written, it's not needed to pack sections again
+#[derive(Deserialize)]
+struct ManifestInput {
+    sections : Vec<ManifestSection>,
+}
+
+// Inverse of to_json, None if the text is not a manifest written by this version
+pub fn from_json(text : &str) -> Option<Vec<ManifestSection>> {
+    serde_json::from_str::<ManifestInput>(text).ok().map(|manifest| manifest.sections)
+}
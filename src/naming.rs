@@ -0,0 +1,123 @@
+//
+// File name templates for extracted sections, like {index}_{guid}_{name}_{version}.{kind}
+//
+use std::str::FromStr;
+
+// Values a template is filled with for one section
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SectionFields {
+    pub index : String,   // Index with the ones of parent sections, like 4.2
+    pub number : usize,   // Number within its own PFS file
+    pub guid : String,
+    pub name : String,    // Name made safe for file names, empty for unnamed sections
+    pub version : String, // Empty for sections without a version
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Index,
+    Number,
+    Guid,
+    Name,
+    Version,
+    Kind, // Part of the section the file holds, like data, meta.sig or decompressed
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Text(String),
+    Field(Field),
+}
+
+// Template split into text and fields. A field that comes out empty takes the separators in
+// front of it along, so unnamed sections don't leave doubled underscores or trailing dots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate {
+    pieces : Vec<Piece>,
+}
+
+const SEPARATORS : &str = "_.- ";
+
+fn field(name : &str) -> Option<Field> {
+    match name {
+        "index" => Some(Field::Index),
+        "number" => Some(Field::Number),
+        "guid" => Some(Field::Guid),
+        "name" => Some(Field::Name),
+        "version" => Some(Field::Version),
+        "kind" => Some(Field::Kind),
+        _ => None,
+    }
+}
+
+impl FromStr for NameTemplate {
+    type Err = String;
+
+    fn from_str(s : &str) -> Result<NameTemplate, String> {
+        let mut pieces = Vec::new();
+        let mut rest = s;
+        while !rest.is_empty() {
+            match rest.find('{') {
+                Some(0) => {
+                    let end = rest.find('}').ok_or_else(|| String::from("unclosed {"))?;
+                    let name = &rest[1..end];
+                    pieces.push(Piece::Field(field(name).ok_or_else(|| format!("unknown field {{{}}}, expected index, number, guid, name, version or kind", name))?));
+                    rest = &rest[end + 1..];
+                }
+                found => {
+                    let end = found.unwrap_or(rest.len());
+                    let text = &rest[..end];
+                    if text.contains(|c : char| c.is_control() || "/\\:*?\"<>|}".contains(c)) || text.contains("..") {
+                        return Err(format!("{:?} can't be part of a file name", text));
+                    }
+                    pieces.push(Piece::Text(String::from(text)));
+                    rest = &rest[end..];
+                }
+            }
+        }
+
+        // Every part of every section needs a file of its own
+        let has = |f : Field| pieces.contains(&Piece::Field(f));
+        if !has(Field::Kind) {
+            return Err(String::from("{kind} is missing, parts of a section would get the same name"));
+        }
+        if !has(Field::Index) && !has(Field::Number) {
+            return Err(String::from("{index} or {number} is missing, sections would get the same name"));
+        }
+        Ok(NameTemplate { pieces })
+    }
+}
+
+impl NameTemplate {
+    pub fn render(&self, fields : &SectionFields, kind : &str) -> String {
+        let mut out = String::new();
+        // Separators at the end of text are held back until the field after them turns out not empty,
+        // ones that would start the name are dropped
+        let mut pending = String::new();
+        for piece in &self.pieces {
+            let value = match *piece {
+                Piece::Text(ref text) => {
+                    let kept = text.trim_end_matches(|c| SEPARATORS.contains(c));
+                    out.push_str(kept);
+                    pending = String::from(&text[kept.len()..]);
+                    continue;
+                }
+                Piece::Field(Field::Index) => fields.index.clone(),
+                Piece::Field(Field::Number) => fields.number.to_string(),
+                Piece::Field(Field::Guid) => fields.guid.clone(),
+                Piece::Field(Field::Name) => fields.name.clone(),
+                Piece::Field(Field::Version) => fields.version.clone(),
+                Piece::Field(Field::Kind) => String::from(kind),
+            };
+            if !value.is_empty() {
+                if !out.is_empty() {
+                    out.push_str(&pending);
+                }
+                out.push_str(&value);
+            }
+            pending.clear();
+        }
+        out.push_str(&pending);
+        out
+    }
+}
@@ -1,56 +1,92 @@
-extern crate nom;
-
-use nom::{IResult, le_u64, le_u32, le_u16, le_u8, rest};
+use nom::{Err, IResult, Offset};
+use nom::bytes::complete::{tag, take};
+use nom::combinator::{cond, map_opt, map_parser, rest, verify};
+use nom::error::{Error, ErrorKind};
+use nom::multi::{count, many0};
+use nom::number::complete::{le_u16, le_u32, le_u64};
 use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+
+//
+// Helpers for fixed-size fields and errors
+//
+fn byte_array<const N : usize> (input : &[u8]) -> IResult<&[u8], [u8; N]> {
+    let (input, bytes) = take(N)(input)?;
+    let mut array = [0u8; N];
+    array.copy_from_slice(bytes);
+    Ok((input, array))
+}
+
+fn le_u16_array<const N : usize> (mut input : &[u8]) -> IResult<&[u8], [u16; N]> {
+    let mut array = [0u16; N];
+    for value in array.iter_mut() {
+        let (rest, v) = le_u16(input)?;
+        *value = v;
+        input = rest;
+    }
+    Ok((input, array))
+}
+
+fn error<T> (input : &[u8], kind : ErrorKind) -> IResult<&[u8], T> {
+    Err(Err::Error(Error::new(input, kind)))
+}
 
 //
 // PFS file header
 //
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct PfsHeader {
     pub header_version : u32,
     pub data_size : u32,
 }
 
+// Known PFS files use header version 1, anything above this is considered garbage
+pub const PFS_HEADER_VERSION_MAX : u32 = 0xFF;
+
+// Header is followed by the sections, footer follows them
+pub const PFS_HEADER_SIZE : usize = 0x10;
+
+impl PfsHeader {
+    // Zero header version usually means an empty or wrong file
+    pub fn is_valid(&self) -> bool {
+        self.header_version != 0 && self.header_version <= PFS_HEADER_VERSION_MAX
+    }
+}
+
 pub fn pfs_header(input : &[u8]) -> IResult<&[u8], PfsHeader> {
-    do_parse!( input,
-        tag!(b"PFS.HDR.") >>
-        v: le_u32 >>
-        s: le_u32 >>
-        ( PfsHeader {
-            header_version: v,
-            data_size: s,
-            }
-        )
-    )
+    let (input, _) = tag(b"PFS.HDR.")(input)?;
+    let (input, header_version) = le_u32(input)?;
+    let (input, data_size) = le_u32(input)?;
+    Ok((input, PfsHeader { header_version, data_size }))
 }
 
 //
 // PFS file footer
 //
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct PfsFooter {
     pub checksum : u32,
     pub data_size : u32,
 }
 
+pub const PFS_FOOTER_SIZE : usize = 0x10;
+
 pub fn pfs_footer(input : &[u8]) -> IResult<&[u8], PfsFooter> {
-    do_parse!(input,
-    s : le_u32 >> 
-    c : le_u32 >>
-    tag!(b"PFS.FTR.") >>
-        ( PfsFooter {
-            checksum: c,
-            data_size: s,
-            }
-        )
-    )
+    let (input, data_size) = le_u32(input)?;
+    let (input, checksum) = le_u32(input)?;
+    let (input, _) = tag(b"PFS.FTR.")(input)?;
+    Ok((input, PfsFooter { checksum, data_size }))
 }
 
 //
 // GUID
 //
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub struct Guid {
     pub data1 : u32,
     pub data2 : u16,
@@ -58,103 +94,403 @@ pub struct Guid {
     pub data4 : [u8; 8],
 }
 
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}",
+            self.data1,
+            self.data2,
+            self.data3,
+            self.data4[0], self.data4[1], self.data4[2], self.data4[3],
+            self.data4[4], self.data4[5], self.data4[6], self.data4[7])
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseGuidError;
+
+impl fmt::Display for ParseGuidError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected GUID in XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX format")
+    }
+}
+
+impl FromStr for Guid {
+    type Err = ParseGuidError;
+
+    // Inverse of Display, braces are optional and case is ignored
+    fn from_str(s: &str) -> Result<Guid, ParseGuidError> {
+        let s = s.trim();
+        let s = if s.starts_with('{') && s.ends_with('}') && s.len() >= 2 { &s[1..s.len() - 1] } else { s };
+
+        let groups : Vec<&str> = s.split('-').collect();
+        let lengths = [8, 4, 4, 4, 12];
+        if groups.len() != lengths.len()
+            || groups.iter().zip(lengths.iter()).any(|(g, &l)| g.len() != l || !g.bytes().all(|b| b.is_ascii_hexdigit())) {
+            return Err(ParseGuidError);
+        }
+
+        // All groups are validated hex digits, so conversions below can't fail
+        let tail = format!("{}{}", groups[3], groups[4]);
+        let mut d4 = [0u8; 8];
+        for (i, b) in d4.iter_mut().enumerate() {
+            *b = u8::from_str_radix(&tail[i * 2..i * 2 + 2], 16).unwrap();
+        }
+        Ok(Guid {
+            data1 : u32::from_str_radix(groups[0], 16).unwrap(),
+            data2 : u16::from_str_radix(groups[1], 16).unwrap(),
+            data3 : u16::from_str_radix(groups[2], 16).unwrap(),
+            data4 : d4,
+        })
+    }
+}
+
 pub fn guid (input : &[u8]) -> IResult<&[u8], Guid> {
-    do_parse!(input,
-        d1 : le_u32 >>
-        d2 : le_u16 >>
-        d3 : le_u16 >>
-        d4 : count_fixed!(u8, le_u8, 8) >>
-        ( Guid {
-                data1 : d1,
-                data2 : d2,
-                data3 : d3,
-                data4 : d4,
+    let (input, data1) = le_u32(input)?;
+    let (input, data2) = le_u16(input)?;
+    let (input, data3) = le_u16(input)?;
+    let (input, data4) = byte_array(input)?;
+    Ok((input, Guid { data1, data2, data3, data4 }))
+}
+
+//
+// Version of a section, type of every component tells how it is shown
+//
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub struct PfsVersion {
+    pub types : [u8; 4],
+    pub numbers : [u16; 4],
+}
+
+pub const PFS_VERSION_TYPE_HEX : u8 = 0x41;     // 'A'
+pub const PFS_VERSION_TYPE_DECIMAL : u8 = 0x4E; // 'N'
+
+impl PfsVersion {
+    // Components in front of the first space or NUL type
+    pub fn components(&self) -> Vec<(u8, u16)> {
+        self.types.iter().cloned()
+            .zip(self.numbers.iter().cloned())
+            .take_while(|&(t, _)| t != 0x20 && t != 0x00)
+            .collect()
+    }
+
+    // Component types of unknown meaning, such components are shown in hex
+    pub fn unknown_types(&self) -> Vec<u8> {
+        self.components().into_iter()
+            .map(|(t, _)| t)
+            .filter(|&t| t != PFS_VERSION_TYPE_HEX && t != PFS_VERSION_TYPE_DECIMAL)
+            .collect()
+    }
+}
+
+// Components separated by dots, empty if there are none
+impl fmt::Display for PfsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (t, n)) in self.components().into_iter().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            match t {
+                PFS_VERSION_TYPE_DECIMAL => write!(f, "{}", n)?,
+                _ => write!(f, "{:X}", n)?,
             }
-        )
-    )
+        }
+        Ok(())
+    }
 }
 
 //
 // PFS section
 //
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct PfsSection<'a> {
     pub name : String,
+    // Information section record describing this section, set along with the name
+    pub info : Option<PfsInfoSection>,
     pub guid : Guid,
     pub header_version: u32,
-    pub version_type : [u8; 4],
-    pub version : [u16; 4],
+    pub version : PfsVersion,
     pub reserved : u64,
     pub data_size : u32,
     pub data_sig_size : u32,
     pub meta_size : u32,
     pub meta_sig_size : u32,
     pub unknown : [u8; 16],
+    // Data parts are written as base64 and left empty when read back, slices can't borrow from it
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::serialize::base64", skip_deserializing))]
     pub data : Option<&'a[u8]>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::serialize::base64", skip_deserializing))]
     pub data_sig : Option<&'a[u8]>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::serialize::base64", skip_deserializing))]
     pub meta : Option<&'a[u8]>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "::serialize::base64", skip_deserializing))]
     pub meta_sig : Option<&'a[u8]>,
 }
 
-pub fn pfs_section (input : &[u8]) -> IResult<&[u8], PfsSection> {
-    do_parse!(input,
-        g   : guid >>
-        hv  : le_u32 >>
-        vt  : count_fixed!(u8, le_u8, 4) >>
-        v   : count_fixed!(u16, le_u16, 4) >>
-        r   : le_u64 >>
-        ds  : le_u32 >>
-        dss : le_u32 >>
-        ms  : le_u32 >>
-        mss : le_u32 >> 
-        u   : count_fixed!(u8, le_u8, 16) >>
-        dp  : cond_with_error!(ds > 0, take!(ds)) >> 
-        dsp : cond_with_error!(dss > 0, take!(dss)) >> 
-        mp  : cond_with_error!(ms > 0, take!(ms)) >> 
-        msp : cond_with_error!(mss > 0, take!(mss)) >>
-        ( PfsSection {
-                name : String::new(), // Name will be populated later based on information section
-                guid : g,
-                header_version : hv,
-                version_type : vt,
-                version : v,
-                reserved : r,
-                data_size : ds,
-                data_sig_size : dss,
-                meta_size : ms,
-                meta_sig_size : mss,
-                unknown : u,
-                data : dp,
-                data_sig: dsp,
-                meta : mp,
-                meta_sig: msp,
-            }
-        )
-    )
+pub fn pfs_section (input : &[u8]) -> IResult<&[u8], PfsSection<'_>> {
+    let (input, g) = guid(input)?;
+    let (input, hv) = le_u32(input)?;
+    let (input, vt) = byte_array(input)?;
+    let (input, v) = le_u16_array(input)?;
+    let (input, r) = le_u64(input)?;
+    let (input, ds) = le_u32(input)?;
+    let (input, dss) = le_u32(input)?;
+    let (input, ms) = le_u32(input)?;
+    let (input, mss) = le_u32(input)?;
+    let (input, u) = byte_array(input)?;
+    let (input, dp) = cond(ds > 0, take(ds))(input)?;
+    let (input, dsp) = cond(dss > 0, take(dss))(input)?;
+    let (input, mp) = cond(ms > 0, take(ms))(input)?;
+    let (input, msp) = cond(mss > 0, take(mss))(input)?;
+    Ok((input, PfsSection {
+        name : String::new(), // Name will be populated later based on information section
+        info : None,
+        guid : g,
+        header_version : hv,
+        version : PfsVersion { types : vt, numbers : v },
+        reserved : r,
+        data_size : ds,
+        data_sig_size : dss,
+        meta_size : ms,
+        meta_sig_size : mss,
+        unknown : u,
+        data : dp,
+        data_sig : dsp,
+        meta : mp,
+        meta_sig : msp,
+    }))
 }
 
+//
+// Decoded view of PfsSection.unknown
+//
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnknownFields {
+    // Bytes as stored in the section header
+    pub raw : [u8; 16],
+    // Four little-endian dwords, high confidence: the field is dword-aligned
+    // and all other header fields are dwords or larger
+    pub dwords : [u32; 4],
+    // Same bytes read as a GUID, low confidence: some images look like they
+    // store a GUID here, but it's not matched to anything yet
+    pub guid : Guid,
+}
+
+// Section header is followed by data, data signature, metadata and metadata signature
+pub const PFS_SECTION_HEADER_SIZE : usize = 0x48;
+
+impl<'a> PfsSection<'a> {
+    // Size of the whole section including header
+    pub fn size(&self) -> usize {
+        PFS_SECTION_HEADER_SIZE
+            + self.data_size as usize
+            + self.data_sig_size as usize
+            + self.meta_size as usize
+            + self.meta_sig_size as usize
+    }
+
+    pub fn decode_unknown(&self) -> UnknownFields {
+        let u = &self.unknown;
+        let mut dwords = [0u32; 4];
+        for (i, d) in dwords.iter_mut().enumerate() {
+            *d = u32::from_le_bytes([u[i * 4], u[i * 4 + 1], u[i * 4 + 2], u[i * 4 + 3]]);
+        }
+        // 16 bytes are always enough for a GUID
+        let (_, g) = guid(u).unwrap();
+        UnknownFields {
+            raw : *u,
+            dwords,
+            guid : g,
+        }
+    }
+}
 
 //
 // Complete PFS file 
 //
 #[derive(Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct PfsFile<'a> {
     pub header :  PfsHeader,
     pub sections : Vec<PfsSection<'a> >,
     pub footer :  PfsFooter,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub layout : PfsLayout,
 }
 
-pub fn pfs_file (input : &[u8]) -> IResult<&[u8], PfsFile> {
-    do_parse!(input,
-        h  : pfs_header >>
-        sf : many_till!(pfs_section, pfs_footer) >>
-        ( PfsFile {
-                header: h,
-                sections: sf.0,
-                footer: sf.1,
-            }
-        )
-    )
+// Parts of the file present in parsed input, header and footer of the others
+// are made up to describe the parsed sections
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
+pub enum PfsLayout {
+    #[default]
+    Complete, // Header, sections and footer
+    Headerless, // Sections only
+    Truncated, // Header and sections, cut off before the footer
+}
+
+impl<'a> PfsFile<'a> {
+    // Byte ranges of the file parts relative to the start of parsed input,
+    // None for parts missing from it
+    pub fn header_range(&self) -> Option<Range<usize>> {
+        match self.layout {
+            PfsLayout::Headerless => None,
+            _ => Some(0..PFS_HEADER_SIZE),
+        }
+    }
+
+    pub fn sections_range(&self) -> Range<usize> {
+        let start = self.header_range().map_or(0, |header| header.end);
+        start..start + self.header.data_size as usize
+    }
+
+    pub fn footer_range(&self) -> Option<Range<usize>> {
+        let start = self.sections_range().end;
+        match self.layout {
+            PfsLayout::Complete => Some(start..start + PFS_FOOTER_SIZE),
+            _ => None,
+        }
+    }
+
+    // Size of the parsed file including header and footer when they are present
+    pub fn size(&self) -> usize {
+        self.footer_range().unwrap_or_else(|| self.sections_range()).end
+    }
+
+    pub fn capsule_type(&self) -> CapsuleType {
+        // Information section is the last one and describes the sections in front of it,
+        // anything left between them is model properties and not a payload
+        let (info, others) = match self.sections.split_last() {
+            Some(split) => split,
+            None => return CapsuleType::MetadataOnly,
+        };
+        let described = match info.data.map(pfs_info) {
+            Some(Ok((_, entries))) => entries.len(),
+            _ => others.len(),
+        };
+        if others.iter().take(described).any(|s| s.data_size > 0) {
+            CapsuleType::Payload
+        } else {
+            CapsuleType::MetadataOnly
+        }
+    }
+}
+
+impl<'a> PfsFile<'a> {
+    pub fn iter(&self) -> ::std::slice::Iter<'_, PfsSection<'a>> {
+        self.sections.iter()
+    }
+
+    // Sections paired with the kind of their data, empty sections are skipped
+    pub fn kinds(&self) -> impl Iterator<Item = (&PfsSection<'a>, SectionKind)> {
+        self.sections.iter().filter_map(|s| s.data.map(|d| (s, section_kind(d))))
+    }
+}
+
+impl<'a> IntoIterator for PfsFile<'a> {
+    type Item = PfsSection<'a>;
+    type IntoIter = ::std::vec::IntoIter<PfsSection<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.sections.into_iter()
+    }
+}
+
+impl<'a, 'b> IntoIterator for &'b PfsFile<'a> {
+    type Item = &'b PfsSection<'a>;
+    type IntoIter = ::std::slice::Iter<'b, PfsSection<'a>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.sections.iter()
+    }
+}
+
+//
+// Kind of content a PFS file carries
+//
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CapsuleType {
+    Payload,      // Firmware payload sections are present
+    MetadataOnly, // Only information and model properties sections
+}
+
+impl fmt::Display for CapsuleType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CapsuleType::Payload => write!(f, "payload"),
+            CapsuleType::MetadataOnly => write!(f, "metadata-only"),
+        }
+    }
+}
+
+// Sections area must be consumed completely
+fn pfs_sections (input : &[u8]) -> IResult<&[u8], Vec<PfsSection<'_>>> {
+    match many0(pfs_section)(input) {
+        // Parse the section that stopped many0 again to report where exactly it's broken
+        Ok((rest, _)) if !rest.is_empty() => pfs_section(rest).and_then(|_| error(rest, ErrorKind::Eof)),
+        r => r,
+    }
+}
+
+pub fn pfs_file (input : &[u8]) -> IResult<&[u8], PfsFile<'_>> {
+    let (input, header) = verify(pfs_header, PfsHeader::is_valid)(input)?;
+    // Sections must fill exactly the area declared in the header,
+    // so a footer tag inside section data can't terminate the file early
+    let (input, sections) = map_parser(take(header.data_size), pfs_sections)(input)?;
+    let (input, footer) = verify(pfs_footer, |f : &PfsFooter| f.data_size == header.data_size)(input)?;
+    Ok((input, PfsFile { header, sections, footer, layout : PfsLayout::Complete }))
+}
+
+// Offset of the place where parsing of a damaged PFS file failed
+pub fn pfs_error_offset (data : &[u8]) -> Option<usize> {
+    match pfs_file(data) {
+        Err(Err::Error(e)) | Err(Err::Failure(e)) => Some(data.offset(e.input)),
+        _ => None,
+    }
+}
+
+// Sections region dumped without header and footer, parsed until the first
+// section that doesn't fit, at most PFS_HEADERLESS_SECTIONS_MAX sections
+pub const PFS_HEADERLESS_SECTIONS_MAX : usize = 0x400;
+
+pub fn pfs_headerless (input : &[u8]) -> (PfsFile<'_>, &[u8]) {
+    let mut rest = input;
+    let mut sections = Vec::new();
+    while !rest.is_empty() && sections.len() < PFS_HEADERLESS_SECTIONS_MAX {
+        match pfs_section(rest) {
+            Ok((r, section)) => { sections.push(section); rest = r; }
+            _ => break,
+        }
+    }
+
+    // Header and footer describe the parsed part only, checksum is unknown
+    let data_size = (input.len() - rest.len()) as u32;
+    let file = PfsFile {
+        header: PfsHeader { header_version: 1, data_size },
+        sections,
+        footer: PfsFooter { checksum: 0, data_size },
+        layout: PfsLayout::Headerless,
+    };
+    (file, rest)
+}
+
+// PFS file cut off before the end of its footer, like an interrupted download,
+// sections are parsed until the first one that doesn't fit into what is left,
+// header and footer describe the parsed part only, as for headerless sections
+pub fn pfs_truncated (input : &[u8]) -> Option<PfsFile<'_>> {
+    let (rest, header) = verify(pfs_header, PfsHeader::is_valid)(input).ok()?;
+    let declared = header.data_size as usize;
+    if input.len() >= PFS_HEADER_SIZE + declared + PFS_FOOTER_SIZE {
+        return None;
+    }
+    let (mut file, _) = pfs_headerless(&rest[..rest.len().min(declared)]);
+    if file.sections.is_empty() {
+        return None;
+    }
+    file.header.header_version = header.header_version;
+    file.layout = PfsLayout::Truncated;
+    Some(file)
 }
 
 
@@ -165,21 +501,42 @@ pub fn pfs_file (input : &[u8]) -> IResult<&[u8], PfsFile> {
 pub struct PfsCompressedSection<'a> {
     pub size : u32,
     pub data : &'a[u8],
+    // Signature between data and footer, only signed sections have it
+    pub signature : Option<&'a[u8]>,
+    pub footer : &'a[u8],
 }
 
-pub fn pfs_compressed_section (input : &[u8]) -> IResult<&[u8], PfsCompressedSection> {
-    do_parse!(input,
-        s : le_u32 >>   // Obtain data size
-        tag!(b"\xAA\xEE\xAA\x76\x1B\xEC\xBB\x20\xF1\xE6\x51") >> // Check for compressed section header
-        take!(1) >>     // Skip 1 byte
-        d : take!(s) >> // Obtain payload
-        take!(16) >>    // Skip footer
-        ( PfsCompressedSection {
-                size: s,
-                data: d,
-            }
-        )
-    )
+// Signature following the size field of a compressed section
+pub const PFS_COMPRESSED_SIGNATURE : &[u8] = b"\xAA\xEE\xAA\x76\x1B\xEC\xBB\x20\xF1\xE6\x51";
+
+// Footer starts with this, meaning of the bytes after it is not known
+pub const PFS_COMPRESSED_FOOTER : &[u8] = b"\xEE\xAA\xEE\x8F\x49\x1B\xE8\xAE\x14\x37\x90";
+pub const PFS_COMPRESSED_FOOTER_SIZE : usize = 0x10;
+
+// Footer is looked for this far after data, signatures of signed sections are smaller
+const PFS_COMPRESSED_SIGNATURE_MAX : usize = 0x4000;
+
+pub fn pfs_compressed_section (input : &[u8]) -> IResult<&[u8], PfsCompressedSection<'_>> {
+    let (input, size) = le_u32(input)?; // Obtain data size
+    let (input, _) = tag(PFS_COMPRESSED_SIGNATURE)(input)?; // Check for compressed section header
+    let (input, _) = take(1usize)(input)?; // Skip 1 byte
+    let (input, data) = take(size)(input)?; // Obtain payload
+
+    // Anything between data and footer is a signature, sections without footer signature keep the old layout
+    let window = &input[..input.len().min(PFS_COMPRESSED_SIGNATURE_MAX + PFS_COMPRESSED_FOOTER.len())];
+    let (input, signature) = match window.windows(PFS_COMPRESSED_FOOTER.len()).position(|w| w == PFS_COMPRESSED_FOOTER) {
+        Some(offset) if offset > 0 => (&input[offset..], Some(&input[..offset])),
+        _ => (input, None),
+    };
+
+    // Padding after the footer makes a longer footer, a short one is only tolerated at the end of data
+    let footer_size = if input.len() < PFS_COMPRESSED_FOOTER_SIZE || is_padding(&input[PFS_COMPRESSED_FOOTER_SIZE..]) {
+        input.len()
+    } else {
+        PFS_COMPRESSED_FOOTER_SIZE
+    };
+    let (input, footer) = take(footer_size)(input)?;
+    Ok((input, PfsCompressedSection { size, data, signature, footer }))
 }
 
 //
@@ -187,10 +544,24 @@ pub fn pfs_compressed_section (input : &[u8]) -> IResult<&[u8], PfsCompressedSec
 //
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 pub struct PfsChunk<'a> {
+    pub guid : Guid,
+    pub header_version : u32,
+    // Sizes and chunk count declared by the chunk, zero when not set
+    pub chunk_size : u32,
+    pub signature_size : u32,
+    pub payload_size : u32,
+    pub total_chunks : u16,
     pub order_number : u16,
+    // Header bytes with unknown meaning
+    pub reserved : &'a[u8],
     pub data : &'a[u8],
+    // Signature after chunk data
+    pub signature : Option<&'a[u8]>,
 }
 
+// Chunk data starts after this header
+pub const PFS_CHUNK_HEADER_SIZE : usize = 0x248;
+
 impl<'a> Ord for PfsChunk<'a> {
     fn cmp(&self, other: &PfsChunk<'a>) -> Ordering {
         self.order_number.cmp(&other.order_number)
@@ -203,55 +574,423 @@ impl<'a> PartialOrd for PfsChunk<'a> {
     }
 }
 
-pub fn pfs_chunk (input : &[u8]) -> IResult<&[u8], PfsChunk> {
-    do_parse!(input,
-        take!(0x3E) >> // Skip first 0x3E bytes
-        on : le_u16 >> // Get order number
-        take!(0x248 - 0x40) >> // Skip the rest of chunk header
-        d: rest >>
-        ( PfsChunk {
-                order_number: on,
-                data : d,
+pub fn pfs_chunk (input : &[u8]) -> IResult<&[u8], PfsChunk<'_>> {
+    let (input, g) = guid(input)?; // Get chunk GUID
+    let (input, header_version) = le_u32(input)?; // Get header version
+    let (input, _) = take(0x1Cusize)(input)?; // Skip unknown fields
+    let (input, chunk_size) = le_u32(input)?; // Get size of chunk data
+    let (input, signature_size) = le_u32(input)?; // Get size of trailing signature
+    let (input, payload_size) = le_u32(input)?; // Get size of assembled payload
+    let (input, total_chunks) = le_u16(input)?; // Get number of chunks
+    let (input, order_number) = le_u16(input)?; // Get order number
+    let (input, reserved) = take(PFS_CHUNK_HEADER_SIZE - 0x40)(input)?; // Get the rest of chunk header
+    let (input, body) = rest(input)?;
+
+    // Signature is only split off when it fits
+    let split = body.len().checked_sub(signature_size as usize).filter(|_| signature_size > 0);
+    let (data, signature) = match split {
+        Some(end) => (&body[..end], Some(&body[end..])),
+        None => (body, None),
+    };
+    Ok((input, PfsChunk {
+        guid : g,
+        header_version,
+        chunk_size,
+        signature_size,
+        payload_size,
+        total_chunks,
+        order_number,
+        reserved,
+        data,
+        signature,
+    }))
+}
+
+//
+// PFS chunk metadata, tells where chunk data belongs in flash
+//
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct PfsChunkMetadata {
+    pub address : u32,
+    pub offset : u32,
+    pub data_size : u32,
+}
+
+pub fn pfs_chunk_metadata (input : &[u8]) -> IResult<&[u8], PfsChunkMetadata> {
+    let (input, address) = le_u32(input)?; // Flash address
+    let (input, _) = take(4usize)(input)?; // Skip unknown field
+    let (input, offset) = le_u32(input)?; // Offset in the assembled image
+    let (input, data_size) = le_u32(input)?; // Size of chunk data
+    Ok((input, PfsChunkMetadata { address, offset, data_size }))
+}
+
+//
+// Section data classification
+//
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SectionKind {
+    Compressed, // PFS zlib-compressed section
+    Chunked,    // PFS subsection made of chunks
+    Subsection, // PFS subsection that is not made of chunks
+    Unknown,    // Anything else, can only be written as is
+}
+
+pub fn section_kind (data : &[u8]) -> SectionKind {
+    if pfs_compressed_section(data).is_ok() {
+        return SectionKind::Compressed;
+    }
+    match pfs_file(data) {
+        Ok((_, sub)) => {
+            // Subsection is chunked if every non-empty section in it is a chunk
+            let chunks : Vec<_> = sub.sections.iter().filter_map(|s| s.data).collect();
+            if !chunks.is_empty() && chunks.iter().all(|d| pfs_chunk(d).is_ok()) {
+                SectionKind::Chunked
+            } else {
+                SectionKind::Subsection
+            }
+        }
+        _ => SectionKind::Unknown,
+    }
+}
+
+//
+// Data found after PFS footer
+//
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum TrailingKind {
+    Padding, // Nothing, or only 0x00 or 0xFF bytes
+    Pfs(usize), // Another PFS file at given offset, possibly after padding or other data
+    Garbage, // Anything else
+}
+
+pub fn is_padding (data : &[u8]) -> bool {
+    data.iter().all(|&b| b == 0x00) || data.iter().all(|&b| b == 0xFF)
+}
+
+pub fn trailing_kind (data : &[u8]) -> TrailingKind {
+    let padding = data.iter().take_while(|&&b| b == 0x00).count()
+        .max(data.iter().take_while(|&&b| b == 0xFF).count());
+    if padding == data.len() {
+        TrailingKind::Padding
+    } else if pfs_file(&data[padding..]).is_ok() {
+        TrailingKind::Pfs(padding)
+    } else if let Some(&offset) = find_pfs_offsets(&data[padding..]).first() {
+        // Recovery files can have PFS images concatenated with other data between them
+        TrailingKind::Pfs(padding + offset)
+    } else {
+        TrailingKind::Garbage
+    }
+}
+
+//
+// PFS files embedded into other data, like Dell update executables
+//
+pub const PFS_HEADER_SIGNATURE : &[u8] = b"PFS.HDR.";
+
+// Offsets of every valid PFS file, files found inside of another one are skipped
+pub fn find_pfs_offsets (data : &[u8]) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut offset = 0;
+    while offset + PFS_HEADER_SIGNATURE.len() <= data.len() {
+        if &data[offset..offset + PFS_HEADER_SIGNATURE.len()] == PFS_HEADER_SIGNATURE {
+            if let Ok((_, file)) = pfs_file(&data[offset..]) {
+                offsets.push(offset);
+                offset += file.size();
+                continue;
             }
-        )
-    )
+        }
+        offset += 1;
+    }
+    offsets
+}
+
+// Offsets of compressed sections in other data, Dell recovery images (.hdr, .rcv)
+// carry zlib-compressed PFS files after a header of their own
+pub fn find_compressed_offsets (data : &[u8]) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut offset = 0;
+    // Size field is in front of the signature
+    while offset + 4 + PFS_COMPRESSED_SIGNATURE.len() <= data.len() {
+        if &data[offset + 4..offset + 4 + PFS_COMPRESSED_SIGNATURE.len()] == PFS_COMPRESSED_SIGNATURE {
+            if let Ok((rest, _)) = pfs_compressed_section(&data[offset..]) {
+                offsets.push(offset);
+                offset = data.len() - rest.len();
+                continue;
+            }
+        }
+        offset += 1;
+    }
+    offsets
+}
+
+//
+// Input classification by leading magic
+//
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum InputKind {
+    Pfs,               // PFS file with a valid header
+    CompressedSection, // PFS zlib-compressed section
+    UefiCapsule,       // UEFI capsule, possibly with PFS inside
+    PeImage,           // Executable, usually a Dell update wrapper
+    Gzip,
+    Zip,
+    Unknown,
+}
+
+impl fmt::Display for InputKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            InputKind::Pfs => "PFS file",
+            InputKind::CompressedSection => "PFS compressed section",
+            InputKind::UefiCapsule => "UEFI capsule",
+            InputKind::PeImage => "PE executable",
+            InputKind::Gzip => "gzip archive",
+            InputKind::Zip => "zip archive",
+            InputKind::Unknown => "unknown data",
+        })
+    }
+}
+
+// EFI_CAPSULE_GUID, EFI_FMP_CAPSULE_GUID and EFI_FIRMWARE_MANAGEMENT_CAPSULE_ID_GUID
+const UEFI_CAPSULE_GUIDS : [&str; 3] = [
+    "3B6686BD-0D76-4030-B70E-B5519E2FC5A0",
+    "6DCBD5ED-E82D-4C44-BDA1-7194199AD92A",
+    "539182B9-ABB5-4391-B69A-E3A943F72FCC",
+];
+
+//
+// UEFI capsule header
+//
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct UefiCapsuleHeader {
+    pub guid : Guid,
+    pub header_size : u32,
+    pub flags : u32,
+    pub image_size : u32,
+}
+
+pub fn uefi_capsule_header (input : &[u8]) -> IResult<&[u8], UefiCapsuleHeader> {
+    let (input, guid) = verify(guid, |g : &Guid| UEFI_CAPSULE_GUIDS.contains(&g.to_string().as_str()))(input)?;
+    let (input, header_size) = le_u32(input)?;
+    let (input, flags) = le_u32(input)?;
+    let (input, image_size) = le_u32(input)?;
+    Ok((input, UefiCapsuleHeader { guid, header_size, flags, image_size }))
+}
+
+// Body of a capsule with this GUID starts with EFI_FIRMWARE_MANAGEMENT_CAPSULE_HEADER
+const UEFI_FMP_CAPSULE_GUID : &str = "6DCBD5ED-E82D-4C44-BDA1-7194199AD92A";
+
+fn uefi_fmp_payload_type (body : &[u8]) -> IResult<&[u8], Guid> {
+    let (input, _) = le_u32(body)?; // Skip version
+    let (input, drivers) = le_u16(input)?; // Get number of embedded drivers
+    let (input, payloads) = le_u16(input)?; // Get number of payloads
+    if payloads == 0 {
+        return error(input, ErrorKind::Count);
+    }
+    let (input, _) = take(drivers as usize * 8)(input)?; // Skip offsets of drivers, payloads follow them
+    let (input, offset) = le_u64(input)?; // Get offset of the first payload
+    let item = match usize::try_from(offset).ok().and_then(|offset| body.get(offset..)) {
+        Some(item) => item,
+        None => return error(input, ErrorKind::Eof),
+    };
+    let (item, _) = le_u32(item)?; // Skip version of the payload header
+    guid(item) // UpdateImageTypeId, reported as firmware class by ESRT
+}
+
+// Type GUID of the first payload of an FMP capsule, the one ESRT and fwupd know the device by
+pub fn uefi_fmp_image_type (data : &[u8]) -> Option<Guid> {
+    let (_, header) = uefi_capsule_header(data).ok()?;
+    if header.guid.to_string() != UEFI_FMP_CAPSULE_GUID {
+        return None;
+    }
+    let body = data.get(header.header_size as usize..)?;
+    uefi_fmp_payload_type(body).ok().map(|(_, guid)| guid)
+}
+
+pub fn classify (data : &[u8]) -> InputKind {
+    if let Ok((_, header)) = pfs_header(data) {
+        if header.is_valid() {
+            return InputKind::Pfs;
+        }
+    }
+    if pfs_compressed_section(data).is_ok() {
+        return InputKind::CompressedSection;
+    }
+    if let Ok((_, g)) = guid(data) {
+        if UEFI_CAPSULE_GUIDS.contains(&g.to_string().as_str()) {
+            return InputKind::UefiCapsule;
+        }
+    }
+    if data.starts_with(b"MZ") {
+        InputKind::PeImage
+    } else if data.starts_with(&[0x1F, 0x8B]) {
+        InputKind::Gzip
+    } else if data.starts_with(b"PK\x03\x04") {
+        InputKind::Zip
+    } else {
+        InputKind::Unknown
+    }
 }
 
 //
 // PFS information section 
 //
-#[derive(Debug, PartialEq, Eq)]
+// Records of version 2 have more fields after the name
+pub const PFS_INFO_VERSION_EXTENDED : u32 = 2;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(::serde::Serialize, ::serde::Deserialize))]
 pub struct PfsInfoSection {
     pub header_version : u32,
     pub guid : Guid,
-    pub version : [u16; 4],
-    pub version_type : [u8; 4],
+    pub version : PfsVersion,
     pub name : String,
+    pub name_terminated : bool,
+    // Fields after the name of version 2 records as they are, empty for version 1 records
+    pub extra : Vec<u8>,
+}
+
+// Name terminator, a missing one is only tolerated at the end of data
+fn pfs_info_name_terminator (input : &[u8]) -> IResult<&[u8], bool> {
+    if input.is_empty() {
+        return Ok((input, false));
+    }
+    let (input, _) = tag(b"\x00\x00")(input)?;
+    Ok((input, true))
+}
+
+// Fields shared by both record versions
+fn pfs_info_record (input : &[u8]) -> IResult<&[u8], PfsInfoSection> {
+    let (input, header_version) = le_u32(input)?;
+    let (input, guid) = guid(input)?;
+    let (input, numbers) = le_u16_array(input)?;
+    let (input, types) = byte_array(input)?;
+    let (input, length) = le_u16(input)?;
+    let (input, name) = count(le_u16, length as usize)(input)?;
+    let (input, name_terminated) = pfs_info_name_terminator(input)?;
+    Ok((input, PfsInfoSection {
+        header_version,
+        guid,
+        version : PfsVersion { types, numbers },
+        name : String::from_utf16_lossy(&name),
+        name_terminated,
+        extra : Vec::new(),
+    }))
+}
+
+// Layout of the extended fields isn't known, so they run up to the next record or the end of data
+fn pfs_info_extra (input : &[u8]) -> IResult<&[u8], &[u8]> {
+    // Version is checked before the whole record, parsing one at every offset is too slow for large sections
+    let is_record = |data : &[u8]| matches!(data.get(..4), Some([1 | 2, 0, 0, 0]))
+        && matches!(pfs_info_record(data), Ok((_, record)) if record.header_version == 1 || record.header_version == PFS_INFO_VERSION_EXTENDED);
+    let size = (0..input.len()).find(|&i| is_record(&input[i..])).unwrap_or(input.len());
+    take(size)(input)
 }
 
 pub fn pfs_info_section (input : &[u8]) -> IResult<&[u8], PfsInfoSection> {
-    do_parse!(input,
-        hv : le_u32 >>
-        g  : guid >> 
-        v  : count_fixed!(u16, le_u16, 4) >>
-        vt : count_fixed!(u8, le_u8, 4) >>
-        l  : le_u16 >> 
-        n  : count!(le_u16, l as usize) >>
-             tag!("\x00\x00") >>
-        ( PfsInfoSection {
-                header_version: hv,
-                guid : g,
-                version : v,
-                version_type : vt,
-                name : String::from_utf16_lossy(&n),
-            }
-        )
-    )
+    let (input, mut record) = pfs_info_record(input)?;
+    if record.header_version != PFS_INFO_VERSION_EXTENDED {
+        return Ok((input, record));
+    }
+    let (input, extra) = pfs_info_extra(input)?;
+    record.extra = extra.to_vec();
+    Ok((input, record))
 }
 
 pub fn pfs_info (input : &[u8]) -> IResult<&[u8], Vec<PfsInfoSection>> {
-    do_parse!(input,
-        v : many0!(complete!(pfs_info_section)) >> 
-        ( v )
-    )
-}
\ No newline at end of file
+    many0(pfs_info_section)(input)
+}
+
+//
+// PFS model properties section
+//
+#[derive(Debug, PartialEq, Eq)]
+pub struct PfsModelProperty {
+    pub key : String,   // Empty for records that are a bare value
+    pub value : String,
+}
+
+// UTF-16LE text of "key=value" records separated by NUL characters or line breaks
+pub fn pfs_model_properties (input : &[u8]) -> IResult<&[u8], Vec<PfsModelProperty>> {
+    if !input.len().is_multiple_of(2) {
+        return error(input, ErrorKind::Verify);
+    }
+    let units : Vec<u16> = input.chunks(2).map(|c| u16::from(c[0]) | u16::from(c[1]) << 8).collect();
+    let text = match String::from_utf16(&units) {
+        Ok(text) => text,
+        Err(_) => return error(input, ErrorKind::Verify),
+    };
+
+    let mut properties = Vec::new();
+    for record in text.split(['\0', '\r', '\n']) {
+        let record = record.trim();
+        if record.is_empty() {
+            continue;
+        }
+        // Anything that isn't text means this is not a properties section
+        if record.chars().any(char::is_control) {
+            return error(input, ErrorKind::Verify);
+        }
+        let (key, value) = match record.find(['=', ':']) {
+            Some(pos) => (record[..pos].trim(), record[pos + 1..].trim()),
+            None => ("", record),
+        };
+        properties.push(PfsModelProperty {
+            key : String::from(key),
+            value : String::from(value),
+        });
+    }
+    if properties.is_empty() {
+        return error(input, ErrorKind::Verify);
+    }
+    Ok((&input[input.len()..], properties))
+}
+
+//
+// PFS section metadata, describes the file the section data was built from
+//
+#[derive(Debug, PartialEq, Eq)]
+pub struct PfsSectionMetadata {
+    pub model_ids : String,
+    pub file_name : String,
+    pub file_version : String,
+    pub date : String,
+    pub brand : String,
+    pub model_file : String,
+    pub model_name : String,
+    pub model_version : String,
+}
+
+pub const PFS_SECTION_METADATA_SIZE : usize = 0x3C0;
+
+// NUL-terminated ASCII field, anything but text in front of NUL means the blob has another layout
+fn metadata_field (input : &[u8]) -> Option<String> {
+    let end = input.iter().position(|&b| b == 0).unwrap_or(input.len());
+    let text = &input[..end];
+    if text.iter().any(|&b| !(0x20..0x7F).contains(&b)) {
+        return None;
+    }
+    Some(String::from_utf8_lossy(text).into_owned())
+}
+
+pub fn pfs_section_metadata (input : &[u8]) -> IResult<&[u8], PfsSectionMetadata> {
+    let field = |size : usize| map_opt(take(size), metadata_field);
+    let (input, model_ids) = field(0x1F5)(input)?;
+    let (input, file_name) = field(0x64)(input)?;
+    let (input, file_version) = field(0x21)(input)?;
+    let (input, date) = field(0x21)(input)?;
+    let (input, brand) = field(0x50)(input)?;
+    let (input, model_file) = field(0x50)(input)?;
+    let (input, model_name) = field(0x64)(input)?;
+    let (input, model_version) = field(0x21)(input)?;
+    Ok((input, PfsSectionMetadata {
+        model_ids,
+        file_name,
+        file_version,
+        date,
+        brand,
+        model_file,
+        model_name,
+        model_version,
+    }))
+}
@@ -0,0 +1,132 @@
+use std::io::{self, IsTerminal, Write};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+//
+// Progress line for long running operations
+//
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum ProgressMode {
+    #[default]
+    Off,
+    Bar,   // Bar redrawn in place, only on a terminal
+    Plain, // Percentage lines every PLAIN_STEP percent, for logs
+}
+
+pub struct Progress {
+    label : String,
+    total : u64,
+    // Clock is only read for the bar, WebAssembly in a browser has none
+    start : Option<Instant>,
+    last : Option<Instant>,
+    // Last percentage printed in plain mode
+    printed : Option<u64>,
+    mode : ProgressMode,
+}
+
+// Operations on less data than this are fast enough to not need progress
+pub const PROGRESS_MIN_SIZE : usize = 0x400000;
+
+// Progress line is redrawn at most this often
+const REFRESH_INTERVAL : Duration = Duration::from_millis(250);
+
+const BAR_WIDTH : u64 = 30;
+const PLAIN_STEP : u64 = 10;
+
+impl Progress {
+    // Bar is only shown if stderr is a terminal, small operations show nothing
+    pub fn new(label : &str, total : u64, mode : ProgressMode) -> Progress {
+        let mode = match mode {
+            _ if total < PROGRESS_MIN_SIZE as u64 => ProgressMode::Off,
+            ProgressMode::Bar if !io::stderr().is_terminal() => ProgressMode::Off,
+            mode => mode,
+        };
+        Progress {
+            label : String::from(label),
+            total,
+            start : if mode == ProgressMode::Bar { Some(Instant::now()) } else { None },
+            last : None,
+            printed : None,
+            mode,
+        }
+    }
+
+    pub fn update(&mut self, done : u64) {
+        let percent = (done.min(self.total) * 100).checked_div(self.total).unwrap_or(100);
+        match self.mode {
+            ProgressMode::Off => {}
+            ProgressMode::Plain => {
+                let step = percent - percent % PLAIN_STEP;
+                if self.printed.is_none_or(|printed| step > printed) {
+                    self.printed = Some(step);
+                    eprintln!("{}: {}%", self.label, step);
+                }
+            }
+            ProgressMode::Bar => {
+                let now = Instant::now();
+                if let Some(last) = self.last {
+                    if now.duration_since(last) < REFRESH_INTERVAL && done < self.total {
+                        return;
+                    }
+                }
+                self.last = Some(now);
+
+                let elapsed = self.start.map_or(0.0, |start| now.duration_since(start).as_secs_f64());
+                let filled = (percent * BAR_WIDTH / 100) as usize;
+                let mut line = format!("\r{}: [{}{}] {}% 0x{:X}/0x{:X} bytes, {:.1}s elapsed", self.label,
+                    "#".repeat(filled), " ".repeat(BAR_WIDTH as usize - filled), percent, done, self.total, elapsed);
+                if done > 0 && done < self.total {
+                    let eta = elapsed * (self.total - done) as f64 / done as f64;
+                    line.push_str(&format!(", {:.1}s left", eta));
+                }
+                // Progress output is best-effort, failing to print it is not an error
+                let mut stderr = io::stderr();
+                let _ = write!(stderr, "{}\x1B[K", line);
+                let _ = stderr.flush();
+            }
+        }
+    }
+
+    pub fn finish(&mut self) {
+        match self.mode {
+            ProgressMode::Off => {}
+            ProgressMode::Plain => self.update(self.total),
+            ProgressMode::Bar => {
+                if self.last.is_some() {
+                    self.update(self.total);
+                    eprintln!();
+                }
+            }
+        }
+    }
+}
+
+// Progress of work shared by several threads, shown as one line
+pub struct SharedProgress {
+    progress : Mutex<Progress>,
+    done : AtomicU64,
+}
+
+impl SharedProgress {
+    pub fn new(label : &str, total : u64, mode : ProgressMode) -> SharedProgress {
+        SharedProgress {
+            progress : Mutex::new(Progress::new(label, total, mode)),
+            done : AtomicU64::new(0),
+        }
+    }
+
+    pub fn add(&self, amount : u64) {
+        let done = self.done.fetch_add(amount, Ordering::Relaxed) + amount;
+        // Thread finding the line busy skips the redraw, the next one shows its work
+        if let Ok(mut progress) = self.progress.try_lock() {
+            progress.update(done);
+        }
+    }
+
+    pub fn finish(&self) {
+        if let Ok(mut progress) = self.progress.lock() {
+            progress.finish();
+        }
+    }
+}
@@ -0,0 +1,87 @@
+//
+// Sections of a PFS file parsed one at a time, for callers that stop early
+//
+use error::PfsError;
+use parser::{self, PfsHeader, PfsSection, PFS_HEADER_SIZE};
+use std::iter::FusedIterator;
+
+// Iterator over sections of a PFS file, each section header is parsed when it's reached and
+// nothing is allocated. Names are left empty, the information section describing them is the last one.
+// The first error ends the iteration, the footer is only checked after the last section
+pub struct PfsReader<'a> {
+    data : &'a [u8],
+    header : Option<PfsHeader>,
+    // Sections not parsed yet, limited to the area declared in the header, and where they end
+    rest : &'a [u8],
+    end : usize,
+    done : bool,
+}
+
+impl<'a> PfsReader<'a> {
+    pub fn sections(data : &'a [u8]) -> PfsReader<'a> {
+        PfsReader { data, header : None, rest : &[], end : 0, done : false }
+    }
+
+    // Header of the file, known once the first item is taken
+    pub fn header(&self) -> Option<PfsHeader> {
+        self.header
+    }
+
+    // Offset of the next section in data
+    pub fn offset(&self) -> usize {
+        self.end - self.rest.len()
+    }
+
+    fn fail(&mut self, error : PfsError) -> Option<Result<PfsSection<'a>, PfsError>> {
+        self.done = true;
+        Some(Err(error))
+    }
+
+    // Area declared in the header has to be there in full and the footer has to agree with the header
+    fn check_end(&mut self, header : PfsHeader) -> Option<Result<PfsSection<'a>, PfsError>> {
+        self.done = true;
+        let end = PFS_HEADER_SIZE + header.data_size as usize;
+        match parser::pfs_footer(self.data.get(end..).unwrap_or_default()) {
+            Ok((_, footer)) if footer.data_size == header.data_size => None,
+            Ok(_) => self.fail(PfsError::Layout("footer data size doesn't match the header")),
+            Err(_) => self.fail(PfsError::Layout("file ends before its footer")),
+        }
+    }
+}
+
+impl<'a> Iterator for PfsReader<'a> {
+    type Item = Result<PfsSection<'a>, PfsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let header = match self.header {
+            Some(header) => header,
+            None => {
+                let header = match parser::pfs_header(self.data) {
+                    Ok((_, header)) if header.is_valid() => header,
+                    _ => return self.fail(PfsError::Parse),
+                };
+                let sections = &self.data[PFS_HEADER_SIZE..];
+                self.rest = &sections[..sections.len().min(header.data_size as usize)];
+                self.end = PFS_HEADER_SIZE + self.rest.len();
+                self.header = Some(header);
+                header
+            }
+        };
+        if self.rest.is_empty() {
+            return self.check_end(header);
+        }
+        match parser::pfs_section(self.rest) {
+            Ok((rest, section)) => {
+                self.rest = rest;
+                Some(Ok(section))
+            }
+            Err(_) if self.data.len() < PFS_HEADER_SIZE + header.data_size as usize => self.fail(PfsError::Layout("file ends before its last section")),
+            Err(_) => self.fail(PfsError::Layout("section doesn't fit into the data size of the header")),
+        }
+    }
+}
+
+impl<'a> FusedIterator for PfsReader<'a> {}
@@ -0,0 +1,146 @@
+use manifest::{self, ManifestFile, ManifestSection, ManifestUnparsed};
+
+//
+// HTML report of the section tree, a single page without external resources
+//
+
+// Bytes of section data shown as hex
+pub const PREVIEW_SIZE : usize = 0x100;
+
+const STYLE : &str = "
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; }
+th { text-align: left; padding-right: 1em; font-weight: normal; color: #555; }
+td { padding-right: 1.5em; }
+details { margin: 0.3em 0 0.3em 1.5em; }
+summary { cursor: pointer; }
+.mono, pre { font-family: monospace; }
+.dim { color: #888; }
+.warn { color: #b00; }
+pre { background: #f4f4f4; padding: 0.5em; display: inline-block; margin: 0.3em 0; }
+";
+
+fn escape(text : &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// Every byte outside of unreserved characters and separators is percent-encoded
+fn url_path(path : &str) -> String {
+    let mut out = String::new();
+    for &b in path.as_bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => out.push(b as char),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    out
+}
+
+// Offset and ASCII columns like in hex editors
+fn hex_dump(data : &[u8]) -> String {
+    let mut out = String::new();
+    for (i, line) in data.chunks(16).enumerate() {
+        let hex : Vec<String> = line.iter().map(|b| format!("{:02X}", b)).collect();
+        let ascii : String = line.iter().map(|&b| if (0x20..0x7F).contains(&b) { b as char } else { '.' }).collect();
+        out.push_str(&format!("{:08X}  {:<47}  {}\n", i * 16, hex.join(" "), escape(&ascii)));
+    }
+    out
+}
+
+fn size(size : usize) -> String {
+    format!("0x{:X} ({} bytes)", size, size)
+}
+
+fn file_row(file : &ManifestFile, links : Option<&str>) -> String {
+    let name = match links {
+        Some(dir) => {
+            let path = if dir.is_empty() { file.name.clone() } else { format!("{}/{}", dir.trim_end_matches('/'), file.name) };
+            let scheme = if path.starts_with('/') { "file://" } else { "" };
+            format!("<a href=\"{}{}\">{}</a>", scheme, escape(&url_path(&path)), escape(&file.name))
+        }
+        None => escape(&file.name),
+    };
+    format!("<tr><td>{}</td><td>{}</td><td class=\"mono\">{}</td></tr>\n", name, size(file.size), file.sha256)
+}
+
+fn part_row(label : &str, part_size : u32, offset : Option<usize>) -> String {
+    if part_size == 0 {
+        return format!("<tr><th>{}</th><td class=\"dim\">none</td></tr>\n", label);
+    }
+    let offset = offset.map_or(String::from("in decompressed data"), |offset| format!("at 0x{:X}", offset));
+    format!("<tr><th>{}</th><td>{}, {}</td></tr>\n", label, size(part_size as usize), offset)
+}
+
+fn section_html(section : &ManifestSection, unparsed : &[ManifestUnparsed], links : Option<&str>) -> String {
+    let name = if section.name.is_empty() { "Unnamed section" } else { &section.name };
+    let mut out = format!("<details open><summary><b>{}</b> {} <span class=\"dim\">{}</span> <span class=\"mono dim\">{}</span></summary>\n",
+        escape(&section.index), escape(name), escape(&section.version), section.guid);
+
+    out.push_str("<table>\n");
+    out.push_str(&part_row("Data", section.data_size, section.data_offset));
+    out.push_str(&part_row("Data signature", section.data_sig_size, section.data_sig_offset));
+    out.push_str(&part_row("Metadata", section.meta_size, section.meta_offset));
+    out.push_str(&part_row("Metadata signature", section.meta_sig_size, section.meta_sig_offset));
+    for u in unparsed.iter().filter(|u| u.index == section.index) {
+        out.push_str(&format!("<tr><th>Unparsed</th><td class=\"warn\">{}</td></tr>\n", size(u.size)));
+    }
+    out.push_str("</table>\n");
+
+    if !section.data_preview.is_empty() {
+        let more = if section.data_size as usize > section.data_preview.len() { "<span class=\"dim\">...</span>" } else { "" };
+        out.push_str(&format!("<details><summary>Data preview</summary><pre>{}{}</pre></details>\n", hex_dump(&section.data_preview), more));
+    }
+    if !section.files.is_empty() {
+        out.push_str("<table>\n");
+        for file in &section.files {
+            out.push_str(&file_row(file, links));
+        }
+        out.push_str("</table>\n");
+    }
+    out
+}
+
+// Sections are in the order of extraction, so nested ones follow the section they are in.
+// Files are linked relative to the report if links is the output directory, they aren't linked if it's none
+pub fn to_html(input : &str, data : &[u8], status : &str, sections : &[ManifestSection], unparsed : &[ManifestUnparsed], links : Option<&str>) -> String {
+    let mut out = format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n",
+        escape(input), STYLE);
+    out.push_str(&format!("<h1>{}</h1>\n<table>\n", escape(input)));
+    out.push_str(&format!("<tr><th>Size</th><td>{}</td></tr>\n", size(data.len())));
+    out.push_str(&format!("<tr><th>SHA-256</th><td class=\"mono\">{}</td></tr>\n", manifest::sha256(data)));
+    out.push_str(&format!("<tr><th>Sections</th><td>{}</td></tr>\n", sections.len()));
+    out.push_str(&format!("<tr><th>Result</th><td>{}</td></tr>\n", escape(status)));
+    out.push_str(&format!("<tr><th>Report by</th><td>PFSExtractor-RS {}</td></tr>\n</table>\n", env!("CARGO_PKG_VERSION")));
+
+    // Data around PFS files rather than in a section
+    for u in unparsed.iter().filter(|u| !sections.iter().any(|s| s.index == u.index)) {
+        let offset = u.offset.map_or(String::new(), |offset| format!(" at 0x{:X}", offset));
+        out.push_str(&format!("<p class=\"warn\">Unparsed data{}: {}</p>\n", offset, size(u.size)));
+    }
+
+    // Sections of a nested file are closed when a section of a lower level comes
+    let mut open = Vec::new();
+    for section in sections {
+        while open.last().is_some_and(|&depth| depth >= section.depth) {
+            out.push_str("</details>\n");
+            open.pop();
+        }
+        out.push_str(&section_html(section, unparsed, links));
+        open.push(section.depth);
+    }
+    for _ in open {
+        out.push_str("</details>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
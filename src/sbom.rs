@@ -0,0 +1,85 @@
+use error::PfsError;
+use manifest::{json_string, sha256};
+use parser::PfsSection;
+use walk::{self, PfsVisitor};
+
+//
+// CycloneDX software bill of materials with a component for every section
+//
+
+pub const SBOM_JSON : &str = "sbom.cdx.json";
+
+const SUPPLIER : &str = "Dell Inc.";
+
+struct SbomComponent {
+    index : String,
+    name : String,
+    guid : String,
+    version : String,
+    sha256 : String,
+    // Hash of the data after decompression and chunk assembly, none if the data is stored as it is
+    payload_sha256 : Option<String>,
+}
+
+#[derive(Default)]
+struct SbomVisitor {
+    found : Vec<SbomComponent>,
+}
+
+impl PfsVisitor for SbomVisitor {
+    fn on_section(&mut self, index : &str, section : &PfsSection) -> bool {
+        self.found.push(SbomComponent {
+            index : String::from(index),
+            name : if section.name.is_empty() { section.guid.to_string() } else { section.name.clone() },
+            guid : section.guid.to_string(),
+            version : section.version.to_string(),
+            sha256 : sha256(section.data.unwrap_or_default()),
+            payload_sha256 : None,
+        });
+        true
+    }
+
+    fn on_compressed_payload(&mut self, index : &str, data : &[u8]) {
+        self.on_chunked_payload(index, data);
+    }
+
+    // Called once for every layer, the last one is the final payload
+    fn on_chunked_payload(&mut self, index : &str, data : &[u8]) {
+        if let Some(component) = self.found.iter_mut().rev().find(|c| c.index == index) {
+            component.payload_sha256 = Some(sha256(data));
+        }
+    }
+}
+
+fn hashes(sha256 : &str) -> String {
+    format!("[{{\"alg\": \"SHA-256\", \"content\": {}}}]", json_string(sha256))
+}
+
+fn property(name : &str, value : &str) -> String {
+    format!("{{\"name\": {}, \"value\": {}}}", json_string(name), json_string(value))
+}
+
+// Components follow the order of extraction, nested sections come right after the section holding them
+// and tell it by their index; no timestamp or serial number, so the same input gives the same file
+pub fn sbom_json(data : &[u8], input : &str) -> Result<String, PfsError> {
+    let mut visitor = SbomVisitor::default();
+    walk::pfs_walk(data, &mut visitor)?;
+
+    let mut out = String::from("{\n  \"bomFormat\": \"CycloneDX\",\n  \"specVersion\": \"1.5\",\n  \"version\": 1,\n");
+    out.push_str(&format!("  \"metadata\": {{\n    \"tools\": [{{\"name\": \"PFSExtractor-RS\", \"version\": {}}}],\n",
+        json_string(env!("CARGO_PKG_VERSION"))));
+    out.push_str(&format!("    \"component\": {{\"type\": \"firmware\", \"bom-ref\": \"input\", \"name\": {}, \"supplier\": {{\"name\": {}}}, \"hashes\": {}}}\n  }},\n",
+        json_string(input), json_string(SUPPLIER), hashes(&sha256(data))));
+
+    let components : Vec<String> = visitor.found.iter().map(|c| {
+        let version = if c.version.is_empty() { String::new() } else { format!(", \"version\": {}", json_string(&c.version)) };
+        let mut properties = vec![property("pfs:index", &c.index), property("pfs:guid", &c.guid)];
+        if let Some(ref payload) = c.payload_sha256 {
+            properties.push(property("pfs:payload_sha256", payload));
+        }
+        format!("\n    {{\"type\": \"firmware\", \"bom-ref\": {}, \"name\": {}{}, \"supplier\": {{\"name\": {}}}, \"hashes\": {}, \"properties\": [{}]}}",
+            json_string(&format!("section-{}", c.index)), json_string(&c.name), version, json_string(SUPPLIER), hashes(&c.sha256), properties.join(", "))
+    }).collect();
+    out.push_str(&format!("  \"components\": [{}{}]\n}}\n", components.join(","), if components.is_empty() { "" } else { "\n  " }));
+    Ok(out)
+}
@@ -0,0 +1,26 @@
+//
+// Serde support for parsed structures
+//
+use base64ct::{Base64, Encoding};
+use parser::Guid;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde::de::Error;
+use std::str::FromStr;
+
+// GUIDs are written in their usual text form
+impl Serialize for Guid {
+    fn serialize<S : Serializer>(&self, serializer : S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Guid {
+    fn deserialize<D : Deserializer<'de>>(deserializer : D) -> Result<Guid, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Guid::from_str(&s).map_err(D::Error::custom)
+    }
+}
+
+pub fn base64<S : Serializer>(data : &Option<&[u8]>, serializer : S) -> Result<S::Ok, S::Error> {
+    data.map(Base64::encode_string).serialize(serializer)
+}
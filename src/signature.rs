@@ -0,0 +1,93 @@
+use cms::cert::CertificateChoices;
+use cms::content_info::ContentInfo;
+use cms::signed_data::SignedData;
+use x509_cert::der::{Decode, EncodePem, SliceReader};
+use x509_cert::der::asn1::OctetString;
+use x509_cert::der::oid::ObjectIdentifier;
+use x509_cert::der::oid::db::DB;
+use x509_cert::der::oid::db::rfc5911::ID_MESSAGE_DIGEST;
+use x509_cert::der::pem::LineEnding;
+
+// PKCS#7 structure can be preceded by a header, it's looked for this far into the blob
+const PKCS7_SEARCH_SIZE : usize = 0x100;
+
+//
+// PKCS#7 signature blobs
+//
+pub struct SignerCertificate {
+    pub subject : String,
+    pub issuer : String,
+    pub pem : String,
+}
+
+// Digest of the signed data a signer put into its signed attributes
+pub struct SignedDigest {
+    pub algorithm : ObjectIdentifier,
+    pub digest : Vec<u8>,
+}
+
+pub struct Pkcs7Signature {
+    pub offset : usize,
+    pub digest_algorithms : Vec<String>,
+    pub certificates : Vec<SignerCertificate>,
+    pub digests : Vec<SignedDigest>,
+}
+
+fn oid_name(oid : &ObjectIdentifier) -> String {
+    DB.by_oid(oid).map_or_else(|| oid.to_string(), String::from)
+}
+
+fn signed_data_at(data : &[u8]) -> Option<SignedData> {
+    // Data after the structure is allowed
+    let mut reader = SliceReader::new(data).ok()?;
+    let info = ContentInfo::decode(&mut reader).ok()?;
+    info.content.decode_as::<SignedData>().ok()
+}
+
+// SignedData near the start of the blob and its offset
+pub fn find_signed_data(data : &[u8]) -> Option<(usize, SignedData)> {
+    let end = data.len().min(PKCS7_SEARCH_SIZE);
+    (0..end)
+        .filter(|&i| data[i] == 0x30)
+        .filter_map(|i| signed_data_at(&data[i..]).map(|signed| (i, signed)))
+        .next()
+}
+
+fn pkcs7(offset : usize, signed : SignedData) -> Option<Pkcs7Signature> {
+
+    let mut digest_algorithms : Vec<String> = signed.signer_infos.0.iter().map(|s| oid_name(&s.digest_alg.oid)).collect();
+    if digest_algorithms.is_empty() {
+        digest_algorithms = signed.digest_algorithms.iter().map(|a| oid_name(&a.oid)).collect();
+    }
+
+    let mut digests = Vec::new();
+    for signer in signed.signer_infos.0.iter() {
+        let attributes = signer.signed_attrs.iter().flat_map(|attrs| attrs.iter());
+        for attribute in attributes.filter(|a| a.oid == ID_MESSAGE_DIGEST) {
+            for value in attribute.values.iter() {
+                if let Ok(digest) = value.decode_as::<OctetString>() {
+                    digests.push(SignedDigest { algorithm : signer.digest_alg.oid, digest : digest.into_bytes() });
+                }
+            }
+        }
+    }
+
+    let mut certificates = Vec::new();
+    if let Some(set) = signed.certificates {
+        for choice in set.0.iter() {
+            if let CertificateChoices::Certificate(ref cert) = *choice {
+                certificates.push(SignerCertificate {
+                    subject : cert.tbs_certificate.subject.to_string(),
+                    issuer : cert.tbs_certificate.issuer.to_string(),
+                    pem : cert.to_pem(LineEnding::LF).ok()?,
+                });
+            }
+        }
+    }
+    Some(Pkcs7Signature { offset, digest_algorithms, certificates, digests })
+}
+
+// Returns None if the blob has no PKCS#7 SignedData near its start
+pub fn parse_pkcs7(data : &[u8]) -> Option<Pkcs7Signature> {
+    find_signed_data(data).and_then(|(offset, signed)| pkcs7(offset, signed))
+}
@@ -0,0 +1,55 @@
+//
+// Byte statistics of extracted data, to tell compressed or encrypted blobs from structured ones
+//
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+
+// Compressed and encrypted data stays close to 8 bits per byte, code and tables rarely go above 7
+const HIGH_ENTROPY : f64 = 7.5;
+
+// Entropy of short data stays low even when it is random
+const HIGH_ENTROPY_MIN_SIZE : usize = 0x400;
+
+// Written into manifests rounded to 4 decimals, fields missing in older manifests are 0
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DataStats {
+    #[serde(serialize_with = "rounded")]
+    pub entropy : f64,   // Shannon entropy in bits per byte, 0 to 8
+    #[serde(serialize_with = "rounded")]
+    pub zeros : f64,     // Share of zero bytes, 0 to 1
+    #[serde(serialize_with = "rounded")]
+    pub printable : f64, // Share of printable ASCII characters, tabs and line breaks, 0 to 1
+}
+
+fn rounded<S : Serializer>(value : &f64, serializer : S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64((value * 10000.0).round() / 10000.0)
+}
+
+impl DataStats {
+    pub fn of(data : &[u8]) -> DataStats {
+        if data.is_empty() {
+            return DataStats::default();
+        }
+        let mut counts = [0usize; 256];
+        for &b in data {
+            counts[b as usize] += 1;
+        }
+        let len = data.len() as f64;
+        // Same as the sum of -p * log2(p), without rounding to -0 for data of one byte value
+        let entropy = len.log2() - counts.iter().filter(|&&n| n > 0).map(|&n| n as f64 * (n as f64).log2()).sum::<f64>() / len;
+        let printable = data.iter().filter(|&&b| (0x20..0x7F).contains(&b) || b"\t\r\n".contains(&b)).count();
+        DataStats { entropy, zeros : counts[0] as f64 / len, printable : printable as f64 / len }
+    }
+
+    // Data this random is most likely compressed or encrypted, whatever its format is
+    pub fn is_high_entropy(&self, size : usize) -> bool {
+        size >= HIGH_ENTROPY_MIN_SIZE && self.entropy >= HIGH_ENTROPY
+    }
+}
+
+impl fmt::Display for DataStats {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "entropy {:.2} bits/byte, zeros {:.1}%, printable {:.1}%", self.entropy, self.zeros * 100.0, self.printable * 100.0)
+    }
+}
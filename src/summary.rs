@@ -0,0 +1,53 @@
+use guids::GuidNames;
+use parser;
+
+//
+// What a batch summary tells about one input, taken from top-level sections without decompressing anything
+//
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputSummary {
+    // Model names from section metadata, model IDs for sections without a name, in the order found
+    pub models : Vec<String>,
+    // Version of the first section named as BIOS, of the first section with a version otherwise
+    pub bios_version : Option<String>,
+}
+
+pub fn summarize(data : &[u8], guid_names : &GuidNames) -> InputSummary {
+    let mut summary = InputSummary::default();
+    let mut other_version = None;
+    for offset in parser::find_pfs_offsets(data) {
+        let file = match parser::pfs_file(&data[offset..]) {
+            Ok((_, file)) => file,
+            Err(_) => continue,
+        };
+        // Names are looked up quietly, extraction reports what's wrong with the information section
+        let info = file.sections.last().and_then(|s| s.data).and_then(|data| parser::pfs_info(data).ok()).map_or(Vec::new(), |(_, info)| info);
+        for section in file.iter() {
+            let name = info.iter().find(|i| i.guid == section.guid).map(|i| i.name.as_str())
+                .or_else(|| guid_names.get(&section.guid))
+                .unwrap_or_default();
+            let version = section.version.to_string();
+            if !version.is_empty() {
+                if summary.bios_version.is_none() && name.to_uppercase().contains("BIOS") {
+                    summary.bios_version = Some(version.clone());
+                }
+                other_version.get_or_insert(version);
+            }
+
+            let metadata = section.meta
+                .filter(|meta| meta.len() == parser::PFS_SECTION_METADATA_SIZE)
+                .and_then(|meta| parser::pfs_section_metadata(meta).ok());
+            if let Some((_, metadata)) = metadata {
+                let model = if metadata.model_name.is_empty() { metadata.model_ids } else { metadata.model_name };
+                if !model.is_empty() && !summary.models.contains(&model) {
+                    summary.models.push(model);
+                }
+            }
+        }
+    }
+    if summary.bios_version.is_none() {
+        summary.bios_version = other_version;
+    }
+    summary
+}
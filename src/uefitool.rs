@@ -0,0 +1,41 @@
+use error::PfsError;
+use parser::{Guid, PfsSection};
+use walk::{self, PfsVisitor};
+
+//
+// GUID database in the format UEFITool NE loads from guids.csv
+//
+
+pub const GUIDS_CSV : &str = "guids.csv";
+
+// Named sections by GUID, in the order they are found
+#[derive(Default)]
+struct GuidVisitor {
+    found : Vec<(Guid, String, String)>,
+}
+
+impl PfsVisitor for GuidVisitor {
+    fn on_section(&mut self, _index : &str, section : &PfsSection) -> bool {
+        // Sections with the same GUID in several PFS files keep the first name
+        if !section.name.is_empty() && !self.found.iter().any(|f| f.0 == section.guid) {
+            self.found.push((section.guid, section.name.clone(), section.version.to_string()));
+        }
+        true
+    }
+}
+
+// UEFITool splits lines at commas and takes the GUID and the name, the version is an extra column it ignores
+fn field(text : &str) -> String {
+    text.replace(',', " ").replace(|c : char| c.is_control(), " ")
+}
+
+// One line for every named section at every nesting level, lines starting with / are comments to UEFITool
+pub fn guids_csv(data : &[u8], input : &str) -> Result<String, PfsError> {
+    let mut visitor = GuidVisitor::default();
+    walk::pfs_walk(data, &mut visitor)?;
+    let mut out = format!("// Generated by PFSExtractor-RS {} from {}\n// GUID,Name,Version\n", env!("CARGO_PKG_VERSION"), field(input));
+    for (guid, name, version) in visitor.found {
+        out.push_str(&format!("{},{},{}\n", guid, field(&name), field(&version)));
+    }
+    Ok(out)
+}
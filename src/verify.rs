@@ -0,0 +1,119 @@
+use chain;
+use flate2::Crc;
+use parser::{self, PfsFile};
+use rsa::{Pkcs1v15Sign, RsaPublicKey};
+use rsa::traits::PublicKeyParts;
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::DecodePublicKey;
+use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+use signature;
+use std::fmt;
+use x509_cert::der::oid::ObjectIdentifier;
+use x509_cert::der::oid::db::rfc5912::{ID_SHA_224, ID_SHA_256, ID_SHA_384, ID_SHA_512};
+
+//
+// PFS footer checksum
+//
+pub fn pfs_checksum(payload : &[u8]) -> u32 {
+    // Footer checksum is an inverted CRC32 of everything between header and footer
+    let mut crc = Crc::new();
+    crc.update(payload);
+    !crc.sum()
+}
+
+// Data must be the same input the file was parsed from
+pub fn pfs_file_checksum(data : &[u8], file : &PfsFile) -> Option<u32> {
+    data.get(file.sections_range()).map(pfs_checksum)
+}
+
+//
+// Section signatures
+//
+pub fn load_public_key(pem : &str) -> Option<RsaPublicKey> {
+    // Accept both "PUBLIC KEY" and "RSA PUBLIC KEY" PEM files
+    RsaPublicKey::from_public_key_pem(pem)
+        .or_else(|_| RsaPublicKey::from_pkcs1_pem(pem))
+        .ok()
+}
+
+pub fn verify_signature(key : &RsaPublicKey, data : &[u8], sig : &[u8]) -> bool {
+    // PKCS#7 signature is made over its signed attributes, which carry the digest of the data
+    if let Some(ok) = chain::verify_with_key(key, data, sig) {
+        return ok;
+    }
+
+    // Any other signature blob ends with a PKCS#1 v1.5 RSA signature of SHA-256 digest of the data,
+    // anything in front of it is a header we don't check
+    let size = key.size();
+    if sig.len() < size {
+        return false;
+    }
+    let hash = Sha256::digest(data);
+    key.verify(Pkcs1v15Sign::new::<Sha256>(), &hash, &sig[sig.len() - size..]).is_ok()
+}
+
+//
+// Digests carried by signatures, checked against the data without validating the signatures
+//
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DigestStatus {
+    Pass,
+    Fail,
+    Unsupported, // Digests of algorithms other than SHA-2 only
+    Missing,     // No signature with a signed digest
+}
+
+impl fmt::Display for DigestStatus {
+    fn fmt(&self, f : &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            DigestStatus::Pass => "PASS",
+            DigestStatus::Fail => "FAIL",
+            DigestStatus::Unsupported => "unsupported",
+            DigestStatus::Missing => "none",
+        })
+    }
+}
+
+// SHA-2 digest of data, None for other algorithms
+pub fn digest(algorithm : &ObjectIdentifier, data : &[u8]) -> Option<Vec<u8>> {
+    match *algorithm {
+        ID_SHA_224 => Some(Sha224::digest(data).to_vec()),
+        ID_SHA_256 => Some(Sha256::digest(data).to_vec()),
+        ID_SHA_384 => Some(Sha384::digest(data).to_vec()),
+        ID_SHA_512 => Some(Sha512::digest(data).to_vec()),
+        _ => None,
+    }
+}
+
+// Every digest signed in a PKCS#7 signature has to match the data it's the signature of
+pub fn check_signed_digests(data : &[u8], sig : Option<&[u8]>) -> DigestStatus {
+    let digests = match sig.and_then(signature::parse_pkcs7) {
+        Some(pkcs7) => pkcs7.digests,
+        None => return DigestStatus::Missing,
+    };
+    let mut status = DigestStatus::Missing;
+    for signed in &digests {
+        match digest(&signed.algorithm, data) {
+            Some(ref computed) if *computed != signed.digest => return DigestStatus::Fail,
+            Some(_) => status = DigestStatus::Pass,
+            None if status == DigestStatus::Missing => status = DigestStatus::Unsupported,
+            None => (),
+        }
+    }
+    status
+}
+
+// Status of every chunk of a section made of chunks by its order number, empty for other sections
+pub fn check_chunk_digests(data : &[u8]) -> Vec<(u16, DigestStatus)> {
+    let sub = match parser::pfs_file(data) {
+        Ok((_, sub)) => sub,
+        Err(_) => return Vec::new(),
+    };
+    let chunks : Option<Vec<parser::PfsChunk>> = sub.sections.iter()
+        .filter_map(|s| s.data)
+        .map(|data| parser::pfs_chunk(data).ok().map(|(_, chunk)| chunk))
+        .collect();
+    chunks.unwrap_or_default().iter()
+        .map(|chunk| (chunk.order_number, check_signed_digests(chunk.data, chunk.signature)))
+        .collect()
+}
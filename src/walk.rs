@@ -0,0 +1,107 @@
+//
+// Walk through sections of a PFS file with callbacks, nothing is written
+//
+use compression;
+use error::PfsError;
+use extract::{self, pfs_set_names};
+use guids::GuidNames;
+use parser::{self, PfsFile, PfsSection};
+
+// Every callback does nothing by default, indexes are the ones extraction uses in file names
+pub trait PfsVisitor {
+    // PFS file starts at given nesting level, 0 for files in the input itself
+    fn on_nesting_enter(&mut self, _depth : usize, _file : &PfsFile) {}
+
+    // Returns whether data of the section is looked into
+    fn on_section(&mut self, _index : &str, _section : &PfsSection) -> bool {
+        true
+    }
+
+    // Decompressed data of a compressed section, once for every compression layer
+    fn on_compressed_payload(&mut self, _index : &str, _data : &[u8]) {}
+
+    // Payload assembled from chunks of a subsection
+    fn on_chunked_payload(&mut self, _index : &str, _data : &[u8]) {}
+
+    fn on_nesting_leave(&mut self, _depth : usize) {}
+
+    // Data of the section can't be decompressed, walking goes on with the next section
+    fn on_error(&mut self, _index : &str, _error : &PfsError) {}
+}
+
+fn decompress(data : &[u8]) -> Result<Vec<u8>, PfsError> {
+    compression::decompress(data, extract::MAX_DECOMPRESSED_SIZE)
+}
+
+// Payload of a subsection made of chunks, none if it has other sections
+fn pfs_chunked_payload(data : &[u8]) -> Option<Vec<u8>> {
+    let (_, sub) = parser::pfs_file(data).ok()?;
+    let mut chunks = Vec::new();
+    for data in sub.sections.iter().filter_map(|s| s.data) {
+        chunks.push(parser::pfs_chunk(data).ok()?.1);
+    }
+    if chunks.is_empty() {
+        return None;
+    }
+    chunks.sort();
+    Some(chunks.iter().flat_map(|ch| ch.data.iter().copied()).collect())
+}
+
+// Compressed data is looked into as PFS file, chunked payload as section data again,
+// it is always smaller than the subsection it comes from
+fn pfs_walk_data<V : PfsVisitor>(data : &[u8], index : &str, depth : usize, visitor : &mut V) -> Result<(), PfsError> {
+    if let Ok((_, comp)) = parser::pfs_compressed_section(data) {
+        let mut decompressed = decompress(comp.data)?;
+        visitor.on_compressed_payload(index, &decompressed);
+        for _ in 1..extract::MAX_COMPRESSION_LAYERS {
+            let inner = match parser::pfs_compressed_section(&decompressed) {
+                Ok((_, inner)) => decompress(inner.data)?,
+                Err(_) => break,
+            };
+            decompressed = inner;
+            visitor.on_compressed_payload(index, &decompressed);
+        }
+        if depth < extract::MAX_DEPTH && parser::pfs_file(&decompressed).is_ok() {
+            pfs_walk_file(&decompressed, &format!("{}.", index), depth + 1, visitor);
+        }
+        return Ok(());
+    }
+    if let Some(payload) = pfs_chunked_payload(data) {
+        visitor.on_chunked_payload(index, &payload);
+        return pfs_walk_data(&payload, index, depth, visitor);
+    }
+    Ok(())
+}
+
+fn pfs_walk_file<V : PfsVisitor>(data : &[u8], index_prefix : &str, depth : usize, visitor : &mut V) {
+    let mut file = match parser::pfs_file(data) {
+        Ok((_, file)) => file,
+        _ => return,
+    };
+    pfs_set_names(&mut file, &GuidNames::default());
+
+    visitor.on_nesting_enter(depth, &file);
+    for (i, section) in file.sections.iter().enumerate() {
+        let index = format!("{}{}", index_prefix, i + 1);
+        if !visitor.on_section(&index, section) {
+            continue;
+        }
+        if let Err(e) = pfs_walk_data(section.data.unwrap_or(&[]), &index, depth, visitor) {
+            visitor.on_error(&index, &e);
+        }
+    }
+    visitor.on_nesting_leave(depth);
+}
+
+// Every PFS file found in the input is walked, sections of files after the first one get pfsN. index prefix
+pub fn pfs_walk<V : PfsVisitor>(data : &[u8], visitor : &mut V) -> Result<(), PfsError> {
+    let offsets = parser::find_pfs_offsets(data);
+    if offsets.is_empty() {
+        return Err(PfsError::Parse);
+    }
+    for (n, &offset) in offsets.iter().enumerate() {
+        let prefix = if n == 0 { String::new() } else { format!("pfs{}.", n + 1) };
+        pfs_walk_file(&data[offset..], &prefix, 0, visitor);
+    }
+    Ok(())
+}
@@ -0,0 +1,80 @@
+//
+// Dell catalog parsing and package selection
+//
+extern crate pfsextractor;
+
+use pfsextractor::catalog::{Catalog, CatalogFilter};
+
+const CATALOG : &str = r#"<?xml version="1.0" encoding="utf-16"?>
+<!-- Shortened from the layout of CatalogPC.xml -->
+<Manifest baseLocation="downloads.dell.com" version="2024.01">
+  <SoftwareComponent path="FOLDER01\Latitude_7420_1.2.3.exe" vendorVersion="1.2.3" size="1024">
+    <Name><Display lang="en"><![CDATA[Dell Latitude 7420 System BIOS]]></Display></Name>
+    <ComponentType value="BIOS"><Display lang="en">BIOS</Display></ComponentType>
+    <SupportedSystems>
+      <Brand key="4"><Display lang="en">Latitude</Display>
+        <Model systemID="0A0B"><Display lang="en">7420</Display></Model>
+      </Brand>
+    </SupportedSystems>
+    <Cryptography>
+      <Hash algorithm="MD5">00</Hash>
+      <Hash algorithm="SHA256">ABCDEF</Hash>
+    </Cryptography>
+  </SoftwareComponent>
+  <SoftwareComponent path="FOLDER02/Dock_Firmware.exe" dellVersion="01">
+    <Name><Display lang="en">Dock &amp; Cable Firmware</Display></Name>
+    <ComponentType value="FRMW"/>
+  </SoftwareComponent>
+</Manifest>
+"#;
+
+fn utf16(text : &str) -> Vec<u8> {
+    b"\xFF\xFE".iter().copied().chain(text.encode_utf16().flat_map(u16::to_le_bytes)).collect()
+}
+
+#[test]
+fn catalog_is_parsed() {
+    let catalog = Catalog::parse(&utf16(CATALOG)).unwrap();
+    assert_eq!(catalog.packages.len(), 2);
+    let bios = &catalog.packages[0];
+    assert_eq!(bios.name, "Dell Latitude 7420 System BIOS");
+    assert_eq!(bios.component_type, "BIOS");
+    assert_eq!(bios.version, "1.2.3");
+    assert_eq!(bios.path, "FOLDER01/Latitude_7420_1.2.3.exe");
+    assert_eq!(bios.file_name(), "Latitude_7420_1.2.3.exe");
+    assert_eq!(bios.size, Some(1024));
+    assert_eq!(bios.sha256.as_deref(), Some("abcdef"));
+    assert_eq!(bios.models(), ["Latitude 7420"]);
+    assert_eq!(bios.systems[0].system_id, "0A0B");
+
+    let dock = &catalog.packages[1];
+    assert_eq!(dock.name, "Dock & Cable Firmware");
+    assert_eq!(dock.version, "01");
+    assert_eq!(dock.sha256, None);
+
+    // Saved copies may be UTF-8
+    assert_eq!(Catalog::parse(CATALOG.as_bytes()).unwrap().packages, catalog.packages);
+}
+
+#[test]
+fn catalog_without_scheme_is_downloaded_over_https() {
+    let catalog = Catalog::parse(CATALOG.as_bytes()).unwrap();
+    assert_eq!(catalog.url(&catalog.packages[0]), "https://downloads.dell.com/FOLDER01/Latitude_7420_1.2.3.exe");
+}
+
+#[test]
+fn packages_are_picked_by_model_and_system_id() {
+    let catalog = Catalog::parse(CATALOG.as_bytes()).unwrap();
+    let picked = |filter : CatalogFilter| catalog.packages.iter().filter(|p| filter.matches(p)).count();
+    assert_eq!(picked(CatalogFilter { model : Some(String::from("latitude 7420")), ..CatalogFilter::default() }), 1);
+    assert_eq!(picked(CatalogFilter { system_id : Some(String::from("0x0a0b")), ..CatalogFilter::default() }), 1);
+    assert_eq!(picked(CatalogFilter { component_type : Some(String::from("frmw")), ..CatalogFilter::default() }), 1);
+    assert_eq!(picked(CatalogFilter { model : Some(String::from("Precision")), ..CatalogFilter::default() }), 0);
+}
+
+#[test]
+fn broken_catalog_is_an_error() {
+    for broken in ["<Manifest><SoftwareComponent></Manifest>", "<Manifest>", "<Catalog/>", ""] {
+        assert!(Catalog::parse(broken.as_bytes()).is_err(), "{}", broken);
+    }
+}
@@ -0,0 +1,66 @@
+//
+// Signatures and signature chains of the test signing chain in tests/data
+//
+extern crate pfsextractor;
+
+use pfsextractor::chain::{self, ChainStatus};
+use pfsextractor::verify;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const ROOT : &str = include_str!("data/test_root.pem");
+const OTHER_ROOT : &str = include_str!("data/other_root.pem");
+const SIGNER_KEY : &str = include_str!("data/signer_key.pem");
+const SIGNED : &[u8] = include_bytes!("data/signed.bin");
+const SIGNATURE : &[u8] = include_bytes!("data/signed.bin.p7s");
+const SIGNED_BY_LEAF : &[u8] = include_bytes!("data/signed_by_leaf.bin.p7s");
+
+fn status(data : &[u8], sig : &[u8], roots : Option<&str>) -> ChainStatus {
+    let roots = roots.map_or_else(Vec::new, |pem| chain::load_roots(pem).unwrap());
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    chain::validate_signature(data, Some(sig), &roots, now).status
+}
+
+#[test]
+fn known_good_signature_is_trusted() {
+    assert_eq!(status(SIGNED, SIGNATURE, Some(ROOT)), ChainStatus::Trusted);
+    // Text around certificates is skipped
+    let commented = format!("Test root\n{}", ROOT);
+    assert_eq!(status(SIGNED, SIGNATURE, Some(&commented)), ChainStatus::Trusted);
+}
+
+#[test]
+fn tampered_signature_fails() {
+    // Last byte of the signer's RSA signature, the signature ends the CMS structure
+    let mut signature = SIGNATURE.to_vec();
+    *signature.last_mut().unwrap() ^= 0x01;
+    assert_eq!(status(SIGNED, &signature, Some(ROOT)), ChainStatus::BadSignature);
+
+    let mut data = SIGNED.to_vec();
+    data[0] ^= 0x01;
+    assert_eq!(status(&data, SIGNATURE, Some(ROOT)), ChainStatus::BadSignature);
+}
+
+#[test]
+fn chain_not_ending_at_root_is_untrusted() {
+    assert_eq!(status(SIGNED, SIGNATURE, Some(OTHER_ROOT)), ChainStatus::Untrusted);
+    // No roots at all
+    assert_eq!(status(SIGNED, SIGNATURE, None), ChainStatus::Untrusted);
+}
+
+// Signer certificate issued by the test signer, which is no CA, with the real chain above it
+#[test]
+fn certificate_issued_by_leaf_is_untrusted() {
+    assert_eq!(status(SIGNED, SIGNED_BY_LEAF, Some(ROOT)), ChainStatus::Untrusted);
+}
+
+// Key of the signer verifies the signature over signed attributes, whose digest has to match the data
+#[test]
+fn pkcs7_signature_is_verified_with_signer_key() {
+    let key = verify::load_public_key(SIGNER_KEY).unwrap();
+    assert!(verify::verify_signature(&key, SIGNED, SIGNATURE));
+
+    let mut data = SIGNED.to_vec();
+    data[0] ^= 0x01;
+    assert!(!verify::verify_signature(&key, &data, SIGNATURE));
+    assert!(!verify::verify_signature(&key, SIGNED, SIGNED_BY_LEAF));
+}
@@ -0,0 +1,259 @@
+//
+// Extraction of hand-built PFS files nesting sections in ways generated files rarely do
+//
+extern crate pfsextractor;
+extern crate proptest;
+
+mod pfs_gen;
+
+use pfs_gen::{chunked, data_section, GenData, GenFile, GenSection};
+use pfsextractor::compression::Compression;
+use pfsextractor::extract::{self, ExtractProfile, Extraction, Options};
+use pfsextractor::parser;
+use pfsextractor::chain::{self, ChainStatus};
+use pfsextractor::PfsError;
+use pfsextractor::error::EXIT_VERIFY;
+
+const INNER : &[u8] = b"data of the section in the innermost PFS file";
+
+fn options() -> Options {
+    Options::builder().jobs(1).build()
+}
+
+fn file_of(data : GenData) -> Vec<u8> {
+    GenFile { sections : vec![data_section(1, data)], names : Vec::new() }.build()
+}
+
+fn written<'a>(extraction : &'a Extraction, data : &[u8]) -> Vec<&'a str> {
+    extraction.files.iter().filter(|f| f.1 == data).map(|f| &f.0[..]).collect()
+}
+
+#[test]
+fn chunks_of_compressed_pfs_file_are_extracted() {
+    let pfs = GenData::Subsection(GenFile { sections : vec![data_section(2, GenData::Raw(INNER.to_vec()))], names : Vec::new() });
+    let compressed = GenData::Compressed(Box::new(pfs), Compression::Zlib).build();
+    let data = file_of(chunked(&compressed, 0x10));
+
+    let extraction = extract::extract_in_memory(&options(), &data);
+    assert!(matches!(extraction.result, Ok(true)), "{:?}", extraction.result);
+    assert_eq!(written(&extraction, INNER).len(), 1, "{:?}", extraction.components);
+}
+
+#[test]
+fn chunk_nesting_stops_at_max_depth() {
+    let mut nested = GenData::Raw(INNER.to_vec());
+    for _ in 0..6 {
+        nested = chunked(&nested.build(), 0x1000);
+    }
+    let data = file_of(nested);
+
+    let mut options = options();
+    options.max_depth = 3;
+    let extraction = extract::extract_in_memory(&options, &data);
+    assert!(matches!(extraction.result, Ok(true)), "{:?}", extraction.result);
+    assert!(written(&extraction, INNER).is_empty());
+    // Payload at the limit is written as it is, still made of chunks
+    let deepest = extraction.files.iter().filter(|f| f.0.ends_with(".payload")).max_by_key(|f| f.0.matches("payload").count()).unwrap();
+    assert_eq!(deepest.0.matches("payload").count(), 3, "{:?}", extraction.components);
+    assert_eq!(parser::section_kind(&deepest.1), parser::SectionKind::Chunked);
+
+    // It's written even when intermediate payloads are not
+    options.profile = ExtractProfile::PayloadOnly;
+    let extraction = extract::extract_in_memory(&options, &data);
+    let payloads : Vec<_> = extraction.files.iter().filter(|f| f.0.ends_with(".payload")).map(|f| &f.0).collect();
+    assert_eq!(payloads, [&deepest.0], "{:?}", extraction.components);
+
+    let extraction = extract::extract_in_memory(&self::options(), &data);
+    assert_eq!(written(&extraction, INNER).len(), 1, "{:?}", extraction.components);
+}
+
+// Information section names it in a way that says nothing about EC
+#[test]
+fn ec_firmware_is_found_by_guid() {
+    let data = GenFile { sections : vec![data_section(1, GenData::Raw(INNER.to_vec()))], names : vec![String::from("Controller Firmware")] }.build();
+    let mut options = options();
+    options.ec = true;
+    let extraction = extract::extract_in_memory(&options, &data);
+    assert!(written(&extraction, INNER).iter().all(|name| !name.ends_with(".ec")));
+
+    let guid = parser::pfs_file(&data).unwrap().1.sections[0].guid;
+    options.guid_names.insert(guid, "Embedded Controller");
+    let extraction = extract::extract_in_memory(&options, &data);
+    let names = written(&extraction, INNER);
+    assert!(names.len() == 1 && names[0].ends_with(".data.ec"), "{:?}", names);
+}
+
+// Plausible size field in front of a wrong or cut off compressed section signature
+#[test]
+fn data_with_broken_compressed_signature_is_written_raw() {
+    let compressed = GenData::Compressed(Box::new(GenData::Raw(INNER.to_vec())), Compression::Zlib).build();
+    let mut wrong = compressed.clone();
+    wrong[4 + parser::PFS_COMPRESSED_SIGNATURE.len() - 1] ^= 0x01;
+    let truncated = compressed[..4 + parser::PFS_COMPRESSED_SIGNATURE.len() / 2].to_vec();
+
+    for broken in [wrong, truncated] {
+        let extraction = extract::extract_in_memory(&options(), &file_of(GenData::Raw(broken.clone())));
+        assert!(matches!(extraction.result, Ok(true)), "{:?}", extraction.result);
+        let names = written(&extraction, &broken);
+        assert!(names.len() == 1 && names[0].ends_with(".data"), "{:?}", extraction.components);
+        assert!(!extraction.files.iter().any(|f| f.0.contains(".decompressed")));
+        assert!(written(&extraction, INNER).is_empty());
+    }
+}
+
+// Data is written to its file while decompressing, the file is removed when decompression fails
+#[test]
+fn failed_streamed_decompression_leaves_no_file() {
+    let raw = vec![0x5Au8; 0x30000];
+    let data = file_of(GenData::Compressed(Box::new(GenData::Raw(raw.clone())), Compression::Zlib));
+    let dir = std::env::temp_dir().join(format!("pfs_streamed_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut options = options();
+    options.max_decompressed_size = raw.len() / 2;
+    let extraction = extract::extract(&options, &data, &dir);
+    let names : Vec<_> = std::fs::read_dir(&dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(extraction.result.is_err(), "{:?}", extraction.result);
+    assert!(!names.iter().any(|name| name.to_string_lossy().contains(".decompressed")), "{:?}", names);
+}
+
+#[test]
+fn double_compressed_data_is_unwrapped() {
+    let inner = GenData::Compressed(Box::new(GenData::Raw(INNER.to_vec())), Compression::Lzma);
+    let data = file_of(GenData::Compressed(Box::new(inner), Compression::Zlib));
+
+    let extraction = extract::extract_in_memory(&options(), &data);
+    assert!(matches!(extraction.result, Ok(true)), "{:?}", extraction.result);
+    let names = written(&extraction, INNER);
+    assert!(names.len() == 1 && names[0].ends_with(".decompressed2"), "{:?}", extraction.components);
+}
+
+// Each layer fits into the limit, both together don't
+#[test]
+fn compression_layers_share_size_limit() {
+    let raw = vec![0u8; 0x1000];
+    let inner = GenData::Compressed(Box::new(GenData::Raw(raw.clone())), Compression::Zlib);
+    let middle = inner.build();
+    let data = file_of(GenData::Compressed(Box::new(inner), Compression::Zlib));
+
+    let mut options = options();
+    options.max_decompressed_size = raw.len() + middle.len();
+    let extraction = extract::extract_in_memory(&options, &data);
+    assert!(matches!(extraction.result, Ok(true)), "{:?}", extraction.result);
+    assert_eq!(written(&extraction, &raw).len(), 1);
+
+    options.max_decompressed_size = raw.len() + middle.len() - 1;
+    let extraction = extract::extract_in_memory(&options, &data);
+    assert!(written(&extraction, &raw).is_empty());
+    assert!(matches!(extraction.result, Err(PfsError::Sections(1))), "{:?}", extraction.result);
+}
+
+const SIGNED : &[u8] = include_bytes!("data/signed.bin");
+const SIGNATURE : &[u8] = include_bytes!("data/signed.bin.p7s");
+
+fn signed_file(data : &[u8]) -> Vec<u8> {
+    let mut section = data_section(1, GenData::Raw(data.to_vec()));
+    section.data_sig = SIGNATURE.to_vec();
+    GenFile { sections : vec![section], names : Vec::new() }.build()
+}
+
+#[test]
+fn data_matching_signed_digest_passes() {
+    let mut options = options();
+    options.verify_hashes = true;
+    let extraction = extract::extract_in_memory(&options, &signed_file(SIGNED));
+    assert!(matches!(extraction.result, Ok(true)), "{:?}", extraction.result);
+}
+
+#[test]
+fn data_not_matching_signed_digest_fails() {
+    let mut tampered = SIGNED.to_vec();
+    tampered[0] ^= 0x01;
+    let data = signed_file(&tampered);
+    let extraction = extract::extract_in_memory(&options(), &data);
+    assert!(matches!(extraction.result, Ok(true)), "{:?}", extraction.result);
+
+    let mut options = options();
+    options.verify_hashes = true;
+    let extraction = extract::extract_in_memory(&options, &data);
+    assert!(matches!(extraction.result, Err(PfsError::Unverified(1))), "{:?}", extraction.result);
+    assert_eq!(extraction.result.as_ref().unwrap_err().exit_code(), EXIT_VERIFY);
+    assert_eq!(written(&extraction, &tampered).len(), 1);
+    options.fail_fast = true;
+    let extraction = extract::extract_in_memory(&options, &data);
+    assert!(matches!(extraction.result, Err(PfsError::Digest("data"))), "{:?}", extraction.result);
+}
+
+// GUIDs are random here, so sections can only be named by what is in them
+fn known_contents() -> Vec<GenSection> {
+    let mut fv = vec![0u8; 0x48];
+    fv[0x28..0x2C].copy_from_slice(b"_FVH");
+    let mut me = vec![0xFFu8; 0x40];
+    me[0x10..0x14].copy_from_slice(b"$FPT");
+    let mut ec = vec![0u8; 0x200];
+    ec[0x100..0x10D].copy_from_slice(b"\xA5\xA5\xA5\xA5\xA5\xA5\xA5\xA5\x85\x12\x5A\x5A\xAA");
+    let properties : Vec<u8> = "Model=Test\0".encode_utf16().flat_map(u16::to_le_bytes).collect();
+    vec![
+        data_section(1, GenData::Compressed(Box::new(GenData::Raw(fv)), Compression::Zlib)),
+        data_section(2, GenData::Raw(me)),
+        data_section(3, GenData::Raw(ec)),
+        data_section(4, GenData::Raw(properties)),
+        data_section(5, GenData::Raw(INNER.to_vec())),
+    ]
+}
+
+fn section_names(extraction : &Extraction) -> Vec<&str> {
+    let mut names : Vec<&str> = extraction.files.iter().map(|f| &f.0[..]).filter(|name| name.ends_with(".data")).collect();
+    names.sort_unstable();
+    names
+}
+
+#[test]
+fn sections_without_information_are_named_by_content() {
+    let data = GenFile { sections : known_contents(), names : Vec::new() }.build();
+    let (_, file) = parser::pfs_file(&data).unwrap();
+    let sections = data[file.sections_range()].to_vec();
+
+    let extraction = extract::extract_in_memory(&options(), &data);
+    assert!(matches!(extraction.result, Ok(true)), "{:?}", extraction.result);
+    let names = section_names(&extraction);
+    assert_eq!(names.len(), 5, "{:?}", names);
+    assert!(names[0].starts_with("1_System_BIOS_"), "{:?}", names);
+    assert!(names[1].starts_with("2_Intel_Management_Engine_"), "{:?}", names);
+    assert!(names[2].starts_with("3_Embedded_Controller_"), "{:?}", names);
+    assert!(names[3].starts_with("4_Model_Properties_"), "{:?}", names);
+
+    let mut options = options();
+    options.headerless = true;
+    let headerless = extract::extract_in_memory(&options, &sections);
+    assert_eq!(section_names(&headerless), names);
+}
+
+#[test]
+fn signature_chain_ending_at_root_passes() {
+    let mut options = options();
+    options.verify_signatures = chain::load_roots(include_str!("data/test_root.pem"));
+    let extraction = extract::extract_in_memory(&options, &signed_file(SIGNED));
+    assert!(matches!(extraction.result, Ok(true)), "{:?}", extraction.result);
+}
+
+#[test]
+fn signature_chain_not_ending_at_root_fails() {
+    let data = signed_file(SIGNED);
+    let mut options = options();
+    options.verify_signatures = chain::load_roots(include_str!("data/other_root.pem"));
+    let extraction = extract::extract_in_memory(&options, &data);
+    assert!(matches!(extraction.result, Err(PfsError::Unverified(1))), "{:?}", extraction.result);
+    assert_eq!(extraction.result.as_ref().unwrap_err().exit_code(), EXIT_VERIFY);
+    assert_eq!(written(&extraction, SIGNED).len(), 1);
+    options.fail_fast = true;
+    let extraction = extract::extract_in_memory(&options, &data);
+    assert!(matches!(extraction.result, Err(PfsError::Signature("data", ChainStatus::Untrusted))), "{:?}", extraction.result);
+
+    // There are no built-in roots, checking against none is refused before anything is extracted
+    options.verify_signatures = Some(Vec::new());
+    let extraction = extract::extract_in_memory(&options, &data);
+    assert!(matches!(extraction.result, Err(PfsError::NoRoots)), "{:?}", extraction.result);
+    assert!(extraction.files.is_empty());
+}
@@ -0,0 +1,100 @@
+//
+// manifest.json written with extraction and read back for repack and --resume
+//
+extern crate pfsextractor;
+
+use pfsextractor::extract::{self, Options};
+use pfsextractor::manifest;
+use pfsextractor::{PfsBuilder, PfsBuilderSection, PfsVersion};
+
+const OLD_MANIFEST : &str = r#"{
+  "sections": [
+    {
+      "index": "1",
+      "depth": 0,
+      "guid": "FD041960-0DC8-4B9F-8225-BBA9E37C71E0",
+      "name": "Name with \"quotes\" and \\",
+      "version": "1.2.3",
+      "data_size": 4,
+      "data_sig_size": 0,
+      "meta_size": 0,
+      "meta_sig_size": 0,
+      "data_offset": 88,
+      "data_sig_offset": null,
+      "meta_offset": null,
+      "meta_sig_offset": null,
+      "pfs_header_version": 1,
+      "header_version": 1,
+      "version_type": "4E4E4E20",
+      "version_numbers": [1, 2, 3, 0],
+      "reserved": 0,
+      "unknown": "00000000000000000000000000000000",
+      "data_file": "1_Name_1.2.3.data",
+      "data_sig_file": null,
+      "meta_file": null,
+      "meta_sig_file": null,
+      "files": [
+        {"name": "1_Name_1.2.3.data", "size": 4, "sha256": "88d4266fd4e6338d13b845fcf289579d209c897823b9217da3e161936f031589"}
+      ]
+    }
+  ],
+  "unparsed": []
+}
+"#;
+
+// Manifests of older versions have no information section fields and no statistics
+#[test]
+fn old_manifest_is_read() {
+    let sections = manifest::from_json(OLD_MANIFEST).unwrap();
+    assert_eq!(sections.len(), 1);
+    let section = &sections[0];
+    assert_eq!(section.name, "Name with \"quotes\" and \\");
+    assert_eq!(section.version_type, *b"NNN ");
+    assert_eq!(section.version_numbers, [1, 2, 3, 0]);
+    assert_eq!(section.data_offset, Some(88));
+    assert_eq!(section.data_sig_file, None);
+    assert_eq!(section.info_header_version, None);
+    assert_eq!(section.info_extra, None);
+    assert_eq!(section.files[0].size, 4);
+    assert_eq!(section.files[0].stats.entropy, 0.0);
+}
+
+#[test]
+fn written_manifest_is_read_back() {
+    let sections = manifest::from_json(OLD_MANIFEST).unwrap();
+    let json = manifest::to_json(&sections, &[]);
+    let read = manifest::from_json(&json).unwrap();
+    assert_eq!(manifest::to_json(&read, &[]), json);
+    assert!(json.contains(r#""name": "Name with \"quotes\" and \\""#), "{}", json);
+
+    assert!(manifest::from_json("{}").is_none());
+    assert!(manifest::from_json(&OLD_MANIFEST.replace("4E4E4E20", "4E4E4E")).is_none());
+}
+
+#[test]
+fn extraction_manifest_is_read_back() {
+    let section = PfsBuilderSection {
+        guid : "FD041960-0DC8-4B9F-8225-BBA9E37C71E0".parse().unwrap(),
+        header_version : 1,
+        version : PfsVersion { types : *b"NNNN", numbers : [1, 2, 3, 4] },
+        reserved : 0,
+        unknown : [0x5A; 16],
+        data : b"data".to_vec(),
+        data_sig : b"signature".to_vec(),
+        meta : Vec::new(),
+        meta_sig : Vec::new(),
+    };
+    let data = PfsBuilder { header_version : 1, sections : vec![section] }.build().unwrap();
+    let mut options = Options::default();
+    options.manifest = true;
+    let extraction = extract::extract_in_memory(&options, &data);
+    assert!(matches!(extraction.result, Ok(true)), "{:?}", extraction.result);
+
+    let json = manifest::to_json(&extraction.manifest, &extraction.unparsed);
+    let read = manifest::from_json(&json).unwrap();
+    assert_eq!(read.len(), 1);
+    assert_eq!(read[0].unknown, [0x5A; 16]);
+    assert_eq!(read[0].data_sig_file, extraction.manifest[0].data_sig_file);
+    assert_eq!(read[0].files.len(), extraction.manifest[0].files.len());
+    assert_eq!(manifest::to_json(&read, &extraction.unparsed), json);
+}
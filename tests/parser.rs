@@ -0,0 +1,375 @@
+//
+// Parsers checked against known bytes
+//
+extern crate pfsextractor;
+
+use pfsextractor::compression::{self, Compression};
+use pfsextractor::parser;
+use pfsextractor::{Guid, InputKind, PfsBuilder, PfsBuilderSection, PfsVersion};
+
+const GUID_TEXT : &str = "FD041960-0DC8-4B9F-8225-BBA9E37C71E0";
+
+fn known_guid() -> Guid {
+    Guid { data1 : 0xFD041960, data2 : 0x0DC8, data3 : 0x4B9F, data4 : [0x82, 0x25, 0xBB, 0xA9, 0xE3, 0x7C, 0x71, 0xE0] }
+}
+
+#[test]
+fn guid_display_parses_back() {
+    let guid = known_guid();
+    assert_eq!(guid.to_string(), GUID_TEXT);
+    let parsed : Guid = guid.to_string().parse().unwrap();
+    assert_eq!(parsed, guid);
+    assert_eq!(parsed.to_string(), GUID_TEXT);
+}
+
+#[test]
+fn guid_parses_from_bytes_and_text_alike() {
+    let bytes = [0x60, 0x19, 0x04, 0xFD, 0xC8, 0x0D, 0x9F, 0x4B, 0x82, 0x25, 0xBB, 0xA9, 0xE3, 0x7C, 0x71, 0xE0];
+    let (rest, guid) = parser::guid(&bytes).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(guid, GUID_TEXT.parse().unwrap());
+}
+
+#[test]
+fn guid_accepts_braces_case_and_spaces() {
+    for text in ["{FD041960-0DC8-4B9F-8225-BBA9E37C71E0}", "fd041960-0dc8-4b9f-8225-bba9e37c71e0",
+                 "{fD041960-0Dc8-4b9F-8225-BbA9e37C71E0}", "  FD041960-0DC8-4B9F-8225-BBA9E37C71E0\n"] {
+        assert_eq!(text.parse::<Guid>(), Ok(known_guid()), "{:?}", text);
+    }
+}
+
+#[test]
+fn guid_rejects_malformed_text() {
+    let malformed = [
+        "",
+        "{}",
+        // Brace on one side only
+        "{FD041960-0DC8-4B9F-8225-BBA9E37C71E0",
+        "FD041960-0DC8-4B9F-8225-BBA9E37C71E0}",
+        // Groups of wrong length
+        "FD04196-0DC8-4B9F-8225-BBA9E37C71E00",
+        "FD041960-0DC80-4B9F-8225-BBA9E37C71E",
+        "FD041960-0DC8-4B9F-822-5BBA9E37C71E0",
+        "FD041960-0DC8-4B9F-8225-BBA9E37C71E",
+        "FD041960-0DC8-4B9F-8225-BBA9E37C71E00",
+        // Wrong number of groups
+        "FD0419600DC84B9F8225BBA9E37C71E0",
+        "FD041960-0DC8-4B9F-8225BBA9E37C71E0",
+        "FD041960-0DC8-4B9F-8225-BBA9-E37C71E0",
+        // Not hex
+        "FD04196G-0DC8-4B9F-8225-BBA9E37C71E0",
+        "FD041960-0DC8-4B9F-8225-BBA9E37C71EZ",
+        "+D041960-0DC8-4B9F-8225-BBA9E37C71E0",
+        "FD041960-+DC8-4B9F-8225-BBA9E37C71E0",
+        "FD041960-0DC8-4B9F-8225-BBA9E37C71É",
+    ];
+    for text in malformed {
+        assert_eq!(text.parse::<Guid>(), Err(parser::ParseGuidError), "{:?}", text);
+    }
+}
+
+fn section_with_data(data1 : u32, data : &[u8]) -> PfsBuilderSection {
+    PfsBuilderSection {
+        guid : Guid { data1, ..known_guid() },
+        header_version : 1,
+        version : PfsVersion { types : *b"NNNN", numbers : [1, 2, 3, 4] },
+        reserved : 0,
+        unknown : [0; 16],
+        data : data.to_vec(),
+        data_sig : Vec::new(),
+        meta : Vec::new(),
+        meta_sig : Vec::new(),
+    }
+}
+
+// Footer with the given data size, checksum is not checked by the parser
+fn fake_footer(data_size : u32) -> Vec<u8> {
+    let mut footer = data_size.to_le_bytes().to_vec();
+    footer.extend_from_slice(&[0; 4]);
+    footer.extend_from_slice(b"PFS.FTR.");
+    footer
+}
+
+#[test]
+fn footer_tag_in_section_data_does_not_end_file() {
+    let mut data = b"data in front".to_vec();
+    data.extend_from_slice(b"PFS.FTR.");
+    // Complete footer matching the size of everything parsed so far
+    data.extend_from_slice(&fake_footer(0x48 + 21));
+    data.extend_from_slice(b"data behind");
+    let builder = PfsBuilder { header_version : 1, sections : vec![section_with_data(1, &data), section_with_data(2, b"second")] };
+    let bytes = builder.build().unwrap();
+
+    let (rest, file) = parser::pfs_file(&bytes).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(file.sections.len(), 2);
+    assert_eq!(file.sections[0].data, Some(&data[..]));
+    assert_eq!(file.sections[1].data, Some(&b"second"[..]));
+    assert_eq!(file.footer.data_size, file.header.data_size);
+}
+
+#[test]
+fn footer_tag_in_section_data_is_not_a_subsection() {
+    let mut data = b"PFS.HDR.".to_vec();
+    data.extend_from_slice(&1u32.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(b"not a section");
+    data.extend_from_slice(&fake_footer(0));
+    assert_eq!(parser::section_kind(&data), parser::SectionKind::Unknown);
+    assert_eq!(parser::section_kind(b"PFS.FTR."), parser::SectionKind::Unknown);
+}
+
+// Compressed section around the given zlib stream, with the signature given as magic
+fn compressed_with_magic(magic : &[u8], stream : &[u8]) -> Vec<u8> {
+    let mut data = (stream.len() as u32).to_le_bytes().to_vec();
+    data.extend_from_slice(magic);
+    data.push(0);
+    data.extend_from_slice(stream);
+    data.extend_from_slice(parser::PFS_COMPRESSED_FOOTER);
+    data.extend_from_slice(&[0; 5]);
+    data
+}
+
+#[test]
+fn compressed_section_needs_exact_magic() {
+    let stream = compression::compress(Compression::Zlib, b"compressed section data");
+    let valid = compressed_with_magic(parser::PFS_COMPRESSED_SIGNATURE, &stream);
+    let (rest, section) = parser::pfs_compressed_section(&valid).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(section.data, &stream[..]);
+
+    // Size field stays plausible while every byte of the magic is wrong in turn
+    for i in 0..parser::PFS_COMPRESSED_SIGNATURE.len() {
+        let mut magic = parser::PFS_COMPRESSED_SIGNATURE.to_vec();
+        magic[i] ^= 0x01;
+        assert!(parser::pfs_compressed_section(&compressed_with_magic(&magic, &stream)).is_err(), "byte {}", i);
+    }
+    // Magic cut short, with and without data behind it
+    for len in 0..parser::PFS_COMPRESSED_SIGNATURE.len() {
+        let magic = &parser::PFS_COMPRESSED_SIGNATURE[..len];
+        assert!(parser::pfs_compressed_section(&compressed_with_magic(magic, &stream)).is_err(), "length {}", len);
+        let mut truncated = (stream.len() as u32).to_le_bytes().to_vec();
+        truncated.extend_from_slice(magic);
+        assert!(parser::pfs_compressed_section(&truncated).is_err(), "length {}", len);
+    }
+}
+
+#[test]
+fn headerless_sections_stop_at_trailing_junk() {
+    let builder = PfsBuilder { header_version : 1, sections : vec![section_with_data(1, b"first"), section_with_data(2, b"second")] };
+    let bytes = builder.build().unwrap();
+    let (_, file) = parser::pfs_file(&bytes).unwrap();
+    let sections = &bytes[file.sections_range()];
+
+    // Junk that is too short for a section, and a section header declaring more data than there is
+    let mut cut_section = sections[..parser::PFS_SECTION_HEADER_SIZE].to_vec();
+    cut_section[0x28..0x2C].copy_from_slice(&0x100u32.to_le_bytes());
+    for junk in [&b""[..], &[0xFF; 0x10][..], &cut_section[..]] {
+        let mut data = sections.to_vec();
+        data.extend_from_slice(junk);
+        let (headerless, rest) = parser::pfs_headerless(&data);
+        assert_eq!(rest, junk);
+        assert_eq!(headerless.sections, file.sections);
+        assert_eq!(headerless.header.data_size as usize, sections.len());
+        assert_eq!(headerless.footer.data_size as usize, sections.len());
+    }
+
+    let (headerless, rest) = parser::pfs_headerless(b"not a section");
+    assert!(headerless.sections.is_empty());
+    assert_eq!(rest, b"not a section");
+}
+
+// Headerless and truncated files have no header or footer of their own to point to
+#[test]
+fn file_ranges_cover_parts_present_in_input() {
+    let bytes = PfsBuilder { header_version : 1, sections : vec![section_with_data(1, b"first"), section_with_data(2, b"second")] }.build().unwrap();
+    let (_, file) = parser::pfs_file(&bytes).unwrap();
+    assert_eq!(file.header_range(), Some(0..parser::PFS_HEADER_SIZE));
+    assert_eq!(file.footer_range(), Some(bytes.len() - parser::PFS_FOOTER_SIZE..bytes.len()));
+    assert_eq!(file.size(), bytes.len());
+    let sections = file.sections_range();
+
+    let (headerless, _) = parser::pfs_headerless(&bytes[sections.clone()]);
+    assert_eq!(headerless.header_range(), None);
+    assert_eq!(headerless.sections_range(), 0..sections.len());
+    assert_eq!(headerless.footer_range(), None);
+    assert_eq!(headerless.size(), sections.len());
+
+    let truncated = parser::pfs_truncated(&bytes[..bytes.len() - 1]).unwrap();
+    assert_eq!(truncated.header_range(), Some(0..parser::PFS_HEADER_SIZE));
+    assert_eq!(truncated.sections_range(), sections);
+    assert_eq!(truncated.footer_range(), None);
+    assert_eq!(truncated.size(), sections.end);
+}
+
+#[test]
+fn classify_pfs_file() {
+    let bytes = PfsBuilder { header_version : 1, sections : vec![section_with_data(1, b"data")] }.build().unwrap();
+    assert_eq!(parser::classify(&bytes), InputKind::Pfs);
+    // Header alone is enough, the rest may be damaged
+    assert_eq!(parser::classify(&bytes[..parser::PFS_HEADER_SIZE]), InputKind::Pfs);
+}
+
+#[test]
+fn classify_compressed_section() {
+    let stream = compression::compress(Compression::Zlib, b"compressed section data");
+    assert_eq!(parser::classify(&compressed_with_magic(parser::PFS_COMPRESSED_SIGNATURE, &stream)), InputKind::CompressedSection);
+}
+
+#[test]
+fn classify_uefi_capsule() {
+    // EFI_FMP_CAPSULE_GUID, header size, flags and image size
+    let mut capsule = vec![0xED, 0xD5, 0xCB, 0x6D, 0x2D, 0xE8, 0x44, 0x4C, 0xBD, 0xA1, 0x71, 0x94, 0x19, 0x9A, 0xD9, 0x2A];
+    capsule.extend_from_slice(&0x20u32.to_le_bytes());
+    capsule.extend_from_slice(&0x50000u32.to_le_bytes());
+    capsule.extend_from_slice(&0x30u32.to_le_bytes());
+    capsule.extend_from_slice(&[0; 0x10]);
+    assert_eq!(parser::classify(&capsule), InputKind::UefiCapsule);
+}
+
+#[test]
+fn classify_pe_image() {
+    assert_eq!(parser::classify(b"MZ\x90\x00\x03\x00\x00\x00\x04\x00\x00\x00\xFF\xFF\x00\x00"), InputKind::PeImage);
+}
+
+#[test]
+fn classify_gzip() {
+    assert_eq!(parser::classify(b"\x1F\x8B\x08\x00\x00\x00\x00\x00\x00\x03"), InputKind::Gzip);
+}
+
+#[test]
+fn classify_zip() {
+    assert_eq!(parser::classify(b"PK\x03\x04\x14\x00\x00\x00\x08\x00"), InputKind::Zip);
+}
+
+#[test]
+fn classify_unknown() {
+    let mut zero_version = b"PFS.HDR.".to_vec();
+    zero_version.extend_from_slice(&[0; 8]);
+    for data in [&b""[..], b"M", b"PK\x05\x06", b"PFS.HDR", &zero_version, &[0xFF; 0x20]] {
+        assert_eq!(parser::classify(data), InputKind::Unknown, "{:?}", data);
+    }
+}
+
+#[test]
+fn header_from_known_bytes() {
+    let bytes = b"PFS.HDR.\x01\x00\x00\x00\x48\x00\x00\x00rest";
+    let (rest, header) = parser::pfs_header(bytes).unwrap();
+    assert_eq!(rest, b"rest");
+    assert_eq!(header, parser::PfsHeader { header_version : 1, data_size : 0x48 });
+    assert!(parser::pfs_header(b"PFS.HDR.\x01\x00\x00\x00\x48\x00\x00").is_err());
+    assert!(parser::pfs_header(b"PFS.HDX.\x01\x00\x00\x00\x48\x00\x00\x00").is_err());
+}
+
+#[test]
+fn footer_from_known_bytes() {
+    let bytes = b"\x48\x00\x00\x00\x78\x56\x34\x12PFS.FTR.rest";
+    let (rest, footer) = parser::pfs_footer(bytes).unwrap();
+    assert_eq!(rest, b"rest");
+    assert_eq!(footer, parser::PfsFooter { checksum : 0x12345678, data_size : 0x48 });
+    assert!(parser::pfs_footer(b"\x48\x00\x00\x00\x78\x56\x34\x12PFS.FTR").is_err());
+}
+
+#[test]
+fn section_from_known_bytes() {
+    let mut bytes = vec![
+        0x60, 0x19, 0x04, 0xFD, 0xC8, 0x0D, 0x9F, 0x4B, 0x82, 0x25, 0xBB, 0xA9, 0xE3, 0x7C, 0x71, 0xE0, // GUID
+        0x01, 0x00, 0x00, 0x00, // Header version
+        b'N', b'N', b'A', b' ', // Version types
+        0x01, 0x00, 0x02, 0x00, 0xAB, 0x00, 0x00, 0x00, // Version numbers
+        0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, // Reserved
+        0x04, 0x00, 0x00, 0x00, // Data size
+        0x02, 0x00, 0x00, 0x00, // Data signature size
+        0x00, 0x00, 0x00, 0x00, // Metadata size
+        0x01, 0x00, 0x00, 0x00, // Metadata signature size
+    ];
+    bytes.extend_from_slice(&[0xCC; 16]);
+    bytes.extend_from_slice(b"DATASGMrest");
+    let (rest, section) = parser::pfs_section(&bytes).unwrap();
+    assert_eq!(rest, b"rest");
+    assert_eq!(section.guid, known_guid());
+    assert_eq!(section.header_version, 1);
+    assert_eq!(section.version, PfsVersion { types : *b"NNA ", numbers : [1, 2, 0xAB, 0] });
+    assert_eq!(section.version.to_string(), "1.2.AB");
+    assert_eq!(section.reserved, 0x8877665544332211);
+    assert_eq!((section.data_size, section.data_sig_size, section.meta_size, section.meta_sig_size), (4, 2, 0, 1));
+    assert_eq!(section.unknown, [0xCC; 16]);
+    assert_eq!(section.data, Some(&b"DATA"[..]));
+    assert_eq!(section.data_sig, Some(&b"SG"[..]));
+    assert_eq!(section.meta, None);
+    assert_eq!(section.meta_sig, Some(&b"M"[..]));
+    assert_eq!(section.size(), parser::PFS_SECTION_HEADER_SIZE + 7);
+
+    // Parts are cut short
+    assert!(parser::pfs_section(&bytes[..parser::PFS_SECTION_HEADER_SIZE + 6]).is_err());
+}
+
+#[test]
+fn compressed_section_from_known_bytes() {
+    let mut bytes = vec![0x03, 0x00, 0x00, 0x00];
+    bytes.extend_from_slice(b"\xAA\xEE\xAA\x76\x1B\xEC\xBB\x20\xF1\xE6\x51");
+    bytes.push(0x01);
+    bytes.extend_from_slice(b"xyz");
+    bytes.extend_from_slice(b"\xEE\xAA\xEE\x8F\x49\x1B\xE8\xAE\x14\x37\x90\x00\x00\x00\x00\x00");
+    let (rest, section) = parser::pfs_compressed_section(&bytes).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(section.size, 3);
+    assert_eq!(section.data, b"xyz");
+    assert_eq!(section.signature, None);
+    assert_eq!(section.footer.len(), parser::PFS_COMPRESSED_FOOTER_SIZE);
+
+    // Bytes in front of the footer are its signature
+    let mut signed = bytes[..0x13].to_vec();
+    signed.extend_from_slice(b"SIG");
+    signed.extend_from_slice(&bytes[0x13..]);
+    let (_, section) = parser::pfs_compressed_section(&signed).unwrap();
+    assert_eq!(section.data, b"xyz");
+    assert_eq!(section.signature, Some(&b"SIG"[..]));
+}
+
+#[test]
+fn recompressed_section_drops_old_signature() {
+    let stream = compression::compress(Compression::Zlib, b"old data");
+    let plain = compressed_with_magic(parser::PFS_COMPRESSED_SIGNATURE, &stream);
+    let mut signed = plain[..0x10 + stream.len()].to_vec();
+    signed.extend_from_slice(b"SIG");
+    signed.extend_from_slice(&plain[0x10 + stream.len()..]);
+
+    let rebuilt = pfsextractor::builder::pfs_recompress(&signed, b"new data").unwrap();
+    let (rest, section) = parser::pfs_compressed_section(&rebuilt).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(section.signature, None);
+    assert_eq!(compression::decompress(section.data, 0x1000).unwrap(), b"new data");
+    assert_eq!(section.footer, &plain[0x10 + stream.len()..]);
+}
+
+#[test]
+fn info_section_from_known_bytes() {
+    let mut bytes = vec![
+        0x01, 0x00, 0x00, 0x00, // Header version
+        0x60, 0x19, 0x04, 0xFD, 0xC8, 0x0D, 0x9F, 0x4B, 0x82, 0x25, 0xBB, 0xA9, 0xE3, 0x7C, 0x71, 0xE0, // GUID
+        0x01, 0x00, 0x11, 0x00, 0x00, 0x00, 0x00, 0x00, // Version numbers
+        b'N', b'N', 0x00, 0x00, // Version types
+        0x04, 0x00, // Name length
+        b'B', 0, b'I', 0, b'O', 0, b'S', 0, 0, 0, // Name with its terminator
+    ];
+    // Extended record, fields after the name run up to the next record
+    let mut second = bytes.clone();
+    second[0] = 0x02;
+    second[4] = 0x61;
+    second.extend_from_slice(&[0xEE; 6]);
+    bytes.extend_from_slice(&second);
+
+    let (rest, records) = parser::pfs_info(&bytes).unwrap();
+    assert!(rest.is_empty());
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].header_version, 1);
+    assert_eq!(records[0].guid, known_guid());
+    assert_eq!(records[0].version, PfsVersion { types : [b'N', b'N', 0, 0], numbers : [1, 0x11, 0, 0] });
+    assert_eq!(records[0].name, "BIOS");
+    assert!(records[0].name_terminated);
+    assert!(records[0].extra.is_empty());
+    assert_eq!(records[1].header_version, parser::PFS_INFO_VERSION_EXTENDED);
+    assert_eq!(records[1].guid, Guid { data1 : 0xFD041961, ..known_guid() });
+    assert_eq!(records[1].extra, [0xEE; 6]);
+}
@@ -0,0 +1,219 @@
+//
+// Generator of synthetic PFS files, sections are random but always valid
+//
+// Every test crate uses only part of it
+#![allow(dead_code)]
+
+use pfsextractor::builder::{pfs_recompress, PfsBuilder, PfsBuilderSection};
+use pfsextractor::compression::{self, Compression};
+use pfsextractor::parser::{self, PFS_CHUNK_HEADER_SIZE};
+use pfsextractor::{Guid, PfsVersion};
+use proptest::collection::vec;
+use proptest::prelude::*;
+
+// Data of a generated section, nested variants are built into bytes when the file is
+#[derive(Debug, Clone)]
+pub enum GenData {
+    Raw(Vec<u8>),
+    // Payload split into chunks, stored in the subsection in the given order
+    Chunked(Vec<Vec<u8>>, Vec<usize>),
+    Compressed(Box<GenData>, Compression),
+    Subsection(GenFile),
+}
+
+#[derive(Debug, Clone)]
+pub struct GenSection {
+    pub guid : Guid,
+    pub version : PfsVersion,
+    pub data : GenData,
+    pub data_sig : Vec<u8>,
+    pub meta : Vec<u8>,
+    pub meta_sig : Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GenFile {
+    pub sections : Vec<GenSection>,
+    // Name of every section in the information section appended as the last one, none if empty
+    pub names : Vec<String>,
+}
+
+// Compressed section of no data, pfs_recompress compresses the same way and keeps the layout
+fn compressed_template(kind : Compression) -> Vec<u8> {
+    let stream = compression::compress(kind, &[]);
+    let mut template = (stream.len() as u32).to_le_bytes().to_vec();
+    template.extend_from_slice(parser::PFS_COMPRESSED_SIGNATURE);
+    template.push(0);
+    template.extend_from_slice(&stream);
+    template.extend_from_slice(&[0u8; 16]);
+    template
+}
+
+fn chunk(data : &[u8], order : u16, total : u16, payload_size : usize) -> Vec<u8> {
+    let mut header = vec![0u8; PFS_CHUNK_HEADER_SIZE];
+    header[0x30..0x34].copy_from_slice(&(data.len() as u32).to_le_bytes());
+    header[0x38..0x3C].copy_from_slice(&(payload_size as u32).to_le_bytes());
+    header[0x3C..0x3E].copy_from_slice(&total.to_le_bytes());
+    header[0x3E..0x40].copy_from_slice(&order.to_le_bytes());
+    header.extend_from_slice(data);
+    header
+}
+
+fn guid(data1 : u32) -> Guid {
+    Guid { data1, data2 : 0, data3 : 0, data4 : [0; 8] }
+}
+
+fn guid_bytes(guid : &Guid) -> Vec<u8> {
+    let mut bytes = guid.data1.to_le_bytes().to_vec();
+    bytes.extend_from_slice(&guid.data2.to_le_bytes());
+    bytes.extend_from_slice(&guid.data3.to_le_bytes());
+    bytes.extend_from_slice(&guid.data4);
+    bytes
+}
+
+fn info_section(sections : &[GenSection], names : &[String]) -> Vec<u8> {
+    let mut info = Vec::new();
+    for (section, name) in sections.iter().zip(names) {
+        let name : Vec<u16> = name.encode_utf16().collect();
+        info.extend_from_slice(&1u32.to_le_bytes());
+        info.extend_from_slice(&guid_bytes(&section.guid));
+        for n in &section.version.numbers {
+            info.extend_from_slice(&n.to_le_bytes());
+        }
+        info.extend_from_slice(&section.version.types);
+        info.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        for c in name {
+            info.extend_from_slice(&c.to_le_bytes());
+        }
+        info.extend_from_slice(&[0, 0]);
+    }
+    info
+}
+
+impl GenData {
+    pub fn build(&self) -> Vec<u8> {
+        match *self {
+            GenData::Raw(ref data) => data.clone(),
+            GenData::Chunked(ref chunks, ref order) => {
+                let payload_size = chunks.iter().map(Vec::len).sum();
+                let sections = order.iter().map(|&i| PfsBuilderSection {
+                    data : chunk(&chunks[i], i as u16, chunks.len() as u16, payload_size),
+                    ..section(guid(0))
+                });
+                PfsBuilder { header_version : 1, sections : sections.collect() }.build().unwrap()
+            }
+            GenData::Compressed(ref data, kind) => pfs_recompress(&compressed_template(kind), &data.build()).unwrap(),
+            GenData::Subsection(ref file) => file.build(),
+        }
+    }
+}
+
+fn section(guid : Guid) -> PfsBuilderSection {
+    PfsBuilderSection {
+        guid,
+        header_version : 1,
+        version : PfsVersion { types : [0; 4], numbers : [0; 4] },
+        reserved : 0,
+        unknown : [0; 16],
+        data : Vec::new(),
+        data_sig : Vec::new(),
+        meta : Vec::new(),
+        meta_sig : Vec::new(),
+    }
+}
+
+impl GenFile {
+    pub fn build(&self) -> Vec<u8> {
+        let mut sections : Vec<PfsBuilderSection> = self.sections.iter().map(|s| PfsBuilderSection {
+            version : s.version,
+            data : s.data.build(),
+            data_sig : s.data_sig.clone(),
+            meta : s.meta.clone(),
+            meta_sig : s.meta_sig.clone(),
+            ..section(s.guid)
+        }).collect();
+        if !self.names.is_empty() {
+            sections.push(PfsBuilderSection {
+                data : info_section(&self.sections, &self.names),
+                ..section(guid(0x0D0D0D0D))
+            });
+        }
+        PfsBuilder { header_version : 1, sections }.build().unwrap()
+    }
+}
+
+fn gen_bytes(max : usize) -> impl Strategy<Value = Vec<u8>> {
+    vec(any::<u8>(), 0..max)
+}
+
+fn gen_version() -> impl Strategy<Value = PfsVersion> {
+    let types = prop_oneof![Just(parser::PFS_VERSION_TYPE_DECIMAL), Just(parser::PFS_VERSION_TYPE_HEX), Just(b' ')];
+    (proptest::array::uniform4(types), any::<[u16; 4]>()).prop_map(|(types, numbers)| PfsVersion { types, numbers })
+}
+
+// Random raw data is practically never taken for a PFS file or compressed section
+fn gen_leaf() -> impl Strategy<Value = GenData> {
+    let raw = gen_bytes(0x100).prop_map(GenData::Raw);
+    let chunked = vec(vec(any::<u8>(), 1..0x40), 1..5)
+        .prop_flat_map(|chunks| {
+            let order = Just((0..chunks.len()).collect::<Vec<_>>()).prop_shuffle();
+            (Just(chunks), order)
+        })
+        .prop_map(|(chunks, order)| GenData::Chunked(chunks, order));
+    prop_oneof![raw, chunked]
+}
+
+fn gen_sections(data : impl Strategy<Value = GenData>) -> impl Strategy<Value = Vec<GenSection>> {
+    // GUIDs are unique in a file, so names are matched to sections by them
+    vec((gen_version(), data, gen_bytes(0x20), gen_bytes(0x20), gen_bytes(0x20)), 1..5).prop_map(|sections| {
+        sections.into_iter().enumerate().map(|(i, (version, data, data_sig, meta, meta_sig))| GenSection {
+            guid : guid(i as u32 + 1),
+            version,
+            data,
+            data_sig,
+            meta,
+            meta_sig,
+        }).collect()
+    })
+}
+
+fn gen_names(sections : Vec<GenSection>) -> impl Strategy<Value = GenFile> {
+    let count = sections.len();
+    let names = prop_oneof![Just(Vec::new()), vec("[A-Za-z0-9 ]{1,16}", count)];
+    (Just(sections), names).prop_map(|(sections, names)| GenFile { sections, names })
+}
+
+fn gen_compression() -> impl Strategy<Value = Compression> {
+    prop_oneof![4 => Just(Compression::Zlib), 1 => Just(Compression::Lzma), 1 => Just(Compression::Xz)]
+}
+
+// Files nested up to three levels deep through compressed sections and subsections
+pub fn gen_file() -> impl Strategy<Value = GenFile> {
+    let data = gen_leaf().prop_recursive(3, 16, 4, |inner| {
+        prop_oneof![
+            2 => gen_leaf(),
+            1 => (inner.clone(), gen_compression()).prop_map(|(data, kind)| GenData::Compressed(Box::new(data), kind)),
+            1 => gen_sections(inner).prop_flat_map(gen_names).prop_map(GenData::Subsection),
+        ]
+    });
+    gen_sections(data).prop_flat_map(gen_names)
+}
+
+// Section with the given data and nothing else, for fixtures built by hand
+pub fn data_section(data1 : u32, data : GenData) -> GenSection {
+    GenSection {
+        guid : guid(data1),
+        version : PfsVersion { types : [0; 4], numbers : [0; 4] },
+        data,
+        data_sig : Vec::new(),
+        meta : Vec::new(),
+        meta_sig : Vec::new(),
+    }
+}
+
+// Data split into chunks of at most size bytes, stored in order
+pub fn chunked(data : &[u8], size : usize) -> GenData {
+    let chunks : Vec<Vec<u8>> = data.chunks(size).map(<[u8]>::to_vec).collect();
+    let order = (0..chunks.len()).collect();
+    GenData::Chunked(chunks, order)
+}
@@ -0,0 +1,130 @@
+//
+// Properties of generated PFS files: parsing gives back what was generated,
+// rebuilding gives the same bytes and extraction never panics
+//
+extern crate pfsextractor;
+extern crate proptest;
+
+mod pfs_gen;
+
+use pfs_gen::gen_file;
+use pfsextractor::annotate;
+use pfsextractor::extract::{self, Options};
+use pfsextractor::guids::GuidNames;
+use pfsextractor::parser;
+use pfsextractor::{HandlerContext, HandlerOutcome, PfsBuilder, PfsError, PfsReader, PfsSection, SectionHandler};
+use proptest::prelude::*;
+
+fn options() -> Options {
+    Options::builder().jobs(1).build()
+}
+
+// Takes every section, so nothing is left for built-in handling
+struct TakeAll;
+
+impl SectionHandler for TakeAll {
+    fn matches(&self, _section : &PfsSection) -> bool {
+        true
+    }
+
+    fn handle(&self, ctx : &mut HandlerContext, data : &[u8]) -> Result<HandlerOutcome, PfsError> {
+        ctx.write_file("handled", data)?;
+        Ok(HandlerOutcome::Handled)
+    }
+}
+
+proptest! {
+    #[test]
+    fn parse_gives_generated_sections(file in gen_file()) {
+        let data = file.build();
+        let (rest, mut parsed) = parser::pfs_file(&data).unwrap();
+        prop_assert!(rest.is_empty());
+        extract::pfs_set_names(&mut parsed, &GuidNames::default());
+
+        let info = if file.names.is_empty() { 0 } else { 1 };
+        prop_assert_eq!(parsed.sections.len(), file.sections.len() + info);
+        for (i, (section, generated)) in parsed.sections.iter().zip(&file.sections).enumerate() {
+            prop_assert_eq!(section.guid, generated.guid);
+            prop_assert_eq!(section.version, generated.version);
+            prop_assert_eq!(section.data.unwrap_or_default(), &generated.data.build()[..]);
+            prop_assert_eq!(section.data_sig.unwrap_or_default(), &generated.data_sig[..]);
+            prop_assert_eq!(section.meta.unwrap_or_default(), &generated.meta[..]);
+            prop_assert_eq!(section.meta_sig.unwrap_or_default(), &generated.meta_sig[..]);
+            if let Some(name) = file.names.get(i) {
+                prop_assert_eq!(&section.name, name);
+            }
+        }
+    }
+
+    #[test]
+    fn rebuild_gives_same_bytes(file in gen_file()) {
+        let data = file.build();
+        let (_, parsed) = parser::pfs_file(&data).unwrap();
+        let rebuilt = PfsBuilder::from_file(&parsed).build().unwrap();
+        prop_assert_eq!(&rebuilt, &data);
+        let (_, reparsed) = parser::pfs_file(&rebuilt).unwrap();
+        prop_assert_eq!(reparsed, parsed);
+    }
+
+    // Lazy reader agrees with the parser on intact and damaged files
+    #[test]
+    fn reader_gives_parsed_sections(file in gen_file(), writes in proptest::collection::vec((any::<usize>(), any::<u8>()), 0..4)) {
+        let mut data = file.build();
+        for (offset, byte) in writes {
+            let len = data.len();
+            data[offset % len] = byte;
+        }
+        let read : Result<Vec<_>, _> = PfsReader::sections(&data).collect();
+        match parser::pfs_file(&data) {
+            Ok((_, parsed)) => prop_assert_eq!(read.unwrap(), parsed.sections),
+            Err(_) => prop_assert!(read.is_err()),
+        }
+    }
+
+    #[test]
+    fn annotated_regions_cover_file(file in gen_file()) {
+        let data = file.build();
+        // Regions of nested PFS files lie inside section data, the rest follow each other without gaps
+        let regions : Vec<_> = annotate::regions(&data).into_iter().filter(|r| !r.id.contains("_pfs_")).collect();
+        let mut offset = 0;
+        for region in &regions {
+            prop_assert_eq!(region.offset, offset);
+            offset += region.size;
+        }
+        prop_assert_eq!(offset, data.len());
+    }
+
+    #[test]
+    fn extraction_succeeds(file in gen_file()) {
+        let extraction = extract::extract_in_memory(&options(), &file.build());
+        prop_assert!(matches!(extraction.result, Ok(true)), "{:?}", extraction.result);
+        prop_assert!(!extraction.files.is_empty());
+    }
+
+    #[test]
+    fn handler_comes_before_built_in_handling(file in gen_file()) {
+        let data = file.build();
+        let mut options = options();
+        options.add_handler(TakeAll);
+        let extraction = extract::extract_in_memory(&options, &data);
+        prop_assert!(matches!(extraction.result, Ok(true)), "{:?}", extraction.result);
+
+        let (_, parsed) = parser::pfs_file(&data).unwrap();
+        let handled = extraction.files.iter().filter(|f| f.0.ends_with(".handled")).count();
+        prop_assert_eq!(handled, parsed.sections.iter().filter(|s| s.data.is_some()).count());
+        prop_assert!(!extraction.files.iter().any(|f| f.0.contains(".decompressed") || f.0.contains(".payload")));
+    }
+
+    // Damaged files may fail to extract, but they never take the extractor down
+    #[test]
+    fn extraction_never_panics(file in gen_file(), writes in proptest::collection::vec((any::<usize>(), any::<u8>()), 0..8), cut in any::<usize>()) {
+        let mut data = file.build();
+        for (offset, byte) in writes {
+            let len = data.len();
+            data[offset % len] = byte;
+        }
+        let len = data.len();
+        data.truncate(len - cut % (len / 4 + 1));
+        extract::extract_in_memory(&options(), &data);
+    }
+}